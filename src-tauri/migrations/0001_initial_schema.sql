@@ -0,0 +1,47 @@
+-- Schema as shipped before versioned migrations. Every statement is
+-- idempotent so databases created by older releases adopt it unchanged.
+
+CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT,
+    priority TEXT NOT NULL CHECK (priority IN ('low', 'medium', 'high', 'critical')),
+    status TEXT NOT NULL CHECK (status IN ('pending', 'inprogress', 'completed', 'paused', 'cancelled')),
+    category TEXT NOT NULL,
+    estimated_time INTEGER NOT NULL,
+    actual_time INTEGER,
+    due_date DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    completed_at DATETIME
+);
+
+CREATE TABLE IF NOT EXISTS ai_insights (
+    id TEXT PRIMARY KEY,
+    message TEXT NOT NULL,
+    insight_type TEXT NOT NULL,
+    confidence REAL NOT NULL,
+    created_at DATETIME NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS notifications (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    notification_type TEXT NOT NULL,
+    is_read BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL,
+    action_url TEXT
+);
+
+CREATE TABLE IF NOT EXISTS communication_activity (
+    id TEXT PRIMARY KEY,
+    service TEXT NOT NULL,
+    message_count INTEGER NOT NULL DEFAULT 0,
+    unread_count INTEGER NOT NULL DEFAULT 0,
+    last_activity DATETIME,
+    mentions INTEGER NOT NULL DEFAULT 0,
+    keywords_detected TEXT, -- JSON array
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
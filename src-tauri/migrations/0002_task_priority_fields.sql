@@ -0,0 +1,4 @@
+ALTER TABLE tasks ADD COLUMN priority_pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE tasks ADD COLUMN importance TEXT NOT NULL DEFAULT 'auto'
+    CHECK (importance IN ('auto', 'important', 'unimportant'));
@@ -0,0 +1,9 @@
+CREATE TABLE priority_escalations (
+    id TEXT PRIMARY KEY,
+    task_id TEXT NOT NULL,
+    task_title TEXT NOT NULL,
+    from_priority TEXT NOT NULL,
+    to_priority TEXT NOT NULL,
+    rule TEXT NOT NULL,
+    escalated_at DATETIME NOT NULL
+);
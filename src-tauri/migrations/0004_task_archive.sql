@@ -0,0 +1,27 @@
+CREATE TABLE archived_tasks (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT,
+    priority TEXT NOT NULL,
+    priority_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    importance TEXT NOT NULL DEFAULT 'auto',
+    status TEXT NOT NULL CHECK (status IN ('completed', 'cancelled')),
+    category TEXT NOT NULL,
+    estimated_time INTEGER NOT NULL,
+    actual_time INTEGER,
+    due_date DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    completed_at DATETIME,
+    archived_at DATETIME NOT NULL
+);
+
+-- Live and archived tasks together, for historical statistics
+CREATE VIEW task_history AS
+SELECT id, title, description, priority, priority_pinned, importance, status,
+       category, estimated_time, actual_time, due_date, created_at, updated_at, completed_at
+FROM tasks
+UNION ALL
+SELECT id, title, description, priority, priority_pinned, importance, status,
+       category, estimated_time, actual_time, due_date, created_at, updated_at, completed_at
+FROM archived_tasks;
@@ -0,0 +1,8 @@
+-- Holds a single row while encryption at rest is enabled. The key itself is
+-- never stored; it is derived from the passphrase and the salt.
+CREATE TABLE encryption (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    salt TEXT NOT NULL,
+    verifier TEXT NOT NULL,
+    created_at DATETIME NOT NULL
+);
@@ -0,0 +1,13 @@
+ALTER TABLE tasks ADD COLUMN project TEXT;
+ALTER TABLE archived_tasks ADD COLUMN project TEXT;
+
+DROP VIEW task_history;
+
+CREATE VIEW task_history AS
+SELECT id, title, description, priority, priority_pinned, importance, status,
+       category, project, estimated_time, actual_time, due_date, created_at, updated_at, completed_at
+FROM tasks
+UNION ALL
+SELECT id, title, description, priority, priority_pinned, importance, status,
+       category, project, estimated_time, actual_time, due_date, created_at, updated_at, completed_at
+FROM archived_tasks;
@@ -0,0 +1,11 @@
+CREATE TABLE achievements (
+    id TEXT PRIMARY KEY,
+    unlocked_at DATETIME NOT NULL
+);
+
+CREATE TABLE streaks (
+    name TEXT PRIMARY KEY,
+    current INTEGER NOT NULL,
+    best INTEGER NOT NULL,
+    last_date DATE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE ai_insights ADD COLUMN expires_at DATETIME;
+
+CREATE INDEX idx_ai_insights_created_at ON ai_insights (created_at);
@@ -0,0 +1,12 @@
+CREATE TABLE communication_snapshots (
+    id TEXT PRIMARY KEY,
+    service TEXT NOT NULL,
+    message_count INTEGER NOT NULL,
+    unread_count INTEGER NOT NULL,
+    mentions INTEGER NOT NULL,
+    keywords_detected TEXT NOT NULL, -- JSON array
+    captured_at DATETIME NOT NULL
+);
+
+CREATE INDEX idx_communication_snapshots_captured_at
+    ON communication_snapshots (captured_at, service);
@@ -0,0 +1,14 @@
+-- Columns the task list, statistics, archive job and inbox filter or sort on
+
+CREATE INDEX idx_tasks_status ON tasks (status);
+CREATE INDEX idx_tasks_due_date ON tasks (due_date);
+CREATE INDEX idx_tasks_created_at ON tasks (created_at);
+CREATE INDEX idx_tasks_completed_at ON tasks (completed_at);
+CREATE INDEX idx_tasks_category ON tasks (category);
+
+CREATE INDEX idx_archived_tasks_archived_at ON archived_tasks (archived_at);
+CREATE INDEX idx_archived_tasks_created_at ON archived_tasks (created_at);
+CREATE INDEX idx_archived_tasks_completed_at ON archived_tasks (completed_at);
+CREATE INDEX idx_archived_tasks_category ON archived_tasks (category);
+
+CREATE INDEX idx_notifications_created_at ON notifications (created_at, id);
@@ -0,0 +1,9 @@
+-- A random id for this database, so state kept outside it, such as folder
+-- sync progress, can tell when it is looking at a different database.
+-- Restoring a backup gives the restored copy a new id.
+CREATE TABLE database_identity (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    database_id TEXT NOT NULL
+);
+
+INSERT INTO database_identity (id, database_id) VALUES (1, lower(hex(randomblob(16))));
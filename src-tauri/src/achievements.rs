@@ -0,0 +1,587 @@
+use crate::error::Result;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use log::{error, info};
+use std::collections::HashSet;
+use tauri::Manager;
+
+use crate::models::*;
+use crate::notifications::NotificationManager;
+use crate::repository::Repositories;
+use crate::stats::TaskActivity;
+use crate::AppState;
+
+const COMPLETION_STREAK: &str = "completion";
+const ZERO_OVERDUE_STREAK: &str = "zero_overdue";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    TasksCreated,
+    TasksCompleted,
+    CompletedBeforeDue,
+    CompletionStreak,  // consecutive days with a completion
+    ZeroOverdueStreak, // consecutive days without an overdue task
+}
+
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub metric: Metric,
+    pub target: i32,
+}
+
+/// Every achievement that can be unlocked. Ids are stored, so never rename
+/// one that has shipped.
+pub const CATALOG: &[Achievement] = &[
+    Achievement {
+        id: "first_task",
+        name: "First Step",
+        description: "Create your first task",
+        metric: Metric::TasksCreated,
+        target: 1,
+    },
+    Achievement {
+        id: "first_completion",
+        name: "Done and Dusted",
+        description: "Complete your first task",
+        metric: Metric::TasksCompleted,
+        target: 1,
+    },
+    Achievement {
+        id: "tasks_completed_10",
+        name: "Getting Things Done",
+        description: "Complete 10 tasks",
+        metric: Metric::TasksCompleted,
+        target: 10,
+    },
+    Achievement {
+        id: "tasks_completed_100",
+        name: "Centurion",
+        description: "Complete 100 tasks",
+        metric: Metric::TasksCompleted,
+        target: 100,
+    },
+    Achievement {
+        id: "tasks_completed_500",
+        name: "Task Master",
+        description: "Complete 500 tasks",
+        metric: Metric::TasksCompleted,
+        target: 500,
+    },
+    Achievement {
+        id: "before_due_10",
+        name: "Ahead of Schedule",
+        description: "Complete 10 tasks before their due date",
+        metric: Metric::CompletedBeforeDue,
+        target: 10,
+    },
+    Achievement {
+        id: "streak_3",
+        name: "On a Roll",
+        description: "Complete a task 3 days in a row",
+        metric: Metric::CompletionStreak,
+        target: 3,
+    },
+    Achievement {
+        id: "streak_7",
+        name: "Week Warrior",
+        description: "Complete a task 7 days in a row",
+        metric: Metric::CompletionStreak,
+        target: 7,
+    },
+    Achievement {
+        id: "streak_30",
+        name: "Unstoppable",
+        description: "Complete a task 30 days in a row",
+        metric: Metric::CompletionStreak,
+        target: 30,
+    },
+    Achievement {
+        id: "zero_overdue_7",
+        name: "Clean Slate",
+        description: "Go a week without an overdue task",
+        metric: Metric::ZeroOverdueStreak,
+        target: 7,
+    },
+    Achievement {
+        id: "zero_overdue_30",
+        name: "Always On Time",
+        description: "Go 30 days without an overdue task",
+        metric: Metric::ZeroOverdueStreak,
+        target: 30,
+    },
+];
+
+/// The figures achievements are measured against.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub tasks_created: i32,
+    pub tasks_completed: i32,
+    pub completed_before_due: i32,
+    pub completion_streak: i32,
+    pub zero_overdue_streak: i32,
+}
+
+impl Metrics {
+    pub fn value(&self, metric: Metric) -> i32 {
+        match metric {
+            Metric::TasksCreated => self.tasks_created,
+            Metric::TasksCompleted => self.tasks_completed,
+            Metric::CompletedBeforeDue => self.completed_before_due,
+            Metric::CompletionStreak => self.completion_streak,
+            Metric::ZeroOverdueStreak => self.zero_overdue_streak,
+        }
+    }
+}
+
+fn new_streak(name: &str) -> Streak {
+    Streak {
+        name: name.to_string(),
+        current: 0,
+        best: 0,
+        last_date: None,
+    }
+}
+
+/// Counts `today` towards the streak if `kept`, or breaks it. A day is only
+/// counted once, and a day that broke the streak stays broken.
+pub fn record_day(streak: &mut Streak, today: NaiveDate, kept: bool) {
+    if streak.last_date == Some(today) {
+        if !kept {
+            streak.current = 0;
+        }
+        return;
+    }
+
+    streak.current = match (kept, streak.last_date) {
+        (false, _) => 0,
+        (true, Some(last)) if last == today - Duration::days(1) => streak.current + 1,
+        (true, _) => 1,
+    };
+    streak.best = streak.best.max(streak.current);
+    streak.last_date = Some(today);
+}
+
+/// The streak as of `today`: a run whose last day is before yesterday has
+/// lapsed.
+pub fn current_streak(streak: &Streak, today: NaiveDate) -> i32 {
+    match streak.last_date {
+        Some(last) if last >= today - Duration::days(1) => streak.current,
+        _ => 0,
+    }
+}
+
+/// Consecutive days with a completion, ending today, or yesterday if
+/// nothing has been completed yet today.
+pub fn completion_streak<Tz: TimeZone>(tasks: &[TaskActivity], now: &DateTime<Tz>) -> Streak {
+    let tz = now.timezone();
+    let days: HashSet<NaiveDate> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .filter_map(|t| t.completed_at)
+        .map(|dt| dt.with_timezone(&tz).date_naive())
+        .collect();
+
+    let today = now.date_naive();
+    let mut streak = new_streak(COMPLETION_STREAK);
+    let mut day = if days.contains(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    while days.contains(&day) {
+        streak.current += 1;
+        streak.last_date.get_or_insert(day);
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+fn is_overdue(task: &TaskActivity, now: DateTime<Utc>) -> bool {
+    !matches!(task.status, TaskStatus::Completed | TaskStatus::Cancelled)
+        && task.due_date.is_some_and(|due| due < now)
+}
+
+/// Counts what the catalog measures. `streaks` are the saved counters,
+/// already updated for `now`.
+pub fn metrics<Tz: TimeZone>(
+    tasks: &[TaskActivity],
+    streaks: &[Streak],
+    now: &DateTime<Tz>,
+) -> Metrics {
+    let completed: Vec<&TaskActivity> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .collect();
+    let before_due = completed
+        .iter()
+        .filter(|t| match (t.completed_at, t.due_date) {
+            (Some(completed_at), Some(due)) => completed_at <= due,
+            _ => false,
+        })
+        .count();
+
+    let today = now.date_naive();
+    let streak = |name: &str| {
+        streaks
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| current_streak(s, today))
+            .unwrap_or(0)
+    };
+
+    Metrics {
+        tasks_created: tasks.len() as i32,
+        tasks_completed: completed.len() as i32,
+        completed_before_due: before_due as i32,
+        completion_streak: streak(COMPLETION_STREAK),
+        zero_overdue_streak: streak(ZERO_OVERDUE_STREAK),
+    }
+}
+
+pub fn progress(metrics: &Metrics, unlocked: &[UnlockedAchievement]) -> Vec<AchievementProgress> {
+    CATALOG
+        .iter()
+        .map(|achievement| AchievementProgress {
+            id: achievement.id.to_string(),
+            name: achievement.name.to_string(),
+            description: achievement.description.to_string(),
+            progress: metrics.value(achievement.metric).min(achievement.target),
+            target: achievement.target,
+            unlocked_at: unlocked
+                .iter()
+                .find(|u| u.id == achievement.id)
+                .map(|u| u.unlocked_at),
+        })
+        .collect()
+}
+
+async fn all_activity(repos: &Repositories) -> Result<Vec<TaskActivity>> {
+    // Four-digit years, so the bounds compare correctly as SQLite text
+    let since = Utc.with_ymd_and_hms(1, 1, 1, 0, 0, 0).unwrap();
+    let until = Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap();
+    repos.tasks.get_task_activity(since, until).await
+}
+
+/// Progress toward every achievement, without recording anything.
+pub async fn load_progress<Tz: TimeZone>(
+    repos: &Repositories,
+    now: DateTime<Tz>,
+) -> Result<Vec<AchievementProgress>> {
+    let tasks = all_activity(repos).await?;
+    let streaks = repos.achievements.get_streaks().await?;
+    let unlocked = repos.achievements.get_unlocked_achievements().await?;
+
+    Ok(progress(&metrics(&tasks, &streaks, &now), &unlocked))
+}
+
+/// An achievement unlocked by `evaluate`, with the notification saved for it.
+pub struct Unlock {
+    pub achievement: &'static Achievement,
+    pub notification: NotificationItem,
+}
+
+/// Updates the streak counters for `now` and unlocks every achievement that
+/// has reached its target, returning only those unlocked by this call.
+pub async fn evaluate<Tz: TimeZone>(
+    repos: &Repositories,
+    now: DateTime<Tz>,
+) -> Result<Vec<Unlock>> {
+    let tasks = all_activity(repos).await?;
+    let saved = repos.achievements.get_streaks().await?;
+    let saved_streak = |name: &str| {
+        saved
+            .iter()
+            .find(|s| s.name == name)
+            .cloned()
+            .unwrap_or_else(|| new_streak(name))
+    };
+    let today = now.date_naive();
+    let now_utc = now.with_timezone(&Utc);
+
+    // Rebuilt from history, so imported and synced completions count too
+    let mut completion = completion_streak(&tasks, &now);
+    completion.best = completion.current.max(saved_streak(COMPLETION_STREAK).best);
+
+    // Overdue tasks leave no history, so this one is only ever counted live
+    let mut zero_overdue = saved_streak(ZERO_OVERDUE_STREAK);
+    let kept = !tasks.iter().any(|t| is_overdue(t, now_utc));
+    record_day(&mut zero_overdue, today, kept);
+
+    let streaks = vec![completion, zero_overdue];
+    for streak in &streaks {
+        repos.achievements.save_streak(streak).await?;
+    }
+
+    let metrics = metrics(&tasks, &streaks, &now);
+    let unlocked: HashSet<String> = repos
+        .achievements
+        .get_unlocked_achievements()
+        .await?
+        .into_iter()
+        .map(|u| u.id)
+        .collect();
+
+    let mut newly_unlocked = Vec::new();
+    for achievement in CATALOG {
+        if unlocked.contains(achievement.id)
+            || metrics.value(achievement.metric) < achievement.target
+        {
+            continue;
+        }
+        let notification = NotificationManager::achievement_notification(achievement.name);
+        if repos
+            .achievements
+            .unlock_achievement(achievement.id, now_utc, &notification)
+            .await?
+        {
+            info!("Achievement unlocked: {}", achievement.name);
+            newly_unlocked.push(Unlock {
+                achievement,
+                notification,
+            });
+        }
+    }
+
+    Ok(newly_unlocked)
+}
+
+/// Evaluates the catalog after a task event or on the hourly job, showing
+/// the achievement notification for each new unlock. The notifications are
+/// already in the inbox, so one that cannot be shown is only logged.
+pub async fn check(app_handle: &tauri::AppHandle) -> Result<()> {
+    let state = app_handle.state::<AppState>();
+    let unlocked = evaluate(&state.repos, chrono::Local::now()).await?;
+    if unlocked.is_empty() {
+        return Ok(());
+    }
+
+    let notifications = state.notifications.lock().await;
+    for unlock in &unlocked {
+        if let Err(e) = notifications
+            .send_achievement_notification(&unlock.notification, app_handle)
+            .await
+        {
+            error!(
+                "Failed to show achievement '{}': {}",
+                unlock.achievement.name, e
+            );
+        }
+    }
+    drop(notifications);
+
+    let ids: Vec<&str> = unlocked.iter().map(|u| u.achievement.id).collect();
+    app_handle.emit_all("achievements_unlocked", &ids)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::task_at;
+    use chrono::{Datelike, FixedOffset};
+
+    fn unlocked_ids(unlocked: &[Unlock]) -> Vec<&'static str> {
+        unlocked.iter().map(|u| u.achievement.id).collect()
+    }
+
+    #[tokio::test]
+    async fn evaluate_unlocks_each_achievement_once() {
+        let repos = Repositories::in_memory();
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let now = tz.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+
+        // One completion on each of the last three local days
+        for days in 0..3 {
+            let completed_at = (now - Duration::days(days)).with_timezone(&Utc);
+            let task = task_at(
+                "Done",
+                completed_at - Duration::hours(1),
+                Some(completed_at),
+            );
+            repos.tasks.create_task(task).await.unwrap();
+        }
+
+        let unlocked = evaluate(&repos, now).await.unwrap();
+        assert_eq!(
+            unlocked_ids(&unlocked),
+            ["first_task", "first_completion", "streak_3"]
+        );
+        assert!(evaluate(&repos, now).await.unwrap().is_empty());
+
+        // Each unlock's notification was saved with it
+        let mut saved: Vec<String> = repos
+            .notifications
+            .get_notifications(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        let mut announced: Vec<String> =
+            unlocked.iter().map(|u| u.notification.id.clone()).collect();
+        saved.sort();
+        announced.sort();
+        assert_eq!(saved, announced);
+
+        let streaks = repos.achievements.get_streaks().await.unwrap();
+        let completion = streaks
+            .iter()
+            .find(|s| s.name == COMPLETION_STREAK)
+            .unwrap();
+        assert_eq!((completion.current, completion.best), (3, 3));
+        let zero_overdue = streaks
+            .iter()
+            .find(|s| s.name == ZERO_OVERDUE_STREAK)
+            .unwrap();
+        assert_eq!(zero_overdue.current, 1);
+    }
+
+    #[tokio::test]
+    async fn evaluate_counts_completions_before_the_due_date() {
+        let repos = Repositories::in_memory();
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+
+        for i in 0..10 {
+            let completed_at = now - Duration::days(40) + Duration::hours(i);
+            let mut task = task_at(
+                "Early",
+                completed_at - Duration::hours(1),
+                Some(completed_at),
+            );
+            task.due_date = Some(completed_at + Duration::days(1));
+            repos.tasks.create_task(task).await.unwrap();
+        }
+
+        let unlocked = unlocked_ids(&evaluate(&repos, now).await.unwrap());
+        assert!(unlocked.contains(&"tasks_completed_10"));
+        assert!(unlocked.contains(&"before_due_10"));
+        // The completions are weeks old, so no streak is running
+        assert!(!unlocked.contains(&"streak_3"));
+    }
+
+    #[tokio::test]
+    async fn an_unlock_and_its_notification_are_saved_once() {
+        let repos = Repositories::sqlite(&crate::database::Database::in_memory().await.unwrap());
+        let now = Utc::now();
+
+        let first = NotificationManager::achievement_notification("First Step");
+        let again = NotificationManager::achievement_notification("First Step");
+        let achievements = &repos.achievements;
+        assert!(achievements
+            .unlock_achievement("first_task", now, &first)
+            .await
+            .unwrap());
+        assert!(!achievements
+            .unlock_achievement("first_task", now, &again)
+            .await
+            .unwrap());
+
+        let saved = repos.notifications.get_notifications(10).await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].id, first.id);
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    fn summary(streak: &Streak) -> (i32, i32, Option<u32>) {
+        (
+            streak.current,
+            streak.best,
+            streak.last_date.map(|d| d.day()),
+        )
+    }
+
+    #[test]
+    fn record_day_extends_breaks_and_restarts_a_streak() {
+        let mut streak = new_streak(ZERO_OVERDUE_STREAK);
+        record_day(&mut streak, date(1), true);
+        record_day(&mut streak, date(2), true);
+        assert_eq!(summary(&streak), (2, 2, Some(2)));
+
+        // A gap starts over
+        record_day(&mut streak, date(5), true);
+        assert_eq!(summary(&streak), (1, 2, Some(5)));
+
+        // A broken day resets the run but keeps the best
+        record_day(&mut streak, date(6), false);
+        assert_eq!(summary(&streak), (0, 2, Some(6)));
+        record_day(&mut streak, date(7), true);
+        assert_eq!(summary(&streak), (1, 2, Some(7)));
+    }
+
+    #[test]
+    fn record_day_counts_a_day_once_and_a_broken_day_stays_broken() {
+        let mut streak = new_streak(ZERO_OVERDUE_STREAK);
+        record_day(&mut streak, date(1), true);
+        record_day(&mut streak, date(2), true);
+        record_day(&mut streak, date(2), true);
+        assert_eq!(summary(&streak), (2, 2, Some(2)));
+
+        record_day(&mut streak, date(2), false);
+        assert_eq!(summary(&streak), (0, 2, Some(2)));
+        record_day(&mut streak, date(2), true);
+        assert_eq!(summary(&streak), (0, 2, Some(2)));
+    }
+
+    #[test]
+    fn current_streak_lapses_after_yesterday() {
+        let streak = Streak {
+            name: COMPLETION_STREAK.to_string(),
+            current: 4,
+            best: 4,
+            last_date: Some(date(10)),
+        };
+        assert_eq!(current_streak(&streak, date(10)), 4);
+        assert_eq!(current_streak(&streak, date(11)), 4);
+        assert_eq!(current_streak(&streak, date(12)), 0);
+        assert_eq!(current_streak(&new_streak(COMPLETION_STREAK), date(10)), 0);
+    }
+
+    #[test]
+    fn completion_streak_ends_today_or_yesterday() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let completion = |day: u32, hour: u32| {
+            let at = tz
+                .with_ymd_and_hms(2024, 3, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc);
+            TaskActivity::from(&task_at("Done", at - Duration::hours(1), Some(at)))
+        };
+        // Two on the 8th count once; the 5th is cut off by the gap on the 6th
+        let tasks = [
+            completion(5, 9),
+            completion(7, 9),
+            completion(8, 9),
+            completion(8, 18),
+            completion(9, 9),
+        ];
+
+        let morning = |day: u32| tz.with_ymd_and_hms(2024, 3, day, 8, 0, 0).unwrap();
+        assert_eq!(
+            summary(&completion_streak(&tasks, &morning(9))),
+            (3, 0, Some(9))
+        );
+        // Nothing yet today, so the run up to yesterday still counts
+        assert_eq!(
+            summary(&completion_streak(&tasks, &morning(10))),
+            (3, 0, Some(9))
+        );
+        assert_eq!(
+            summary(&completion_streak(&tasks, &morning(11))),
+            (0, 0, None)
+        );
+
+        // 23:30 UTC on the 9th is already the 10th here
+        let late = Utc.with_ymd_and_hms(2024, 3, 9, 23, 30, 0).unwrap();
+        let mut tasks = tasks.to_vec();
+        tasks.push(TaskActivity::from(&task_at("Late", late, Some(late))));
+        assert_eq!(
+            summary(&completion_streak(&tasks, &morning(10))),
+            (4, 0, Some(10))
+        );
+    }
+}
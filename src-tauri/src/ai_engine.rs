@@ -1,15 +1,103 @@
-use anyhow::Result;
 use chrono::Timelike;
-use log::{error, info, warn};
+use log::{error, info};
 use reqwest::Client;
-use serde_json::json;
-use std::env;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::error::Result;
+use crate::llm::{self, CompletionRequest, LlmProvider};
+use crate::models::{
+    AIInsight, AiConnectionTest, AiSettings, EisenhowerMatrix, EisenhowerQuadrant,
+    EisenhowerThresholds, InsightType, Task,
+};
+
+/// How much weight each kind of insight deserves: rules computed from the
+/// user's own numbers, model output, then generic advice.
+const DATA_CONFIDENCE: f32 = 0.9;
+const PATTERN_CONFIDENCE: f32 = 0.8;
+const MODEL_CONFIDENCE: f32 = 0.7;
+const TIME_OF_DAY_CONFIDENCE: f32 = 0.6;
+const GENERIC_CONFIDENCE: f32 = 0.5;
+
+/// Reads the `type: message` lines the insights prompt asks for. Lines
+/// without a recognised type are kept as productivity tips.
+fn parse_insight_line(line: &str) -> AIInsight {
+    let line = line
+        .trim()
+        .trim_start_matches(|c: char| c == '-' || c == '*' || c.is_ascii_digit() || c == '.')
+        .trim();
+
+    let typed = line.split_once(':').and_then(|(label, message)| {
+        let insight_type = match label
+            .trim()
+            .to_lowercase()
+            .replace([' ', '-'], "_")
+            .as_str()
+        {
+            "productivity_tip" => InsightType::ProductivityTip,
+            "task_prioritization" => InsightType::TaskPrioritization,
+            "time_management" => InsightType::TimeManagement,
+            "pattern_recognition" => InsightType::PatternRecognition,
+            "accountability" => InsightType::Accountability,
+            _ => return None,
+        };
+        Some((insight_type, message.trim()))
+    });
+
+    let (insight_type, message) = typed.unwrap_or((InsightType::ProductivityTip, line));
+    AIInsight::new(message.to_string(), insight_type, MODEL_CONFIDENCE)
+}
 
-use crate::models::Task;
+/// Sends a minimal request through `provider` and times it. Takes the
+/// provider rather than the engine so no lock is held during the request.
+pub async fn test_connection(provider: Option<Box<dyn LlmProvider>>) -> AiConnectionTest {
+    let provider = match provider {
+        Some(provider) => provider,
+        None => {
+            return AiConnectionTest {
+                provider: None,
+                model: None,
+                success: false,
+                latency_ms: None,
+                reply: None,
+                error: Some("AI is disabled or its API key is not set".to_string()),
+            }
+        }
+    };
+
+    let request = CompletionRequest {
+        system: "You are a connection check. Reply with OK.",
+        prompt: "Reply with OK.",
+        max_tokens: 5,
+        temperature: 0.0,
+    };
+    let start = Instant::now();
+    let result = provider.complete(&request).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let (reply, error) = match result {
+        Ok(reply) => (Some(reply), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    AiConnectionTest {
+        provider: Some(provider.name().to_string()),
+        model: Some(provider.model().to_string()),
+        success: error.is_none(),
+        latency_ms: Some(latency_ms),
+        reply,
+        error,
+    }
+}
 
+/// Cheap to clone: the provider is shared, so callers copy the engine out of
+/// its lock before a model call instead of holding the lock through it.
+#[derive(Clone)]
 pub struct AIEngine {
     client: Client,
-    openai_api_key: Option<String>,
+    provider: Option<Arc<dyn LlmProvider>>,
+    ai_settings: AiSettings,
+    eisenhower_thresholds: EisenhowerThresholds,
 }
 
 impl Default for AIEngine {
@@ -19,25 +107,50 @@ impl Default for AIEngine {
 }
 
 impl AIEngine {
+    /// Starts with the rule-based fallbacks only; `set_ai_settings` picks a
+    /// provider.
     pub fn new() -> Self {
-        let openai_api_key = env::var("OPENAI_API_KEY").ok();
-
-        if openai_api_key.is_none() {
-            warn!("OpenAI API key not found. AI features will use fallback responses.");
-        }
-
         Self {
             client: Client::new(),
-            openai_api_key,
+            provider: None,
+            ai_settings: AiSettings::default(),
+            eisenhower_thresholds: EisenhowerThresholds::default(),
+        }
+    }
+
+    /// A copy of the engine as currently configured, taken without holding
+    /// `engine` for longer than the copy.
+    pub async fn current(engine: &Mutex<Self>) -> Self {
+        engine.lock().await.clone()
+    }
+
+    pub fn set_eisenhower_thresholds(&mut self, thresholds: EisenhowerThresholds) {
+        self.eisenhower_thresholds = thresholds;
+    }
+
+    /// Switches to the provider `settings` select; requests already in
+    /// flight finish on the old one, through the copies that made them.
+    pub fn set_ai_settings(&mut self, settings: &AiSettings) {
+        self.provider = llm::from_settings(settings, &self.client).map(Arc::from);
+        self.ai_settings = settings.clone();
+        match &self.provider {
+            Some(provider) => info!("AI provider: {} ({})", provider.name(), provider.model()),
+            None => info!("AI provider: none, using fallback responses"),
         }
     }
 
-    pub async fn generate_insights(&self, tasks: &[Task]) -> Result<Vec<String>> {
-        if let Some(api_key) = &self.openai_api_key {
-            match self.generate_openai_insights(tasks, api_key).await {
+    /// The provider `settings` select, which need not be the saved ones,
+    /// sharing the engine's HTTP client.
+    pub fn provider_for(&self, settings: &AiSettings) -> Option<Box<dyn LlmProvider>> {
+        llm::from_settings(settings, &self.client)
+    }
+
+    pub async fn generate_insights(&self, tasks: &[Task]) -> Result<Vec<AIInsight>> {
+        if let Some(provider) = &self.provider {
+            match self.generate_model_insights(provider.as_ref(), tasks).await {
                 Ok(insights) => return Ok(insights),
                 Err(e) => {
-                    error!("OpenAI insights generation failed: {}", e);
+                    error!("{} insights generation failed: {}", provider.name(), e);
                     // Fall back to rule-based insights
                 }
             }
@@ -46,7 +159,11 @@ impl AIEngine {
         Ok(self.generate_fallback_insights(tasks))
     }
 
-    async fn generate_openai_insights(&self, tasks: &[Task], api_key: &str) -> Result<Vec<String>> {
+    async fn generate_model_insights(
+        &self,
+        provider: &dyn LlmProvider,
+        tasks: &[Task],
+    ) -> Result<Vec<AIInsight>> {
         let completed_tasks = tasks
             .iter()
             .filter(|t| matches!(t.status, crate::models::TaskStatus::Completed))
@@ -72,58 +189,31 @@ impl AIEngine {
             - High priority pending: {}
             - Overdue: {}
             
-            Focus on task completion strategies, time management, and motivation. Keep responses concise and encouraging.",
+            Focus on task completion strategies, time management, and motivation. Keep responses concise and encouraging.
+            Write one insight per line as `type: insight`, where type is one of productivity_tip, task_prioritization, time_management, pattern_recognition or accountability.",
             total_tasks, completed_tasks, high_priority_tasks, overdue_tasks
         );
 
-        let request_body = json!({
-            "model": "gpt-3.5-turbo",
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "You are an AI productivity coach that provides brief, actionable insights. Each insight should be under 100 characters and actionable."
-                },
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "max_tokens": 300,
-            "temperature": 0.7
-        });
-
-        let response = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
+        let content = provider
+            .complete(&CompletionRequest::new(
+                "You are an AI productivity coach that provides brief, actionable insights. Each insight should be under 100 characters and actionable.",
+                &prompt,
+                &self.ai_settings.insights,
+            ))
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("OpenAI API error: {}", error_text));
-        }
-
-        let response_json: serde_json::Value = response.json().await?;
-
-        let content = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .trim();
-
-        let insights: Vec<String> = content
+        let insights: Vec<AIInsight> = content
             .split('\n')
             .filter(|line| !line.trim().is_empty())
-            .map(|line| line.trim().to_string())
+            .map(parse_insight_line)
+            .filter(|insight| !insight.message.is_empty())
             .take(3)
             .collect();
 
         info!("Generated {} AI insights", insights.len());
         Ok(insights)
     }
-    fn generate_fallback_insights(&self, tasks: &[Task]) -> Vec<String> {
+    fn generate_fallback_insights(&self, tasks: &[Task]) -> Vec<AIInsight> {
         let mut insights = Vec::new();
 
         let completed_tasks = tasks
@@ -143,69 +233,137 @@ impl AIEngine {
         let overdue_tasks = tasks.iter().filter(|t| t.is_overdue()).count();
 
         if total_tasks == 0 {
-            insights.push(
+            insights.push(AIInsight::new(
                 "Start by adding your daily tasks to track progress effectively.".to_string(),
-            );
+                InsightType::ProductivityTip,
+                GENERIC_CONFIDENCE,
+            ));
         } else {
             let completion_rate = (completed_tasks as f32 / total_tasks as f32) * 100.0;
 
             if completion_rate >= 80.0 {
-                insights.push(format!(
-                    "Excellent progress! You've completed {:.0}% of your tasks. 🎉",
-                    completion_rate
+                insights.push(AIInsight::new(
+                    format!(
+                        "Excellent progress! You've completed {:.0}% of your tasks. 🎉",
+                        completion_rate
+                    ),
+                    InsightType::Accountability,
+                    DATA_CONFIDENCE,
                 ));
             } else if completion_rate >= 50.0 {
-                insights.push(format!(
-                    "Good momentum! Focus on completing the remaining {} tasks.",
-                    total_tasks - completed_tasks
+                insights.push(AIInsight::new(
+                    format!(
+                        "Good momentum! Focus on completing the remaining {} tasks.",
+                        total_tasks - completed_tasks
+                    ),
+                    InsightType::Accountability,
+                    DATA_CONFIDENCE,
                 ));
             } else if high_priority_tasks > 0 {
-                insights.push(format!(
-                    "{} high-priority tasks need attention. Consider tackling these first.",
-                    high_priority_tasks
+                insights.push(AIInsight::new(
+                    format!(
+                        "{} high-priority tasks need attention. Consider tackling these first.",
+                        high_priority_tasks
+                    ),
+                    InsightType::TaskPrioritization,
+                    DATA_CONFIDENCE,
                 ));
             } else {
-                insights.push(
+                insights.push(AIInsight::new(
                     "Break down large tasks into smaller, manageable chunks for better progress."
                         .to_string(),
-                );
+                    InsightType::ProductivityTip,
+                    GENERIC_CONFIDENCE,
+                ));
             }
         }
 
         if overdue_tasks > 0 {
-            insights.push(format!(
-                "{} tasks are overdue. Prioritize these to get back on track.",
-                overdue_tasks
+            insights.push(AIInsight::new(
+                format!(
+                    "{} tasks are overdue. Prioritize these to get back on track.",
+                    overdue_tasks
+                ),
+                InsightType::TaskPrioritization,
+                DATA_CONFIDENCE,
+            ));
+        }
+
+        if let Some(message) = self.quadrant_imbalance_insight(tasks) {
+            insights.push(AIInsight::new(
+                message,
+                InsightType::PatternRecognition,
+                PATTERN_CONFIDENCE,
             ));
         }
 
         // Time-based insights
         let current_hour = chrono::Utc::now().hour();
-        match current_hour {
-            9..=11 => insights.push(
-                "Peak productivity hours: 9-11 AM. Use this time for challenging tasks."
-                    .to_string(),
-            ),
-            14..=16 => insights.push(
-                "Post-lunch dip is normal. Consider lighter tasks or a short break.".to_string(),
-            ),
-            17..=19 => insights.push(
-                "End of workday approaching. Review what you've accomplished today.".to_string(),
-            ),
-            _ => {}
+        let time_of_day = match current_hour {
+            9..=11 => {
+                Some("Peak productivity hours: 9-11 AM. Use this time for challenging tasks.")
+            }
+            14..=16 => Some("Post-lunch dip is normal. Consider lighter tasks or a short break."),
+            17..=19 => Some("End of workday approaching. Review what you've accomplished today."),
+            _ => None,
+        };
+        if let Some(message) = time_of_day {
+            insights.push(AIInsight::new(
+                message.to_string(),
+                InsightType::TimeManagement,
+                TIME_OF_DAY_CONFIDENCE,
+            ));
         }
 
         // Ensure we have at least one insight
         if insights.is_empty() {
-            insights.push(
+            insights.push(AIInsight::new(
                 "Stay focused on your goals. Small consistent progress leads to big results!"
                     .to_string(),
-            );
+                InsightType::ProductivityTip,
+                GENERIC_CONFIDENCE,
+            ));
         }
 
+        // Insights drawn from the user's own tasks go ahead of generic advice
+        // and the time-of-day tip, so the cut to three never drops them
+        insights.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
         insights.into_iter().take(3).collect()
     }
 
+    fn quadrant_imbalance_insight(&self, tasks: &[Task]) -> Option<String> {
+        let matrix =
+            EisenhowerMatrix::build(tasks, &self.eisenhower_thresholds, chrono::Utc::now());
+
+        // Too few open tasks to call anything an imbalance
+        if matrix.task_count() < 3 {
+            return None;
+        }
+
+        let delegate = matrix.time_share(EisenhowerQuadrant::Delegate);
+        let do_first = matrix.time_share(EisenhowerQuadrant::DoFirst);
+        let eliminate = matrix.time_share(EisenhowerQuadrant::Eliminate);
+
+        if delegate >= 0.5 {
+            Some(format!(
+                "{:.0}% of your time goes to urgent-but-unimportant tasks. Delegate or batch them.",
+                delegate * 100.0
+            ))
+        } else if do_first >= 0.6 {
+            Some(format!(
+                "{:.0}% of your time is spent firefighting urgent priorities. Plan ahead to avoid crunches.",
+                do_first * 100.0
+            ))
+        } else if eliminate >= 0.4 {
+            Some(format!(
+                "{:.0}% of your time sits in tasks that are neither urgent nor important. Consider dropping some.",
+                eliminate * 100.0
+            ))
+        } else {
+            None
+        }
+    }
+
     pub async fn generate_accountability_message(&self, tasks: &[Task]) -> Result<String> {
         let completed_tasks = tasks
             .iter()
@@ -213,14 +371,22 @@ impl AIEngine {
             .count();
         let pending_tasks = tasks.len() - completed_tasks;
 
-        if let Some(api_key) = &self.openai_api_key {
+        if let Some(provider) = &self.provider {
             match self
-                .generate_openai_accountability_message(completed_tasks, pending_tasks, api_key)
+                .generate_model_accountability_message(
+                    provider.as_ref(),
+                    completed_tasks,
+                    pending_tasks,
+                )
                 .await
             {
                 Ok(message) => return Ok(message),
                 Err(e) => {
-                    error!("OpenAI accountability message generation failed: {}", e);
+                    error!(
+                        "{} accountability message generation failed: {}",
+                        provider.name(),
+                        e
+                    );
                     // Fall back to predefined messages
                 }
             }
@@ -229,11 +395,11 @@ impl AIEngine {
         Ok(self.generate_fallback_accountability_message(completed_tasks, pending_tasks))
     }
 
-    async fn generate_openai_accountability_message(
+    async fn generate_model_accountability_message(
         &self,
+        provider: &dyn LlmProvider,
         completed: usize,
         pending: usize,
-        api_key: &str,
     ) -> Result<String> {
         let prompt = format!(
             "Generate a brief, encouraging accountability check-in message (under 150 characters) based on:
@@ -245,44 +411,13 @@ impl AIEngine {
             completed, pending, chrono::Utc::now().format("%H:%M")
         );
 
-        let request_body = json!({
-            "model": "gpt-3.5-turbo",
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "You are a supportive productivity coach. Create brief, encouraging check-in messages under 150 characters."
-                },
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "max_tokens": 100,
-            "temperature": 0.8
-        });
-
-        let response = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("OpenAI API error: {}", error_text));
-        }
-
-        let response_json: serde_json::Value = response.json().await?;
-        let message = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .trim()
-            .to_string();
-
-        Ok(message)
+        provider
+            .complete(&CompletionRequest::new(
+                "You are a supportive productivity coach. Create brief, encouraging check-in messages under 150 characters.",
+                &prompt,
+                &self.ai_settings.accountability,
+            ))
+            .await
     }
 
     fn generate_fallback_accountability_message(&self, completed: usize, pending: usize) -> String {
@@ -358,3 +493,71 @@ impl AIEngine {
         Ok(patterns)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use crate::llm::CompletionRequest;
+    use async_trait::async_trait;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// Replies only once released, like a model that is slow to answer.
+    struct SlowProvider {
+        started: Arc<Notify>,
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for SlowProvider {
+        fn name(&self) -> &'static str {
+            "Slow"
+        }
+
+        fn model(&self) -> &str {
+            "slow"
+        }
+
+        async fn complete(&self, _request: &CompletionRequest<'_>) -> Result<String> {
+            self.started.notify_one();
+            self.release.notified().await;
+            Ok("productivity_tip: Take a break".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn settings_can_change_while_a_model_call_is_pending() {
+        let started = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let mut engine = AIEngine::new();
+        engine.provider = Some(Arc::new(SlowProvider {
+            started: started.clone(),
+            release: release.clone(),
+        }));
+        let engine = Arc::new(Mutex::new(engine));
+
+        let pending = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                AIEngine::current(&engine)
+                    .await
+                    .generate_insights(&[])
+                    .await
+            }
+        });
+        started.notified().await;
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            let mut engine = engine.lock().await;
+            engine.set_ai_settings(&AiSettings::default());
+        })
+        .await
+        .expect("settings swap waited for the model call");
+        assert!(engine.lock().await.provider.is_none());
+
+        release.notify_one();
+        let insights = pending.await.unwrap().unwrap();
+        assert_eq!(insights[0].message, "Take a break");
+    }
+}
@@ -0,0 +1,307 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use crate::models::{BackupInfo, BackupSettings, BackupTier};
+
+/// Microseconds keep two backups taken within the same second apart.
+pub const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%6f";
+/// Backups written before microseconds were added to the name.
+const LEGACY_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const TIERS: [BackupTier; 5] = [
+    BackupTier::Hourly,
+    BackupTier::Daily,
+    BackupTier::Weekly,
+    BackupTier::PreMigration,
+    BackupTier::PreRestore,
+];
+
+pub struct BackupManager {
+    backup_dir: PathBuf,
+}
+
+impl BackupManager {
+    /// Backups live next to the database in `<data dir>/backups`. In-memory
+    /// databases have nowhere to back up to.
+    pub fn for_database(db: &Database) -> Option<Self> {
+        db.data_dir().map(|dir| Self {
+            backup_dir: dir.join("backups"),
+        })
+    }
+
+    fn tier_dir(&self, tier: BackupTier) -> PathBuf {
+        self.backup_dir.join(tier_name(tier))
+    }
+
+    /// Writes a consistent snapshot of the live database and verifies its
+    /// integrity. A snapshot that fails verification is deleted.
+    pub async fn create_snapshot(&self, db: &Database, tier: BackupTier) -> Result<BackupInfo> {
+        let dir = self.tier_dir(tier);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let file_name = format!("app-{}.db", Utc::now().format(TIMESTAMP_FORMAT));
+        let path = dir.join(&file_name);
+        let tmp_path = dir.join(format!("{}.tmp", file_name));
+
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        db.copy_to(&tmp_path).await?;
+
+        if let Err(e) = Database::verify_file(&tmp_path).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(e);
+        }
+
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        info!("Database backup written to: {}", path.display());
+        backup_info(tier, &path).await
+    }
+
+    /// Takes the hourly snapshot, promotes it to the daily and weekly tiers
+    /// when those have no copy for the current day or week yet, and prunes
+    /// every tier down to its configured size.
+    pub async fn run_scheduled(
+        &self,
+        db: &Database,
+        settings: &BackupSettings,
+    ) -> Result<BackupInfo> {
+        let snapshot = self.create_snapshot(db, BackupTier::Hourly).await?;
+        let today = Local::now().date_naive();
+
+        let backups = self.list().await?;
+        let has_daily = backups.iter().any(|b| {
+            b.tier == BackupTier::Daily && b.created_at.with_timezone(&Local).date_naive() == today
+        });
+        let has_weekly = backups.iter().any(|b| {
+            b.tier == BackupTier::Weekly
+                && b.created_at.with_timezone(&Local).iso_week() == today.iso_week()
+        });
+
+        if !has_daily {
+            self.promote(&snapshot, BackupTier::Daily).await?;
+        }
+        if !has_weekly {
+            self.promote(&snapshot, BackupTier::Weekly).await?;
+        }
+
+        self.prune(BackupTier::Hourly, settings.hourly_to_keep)
+            .await?;
+        self.prune(BackupTier::Daily, settings.daily_to_keep)
+            .await?;
+        self.prune(BackupTier::Weekly, settings.weekly_to_keep)
+            .await?;
+        self.prune(BackupTier::PreMigration, settings.pre_migration_to_keep)
+            .await?;
+        self.prune(BackupTier::PreRestore, settings.pre_restore_to_keep)
+            .await?;
+
+        Ok(snapshot)
+    }
+
+    async fn promote(&self, snapshot: &BackupInfo, tier: BackupTier) -> Result<()> {
+        let dir = self.tier_dir(tier);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let source = PathBuf::from(&snapshot.path);
+        let file_name = source
+            .file_name()
+            .ok_or_else(|| AppError::Internal(format!("Invalid backup path: {}", snapshot.path)))?;
+        tokio::fs::copy(&source, dir.join(file_name)).await?;
+
+        Ok(())
+    }
+
+    async fn prune(&self, tier: BackupTier, keep: usize) -> Result<()> {
+        let mut backups = self.list_tier(tier).await?;
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+
+        for backup in backups.into_iter().skip(keep) {
+            info!("Removing old backup: {}", backup.path);
+            tokio::fs::remove_file(&backup.path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// All backups, newest first.
+    pub async fn list(&self) -> Result<Vec<BackupInfo>> {
+        let mut backups = Vec::new();
+        for tier in TIERS {
+            backups.extend(self.list_tier(tier).await?);
+        }
+
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(backups)
+    }
+
+    async fn list_tier(&self, tier: BackupTier) -> Result<Vec<BackupInfo>> {
+        let mut entries = match tokio::fs::read_dir(self.tier_dir(tier)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("db") {
+                continue;
+            }
+
+            match backup_info(tier, &path).await {
+                Ok(info) => backups.push(info),
+                Err(e) => warn!("Skipping unrecognised backup {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(backups)
+    }
+
+    /// Moves every backup into `<data_dir>/backups`, for when the database
+    /// itself has moved there. Falls back to copying file by file when the
+    /// folders are on different file systems or the target has backups.
+    pub async fn move_to(&self, data_dir: &Path) -> Result<()> {
+        let target = data_dir.join("backups");
+        if !tokio::fs::try_exists(&self.backup_dir).await? {
+            return Ok(());
+        }
+        if tokio::fs::rename(&self.backup_dir, &target).await.is_ok() {
+            info!("Backups moved to: {}", target.display());
+            return Ok(());
+        }
+
+        for tier in TIERS {
+            let dir = target.join(tier_name(tier));
+            tokio::fs::create_dir_all(&dir).await?;
+            for backup in self.list_tier(tier).await? {
+                let source = PathBuf::from(&backup.path);
+                if let Some(file_name) = source.file_name() {
+                    tokio::fs::copy(&source, dir.join(file_name)).await?;
+                    tokio::fs::remove_file(&source).await?;
+                }
+            }
+        }
+        let _ = tokio::fs::remove_dir_all(&self.backup_dir).await;
+
+        info!("Backups copied to: {}", target.display());
+        Ok(())
+    }
+
+    /// Replaces the live database with a backup, saving the current state as
+    /// a pre-restore snapshot first. The database is closed afterwards, so the
+    /// app must restart to pick up the restored data.
+    pub async fn restore(&self, db: &Database, id: &str) -> Result<BackupInfo> {
+        let backup = self.find(id).await?;
+        Database::verify_file(Path::new(&backup.path)).await?;
+
+        self.create_snapshot(db, BackupTier::PreRestore).await?;
+
+        db.replace_with(Path::new(&backup.path)).await?;
+
+        info!("Database restored from backup: {}", backup.path);
+        Ok(backup)
+    }
+
+    /// Looks a backup up by id. Only files that `list` would return can be
+    /// found, so ids cannot point outside the backup directory.
+    pub async fn find(&self, id: &str) -> Result<BackupInfo> {
+        self.list()
+            .await?
+            .into_iter()
+            .find(|b| b.id == id)
+            .ok_or_else(|| AppError::not_found("Backup", id))
+    }
+}
+
+fn tier_name(tier: BackupTier) -> &'static str {
+    match tier {
+        BackupTier::Hourly => "hourly",
+        BackupTier::Daily => "daily",
+        BackupTier::Weekly => "weekly",
+        BackupTier::PreMigration => "pre-migration",
+        BackupTier::PreRestore => "pre-restore",
+    }
+}
+
+async fn backup_info(tier: BackupTier, path: &Path) -> Result<BackupInfo> {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| AppError::Internal("Invalid backup file name".to_string()))?;
+
+    let created_at = parse_timestamp(file_name.trim_end_matches(".db"))
+        .ok_or_else(|| AppError::Internal(format!("Missing timestamp in {}", file_name)))?;
+
+    let metadata = tokio::fs::metadata(path).await?;
+
+    Ok(BackupInfo {
+        id: format!("{}/{}", tier_name(tier), file_name),
+        tier,
+        path: path.display().to_string(),
+        created_at,
+        size_bytes: metadata.len(),
+    })
+}
+
+/// File names end in a timestamp: app-20240101-120000-123456.db,
+/// app-v3-20240101-120000-123456.db, or without the microseconds when
+/// written by older versions.
+fn parse_timestamp(stem: &str) -> Option<DateTime<Utc>> {
+    let suffix = |len: usize| stem.get(stem.len().checked_sub(len)?..);
+    suffix(22)
+        .and_then(|ts| NaiveDateTime::parse_from_str(ts, TIMESTAMP_FORMAT).ok())
+        .or_else(|| {
+            suffix(15)
+                .and_then(|ts| NaiveDateTime::parse_from_str(ts, LEGACY_TIMESTAMP_FORMAT).ok())
+        })
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_timestamps_with_and_without_microseconds() {
+        assert_eq!(
+            parse_timestamp("app-20240101-120000-000250"),
+            Some(
+                Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()
+                    + chrono::Duration::microseconds(250)
+            )
+        );
+        assert_eq!(
+            parse_timestamp("app-v3-20240101-120000"),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap())
+        );
+        assert_eq!(parse_timestamp("app"), None);
+    }
+
+    #[test]
+    fn names_within_one_second_differ() {
+        let at = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let later = at + chrono::Duration::milliseconds(1);
+        assert_ne!(
+            at.format(TIMESTAMP_FORMAT).to_string(),
+            later.format(TIMESTAMP_FORMAT).to_string()
+        );
+        assert_eq!(
+            parse_timestamp(&format!("app-{}", later.format(TIMESTAMP_FORMAT))),
+            Some(later)
+        );
+    }
+
+    #[tokio::test]
+    async fn a_missing_backup_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = BackupManager {
+            backup_dir: dir.path().join("backups"),
+        };
+
+        let err = manager.find("daily/app-nope.db").await.unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+}
@@ -0,0 +1,168 @@
+//! Latency targets for the main commands on a database of 100k tasks.
+//! Seeding takes a while, so the timed run is ignored by default; run it on a
+//! release build with `cargo test --release -- --ignored benchmarks`.
+
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use std::future::Future;
+use std::time::Instant;
+
+use crate::database::Database;
+use crate::error::Result;
+use crate::models::*;
+use crate::repository::Repositories;
+use crate::stats;
+
+const TASKS: i64 = 100_000;
+const CATEGORIES: &[&str] = &["work", "personal", "health", "learning", "errands", "home"];
+
+/// `count` tasks ten minutes apart, two years' worth for the full set: a
+/// third open, some overdue, the rest completed or cancelled at varying
+/// hours.
+async fn seed(repos: &Repositories, count: i64) -> Result<()> {
+    let now = Utc::now();
+    for i in 0..count {
+        let created_at = now - Duration::minutes(i * 10);
+        let mut task = Task::new(CreateTaskRequest {
+            title: format!("Task {}", i),
+            description: (i % 4 == 0).then(|| "Some details".to_string()),
+            priority: [
+                Priority::Low,
+                Priority::Medium,
+                Priority::High,
+                Priority::Critical,
+            ][(i % 4) as usize],
+            priority_pinned: false,
+            importance: Importance::Auto,
+            category: CATEGORIES[(i % CATEGORIES.len() as i64) as usize].to_string(),
+            project: None,
+            estimated_time: 30,
+            due_date: (i % 3 != 0).then(|| created_at + Duration::days(i % 10)),
+        });
+        task.created_at = created_at;
+        task.updated_at = created_at;
+        match i % 3 {
+            0 => {}
+            1 => {
+                task.status = TaskStatus::Completed;
+                task.completed_at = Some(created_at + Duration::hours(i % 48));
+                task.actual_time = Some((i % 120) as i32);
+            }
+            _ => task.status = TaskStatus::Cancelled,
+        }
+        repos.tasks.create_task(task).await?;
+    }
+    Ok(())
+}
+
+async fn timed<T>(name: &str, target_ms: u128, call: impl Future<Output = Result<T>>) -> T {
+    let start = Instant::now();
+    let value = call
+        .await
+        .unwrap_or_else(|e| panic!("{} failed: {}", name, e));
+    let elapsed = start.elapsed().as_millis();
+
+    log::info!("{}: {} ms (target {} ms)", name, elapsed, target_ms);
+    assert!(
+        elapsed <= target_ms,
+        "{} took {} ms, over its {} ms target",
+        name,
+        elapsed,
+        target_ms
+    );
+    value
+}
+
+/// What the `get_stats_report` command does.
+async fn stats_report(repos: &Repositories, query: &StatsQuery) -> Result<StatsReport> {
+    let (since, until) = stats::activity_window(query, &Local)?;
+    let activity = repos.tasks.get_task_activity(since, until).await?;
+    stats::report(&activity, query, &Local)
+}
+
+/// The SQL aggregates behind the timed stats must still agree with the
+/// per-row definitions in `stats`, which the in-memory backend uses.
+#[tokio::test]
+async fn aggregate_stats_match_per_row_stats() {
+    let db = Database::in_memory().await.unwrap();
+    let sqlite = Repositories::sqlite(&db);
+    let memory = Repositories::in_memory();
+    let cutoff = Utc::now() - Duration::days(5);
+
+    for repos in [&sqlite, &memory] {
+        seed(repos, 2000).await.unwrap();
+        repos.tasks.archive_tasks_older_than(cutoff).await.unwrap();
+    }
+
+    let aggregate = sqlite.tasks.get_productivity_stats().await.unwrap();
+    let per_row = memory.tasks.get_productivity_stats().await.unwrap();
+    assert_eq!(aggregate.total_tasks, 2000);
+    assert_eq!(
+        serde_json::to_value(&aggregate).unwrap(),
+        serde_json::to_value(&per_row).unwrap()
+    );
+}
+
+#[tokio::test]
+#[ignore]
+async fn benchmarks_large_task_set() {
+    let db = Database::in_memory().await.unwrap();
+    let repos = Repositories::sqlite(&db);
+    seed(&repos, TASKS).await.unwrap();
+
+    let tasks = timed("get_tasks", 2000, repos.tasks.get_all_tasks()).await;
+    assert_eq!(tasks.len() as i64, TASKS);
+
+    timed("get_open_tasks", 1000, repos.tasks.get_open_tasks()).await;
+    timed("get_overdue_tasks", 1000, repos.tasks.get_overdue_tasks()).await;
+    timed(
+        "get_productivity_stats",
+        300,
+        repos.tasks.get_productivity_stats(),
+    )
+    .await;
+
+    let today = Local::now().date_naive();
+    let query = StatsQuery {
+        from: today - Duration::days(29),
+        to: today,
+        granularity: StatsGranularity::Day,
+        category: None,
+        project: None,
+        priority: None,
+    };
+    timed("get_stats_report", 150, stats_report(&repos, &query)).await;
+
+    let cutoff = Utc::now() - Duration::days(180);
+    timed(
+        "archive_old_tasks",
+        5000,
+        repos.tasks.archive_tasks_older_than(cutoff),
+    )
+    .await;
+    timed(
+        "get_archived_tasks",
+        100,
+        repos.tasks.get_archived_tasks(50, 0),
+    )
+    .await;
+    timed(
+        "get_productivity_stats (archived)",
+        300,
+        repos.tasks.get_productivity_stats(),
+    )
+    .await;
+
+    let year_start = NaiveDate::from_ymd_opt(today.year() - 1, 1, 1).unwrap();
+    let query = StatsQuery {
+        from: year_start,
+        to: today,
+        granularity: StatsGranularity::Month,
+        ..query
+    };
+    timed(
+        "get_stats_report (monthly)",
+        1000,
+        stats_report(&repos, &query),
+    )
+    .await;
+}
@@ -1,138 +1,735 @@
+use crate::backup::BackupManager;
+use crate::database::{Database, DatabaseLocation};
+use crate::error::{AppError, Result};
+use crate::{
+    achievements, ai_engine, communication, eisenhower, ical, importers, insights, llm, settings,
+    stats, transfer,
+};
 use crate::{models::*, AppState};
-use anyhow::Result;
+use chrono::{DateTime, Duration, Local, Utc};
+use std::path::PathBuf;
 use tauri::{Manager, State};
 
 #[tauri::command]
-pub async fn get_tasks(state: State<'_, AppState>) -> Result<Vec<Task>, String> {
-    let db = state.db.lock().await;
-    db.get_all_tasks().await.map_err(|e| e.to_string())
+pub async fn get_tasks(state: State<'_, AppState>) -> Result<Vec<Task>> {
+    state.repos.tasks.get_all_tasks().await
 }
 
 #[tauri::command]
 pub async fn create_task(
     state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
     request: CreateTaskRequest,
-) -> Result<Task, String> {
+) -> Result<Task> {
     let task = Task::new(request);
-    let db = state.db.lock().await;
-    db.create_task(task).await.map_err(|e| e.to_string())
+    let task = state.repos.tasks.create_task(task).await?;
+
+    check_achievements(app_handle);
+    Ok(task)
 }
 
 #[tauri::command]
 pub async fn update_task(
     state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
     id: String,
     request: UpdateTaskRequest,
-) -> Result<Task, String> {
-    let db = state.db.lock().await;
-
-    let mut task = db
+) -> Result<Task> {
+    let mut task = state
+        .repos
+        .tasks
         .get_task_by_id(&id)
-        .await
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Task not found".to_string())?;
+        .await?
+        .ok_or_else(|| AppError::not_found("Task", &id))?;
 
     task.update(request);
-    db.update_task(task).await.map_err(|e| e.to_string())
+    let task = state.repos.tasks.update_task(task).await?;
+
+    check_achievements(app_handle);
+    Ok(task)
 }
 
 #[tauri::command]
-pub async fn delete_task(state: State<'_, AppState>, id: String) -> Result<bool, String> {
-    let db = state.db.lock().await;
-    db.delete_task(&id).await.map_err(|e| e.to_string())
+pub async fn delete_task(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<bool> {
+    let deleted = state.repos.tasks.delete_task(&id).await?;
+
+    check_achievements(app_handle);
+    Ok(deleted)
+}
+
+/// Task events can unlock achievements. The check scans the task history,
+/// so it runs in the background rather than delaying the command.
+fn check_achievements(app_handle: tauri::AppHandle) {
+    tokio::spawn(async move {
+        if let Err(e) = achievements::check(&app_handle).await {
+            log::error!("Achievement check failed: {}", e);
+        }
+    });
 }
 
 #[tauri::command]
-pub async fn get_ai_insights(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let db = state.db.lock().await;
-    let tasks = db.get_all_tasks().await.map_err(|e| e.to_string())?;
-    drop(db);
+pub async fn get_achievements(state: State<'_, AppState>) -> Result<Vec<AchievementProgress>> {
+    achievements::load_progress(&state.repos, Local::now()).await
+}
 
-    let ai_engine = state.ai_engine.lock().await;
-    ai_engine
-        .generate_insights(&tasks)
+#[tauri::command]
+pub async fn get_ai_insights(state: State<'_, AppState>) -> Result<Vec<String>> {
+    let tasks = state.repos.tasks.get_all_tasks().await?;
+
+    let ai_engine = ai_engine::AIEngine::current(&state.ai_engine).await;
+    let generated = ai_engine.generate_insights(&tasks).await?;
+
+    let messages = generated.iter().map(|i| i.message.clone()).collect();
+    insights::record(&state.repos, generated, Utc::now()).await?;
+
+    Ok(messages)
+}
+
+#[tauri::command]
+pub async fn get_insight_history(
+    state: State<'_, AppState>,
+    insight_type: Option<InsightType>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<Vec<AIInsight>> {
+    state
+        .repos
+        .insights
+        .get_insight_history(insight_type, limit.unwrap_or(50), offset.unwrap_or(0))
         .await
-        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn get_communication_status(
     state: State<'_, AppState>,
-) -> Result<Vec<CommunicationActivity>, String> {
+) -> Result<Vec<CommunicationActivity>> {
     let communication = state.communication.lock().await;
-    communication.get_status().await.map_err(|e| e.to_string())
+    communication.get_status().await
 }
 
+/// Communication history since `since`, for every service or just one.
 #[tauri::command]
-pub async fn connect_service(
+pub async fn get_communication_trends(
     state: State<'_, AppState>,
-    service: String,
-) -> Result<String, String> {
+    since: DateTime<Utc>,
+    service: Option<String>,
+) -> Result<CommunicationTrends> {
+    let snapshots = state
+        .repos
+        .communication
+        .get_communication_snapshots(since, service.as_deref())
+        .await?;
+
+    Ok(communication::trends(&snapshots, &Local))
+}
+
+#[tauri::command]
+pub async fn connect_service(state: State<'_, AppState>, service: String) -> Result<String> {
     let communication = state.communication.lock().await;
-    communication
-        .connect_service(&service)
-        .await
-        .map_err(|e| e.to_string())
+    communication.connect_service(&service).await
 }
 
 #[tauri::command]
 pub async fn get_notifications(
     state: State<'_, AppState>,
     limit: Option<i32>,
-) -> Result<Vec<NotificationItem>, String> {
-    let db = state.db.lock().await;
-    db.get_notifications(limit.unwrap_or(20))
+) -> Result<Vec<NotificationItem>> {
+    state
+        .repos
+        .notifications
+        .get_notifications(limit.unwrap_or(20))
         .await
-        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn mark_notification_read(
+pub async fn mark_notification_read(state: State<'_, AppState>, id: String) -> Result<bool> {
+    state.repos.notifications.mark_notification_read(&id).await
+}
+
+#[tauri::command]
+pub async fn query_notifications(
     state: State<'_, AppState>,
-    id: String,
-) -> Result<bool, String> {
-    let db = state.db.lock().await;
-    db.mark_notification_read(&id)
+    query: NotificationQuery,
+) -> Result<NotificationPage> {
+    state.repos.notifications.query_notifications(&query).await
+}
+
+#[tauri::command]
+pub async fn mark_all_notifications_read(
+    state: State<'_, AppState>,
+    notification_type: Option<NotificationType>,
+) -> Result<u64> {
+    state
+        .repos
+        .notifications
+        .mark_all_notifications_read(notification_type)
         .await
-        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_productivity_stats(
+pub async fn delete_notifications(state: State<'_, AppState>, ids: Vec<String>) -> Result<u64> {
+    state.repos.notifications.delete_notifications(&ids).await
+}
+
+#[tauri::command]
+pub async fn get_productivity_stats(state: State<'_, AppState>) -> Result<ProductivityStats> {
+    state.repos.tasks.get_productivity_stats().await
+}
+
+#[tauri::command]
+pub async fn get_stats_report(
+    state: State<'_, AppState>,
+    query: StatsQuery,
+) -> Result<StatsReport> {
+    let (since, until) = stats::activity_window(&query, &Local)?;
+    let activity = state.repos.tasks.get_task_activity(since, until).await?;
+
+    stats::report(&activity, &query, &Local)
+}
+
+#[tauri::command]
+pub async fn get_eisenhower_matrix(
+    state: State<'_, AppState>,
+    thresholds: Option<EisenhowerThresholds>,
+) -> Result<EisenhowerMatrix> {
+    let thresholds = match thresholds {
+        Some(thresholds) => {
+            eisenhower::validate(&thresholds)?;
+            thresholds
+        }
+        None => state.settings.lock().await.get().eisenhower.clone(),
+    };
+
+    let tasks = state.repos.tasks.get_open_tasks().await?;
+
+    Ok(EisenhowerMatrix::build(&tasks, &thresholds, Utc::now()))
+}
+
+#[tauri::command]
+pub async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings> {
+    let settings = state.settings.lock().await;
+    Ok(settings.get().clone())
+}
+
+/// Saves every setting except the data directory, which only
+/// `move_data_directory` changes, and the sync folder, which only
+/// `update_sync_settings` changes.
+#[tauri::command]
+pub async fn update_settings(
+    state: State<'_, AppState>,
+    mut settings: AppSettings,
+) -> Result<AppSettings> {
+    settings::validate(&settings)?;
+
+    let mut manager = state.settings.lock().await;
+    settings.data_directory = manager.get().data_directory.clone();
+    settings.sync = manager.get().sync.clone();
+    manager.update(settings).await?;
+    let settings = manager.get().clone();
+    drop(manager);
+
+    let mut ai_engine = state.ai_engine.lock().await;
+    ai_engine.set_eisenhower_thresholds(settings.eisenhower.clone());
+    ai_engine.set_ai_settings(&settings.ai);
+
+    Ok(settings)
+}
+
+/// Sends a minimal request with `settings`, or the saved AI settings, so a
+/// provider can be checked before switching to it. Connection and API
+/// failures are reported in the result rather than as an error.
+#[tauri::command]
+pub async fn test_ai_connection(
+    state: State<'_, AppState>,
+    settings: Option<AiSettings>,
+) -> Result<AiConnectionTest> {
+    let settings = match settings {
+        Some(settings) => {
+            llm::validate(&settings)?;
+            settings
+        }
+        None => state.settings.lock().await.get().ai.clone(),
+    };
+
+    // Not under the engine lock: the request may take until it times out
+    let provider = state.ai_engine.lock().await.provider_for(&settings);
+    Ok(ai_engine::test_connection(provider).await)
+}
+
+#[tauri::command]
+pub async fn get_priority_escalations(
+    state: State<'_, AppState>,
+    task_id: Option<String>,
+    limit: Option<i32>,
+) -> Result<Vec<PriorityEscalation>> {
+    state
+        .repos
+        .tasks
+        .get_priority_escalations(task_id.as_deref(), limit.unwrap_or(50))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_archived_tasks(
+    state: State<'_, AppState>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<Vec<ArchivedTask>> {
+    state
+        .repos
+        .tasks
+        .get_archived_tasks(limit.unwrap_or(50), offset.unwrap_or(0))
+        .await
+}
+
+#[tauri::command]
+pub async fn search_archived_tasks(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<i32>,
+) -> Result<Vec<ArchivedTask>> {
+    state
+        .repos
+        .tasks
+        .search_archived_tasks(&query, limit.unwrap_or(50))
+        .await
+}
+
+#[tauri::command]
+pub async fn unarchive_task(state: State<'_, AppState>, id: String) -> Result<Task> {
+    state
+        .repos
+        .tasks
+        .unarchive_task(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Archived task", &id))
+}
+
+#[tauri::command]
+pub async fn archive_old_tasks(state: State<'_, AppState>) -> Result<u64> {
+    let settings = state.settings.lock().await;
+    let archive_after_days = settings.get().archive.archive_after_days;
+    drop(settings);
+
+    let cutoff = Utc::now() - Duration::days(archive_after_days);
+    state.repos.tasks.archive_tasks_older_than(cutoff).await
+}
+
+#[tauri::command]
+pub async fn get_data_directory(state: State<'_, AppState>) -> Result<Option<String>> {
+    Ok(state.db.data_dir().map(|dir| dir.display().to_string()))
+}
+
+#[tauri::command]
+pub async fn move_data_directory(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    new_directory: String,
+) -> Result<String> {
+    if DatabaseLocation::override_value().is_some() {
+        return Err(AppError::Unsupported(
+            "The data directory is set by --data-dir or DATA_STORAGE_PATH and cannot be moved from the app"
+                .to_string(),
+        ));
+    }
+
+    let new_dir = PathBuf::from(new_directory);
+
+    let db = &state.db;
+    let old_dir = db
+        .data_dir()
+        .ok_or_else(|| AppError::Unsupported("An in-memory database cannot be moved".to_string()))?
+        .to_path_buf();
+
+    let new_path = db.prepare_move(&new_dir).await?;
+
+    // Once the files are moved the database is closed, so restart whether or
+    // not the move succeeded; on failure the old location is kept
+    let result = async {
+        db.move_files_to(&new_path).await?;
+
+        let mut settings = state.settings.lock().await;
+        let mut updated = settings.get().clone();
+        updated.data_directory = Some(new_dir.clone());
+        if let Err(e) = settings.update(updated).await {
+            let _ = Database::remove_files(&new_dir).await;
+            return Err(AppError::from(e));
+        }
+
+        Ok(())
+    }
+    .await;
+
+    match &result {
+        Ok(()) => {
+            if let Err(e) = Database::remove_files(&old_dir).await {
+                log::warn!("Failed to remove old database files: {}", e);
+            }
+            if let Some(backups) = BackupManager::for_database(db) {
+                if let Err(e) = backups.move_to(&new_dir).await {
+                    log::warn!(
+                        "Failed to move backups, they remain in the old folder: {}",
+                        e
+                    );
+                }
+            }
+            log::info!(
+                "Data directory moved from {} to {}, restarting",
+                old_dir.display(),
+                new_path.display()
+            );
+        }
+        Err(e) => log::error!("Failed to move data directory, restarting: {}", e),
+    }
+    app_handle.restart();
+
+    result?;
+    Ok(new_path.display().to_string())
+}
+
+#[tauri::command]
+pub async fn list_backups(state: State<'_, AppState>) -> Result<Vec<BackupInfo>> {
+    let db = &state.db;
+    match BackupManager::for_database(db) {
+        Some(manager) => Ok(manager.list().await?),
+        None => Ok(vec![]),
+    }
+}
+
+#[tauri::command]
+pub async fn create_backup(state: State<'_, AppState>) -> Result<BackupInfo> {
+    let db = &state.db;
+    let manager = BackupManager::for_database(db).ok_or_else(|| {
+        AppError::Unsupported("An in-memory database cannot be backed up".to_string())
+    })?;
+
+    manager.create_snapshot(db, BackupTier::Hourly).await
+}
+
+/// Checks the database for corruption and rows the app would misread. With
+/// `fix`, takes a backup and then repairs what can be repaired safely.
+#[tauri::command]
+pub async fn check_database(
     state: State<'_, AppState>,
-) -> Result<ProductivityStats, String> {
-    let db = state.db.lock().await;
-    db.get_productivity_stats().await.map_err(|e| e.to_string())
+    fix: Option<bool>,
+) -> Result<DatabaseCheckReport> {
+    let db = &state.db;
+    let fix = fix.unwrap_or(false);
+
+    let mut backup_id = None;
+    if fix {
+        let report = db.check(false).await?;
+        if !report.issues.iter().any(|i| i.fixable) {
+            return Ok(report);
+        }
+        if let Some(manager) = BackupManager::for_database(db) {
+            let backup = manager.create_snapshot(db, BackupTier::Hourly).await?;
+            backup_id = Some(backup.id);
+        }
+    }
+
+    let mut report = db.check(fix).await?;
+    report.backup_id = backup_id;
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<BackupInfo> {
+    let db = &state.db;
+    let manager = BackupManager::for_database(db).ok_or_else(|| {
+        AppError::Unsupported("An in-memory database cannot be restored".to_string())
+    })?;
+
+    let backup = manager.restore(db, &id).await?;
+
+    log::info!("Restored backup {}, restarting", backup.id);
+    app_handle.restart();
+
+    Ok(backup)
+}
+
+#[tauri::command]
+pub async fn get_encryption_status(state: State<'_, AppState>) -> Result<EncryptionStatus> {
+    Ok(state.db.encryption_status().await)
+}
+
+#[tauri::command]
+pub async fn unlock_database(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<EncryptionStatus> {
+    let db = &state.db;
+    db.unlock(&passphrase).await?;
+    Ok(db.encryption_status().await)
+}
+
+#[tauri::command]
+pub async fn enable_encryption(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<EnableEncryptionResult> {
+    let db = &state.db;
+    db.enable_encryption(&passphrase).await?;
+
+    let plaintext_backups: Vec<String> = match BackupManager::for_database(db) {
+        Some(manager) => manager.list().await?.into_iter().map(|b| b.id).collect(),
+        None => vec![],
+    };
+    if !plaintext_backups.is_empty() {
+        log::warn!(
+            "{} backups were taken before encryption and still hold plaintext",
+            plaintext_backups.len()
+        );
+    }
+
+    Ok(EnableEncryptionResult {
+        status: db.encryption_status().await,
+        plaintext_backups,
+    })
+}
+
+#[tauri::command]
+pub async fn change_passphrase(
+    state: State<'_, AppState>,
+    current_passphrase: String,
+    new_passphrase: String,
+) -> Result<EncryptionStatus> {
+    let db = &state.db;
+    db.change_passphrase(&current_passphrase, &new_passphrase)
+        .await?;
+    Ok(db.encryption_status().await)
+}
+
+#[tauri::command]
+pub async fn disable_encryption(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<EncryptionStatus> {
+    let db = &state.db;
+    db.disable_encryption(&passphrase).await?;
+    Ok(db.encryption_status().await)
 }
 
 #[tauri::command]
 pub async fn trigger_accountability_check(
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<String, String> {
-    let db = state.db.lock().await;
-    let tasks = db.get_all_tasks().await.map_err(|e| e.to_string())?;
-    drop(db);
-
-    let ai_engine = state.ai_engine.lock().await;
-    let message = ai_engine
-        .generate_accountability_message(&tasks)
-        .await
-        .map_err(|e| e.to_string())?;
-    drop(ai_engine);
+) -> Result<String> {
+    let tasks = state.repos.tasks.get_all_tasks().await?;
+
+    let ai_engine = ai_engine::AIEngine::current(&state.ai_engine).await;
+    let message = ai_engine.generate_accountability_message(&tasks).await?;
 
     // Send notification
     let notifications = state.notifications.lock().await;
     notifications
         .send_accountability_notification(&message, &app_handle)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     drop(notifications);
 
     // Emit to frontend
     app_handle
         .emit_all("accountability_check", &message)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Internal(e.to_string()))?;
 
     Ok(message)
 }
+
+#[tauri::command]
+pub async fn export_data(
+    state: State<'_, AppState>,
+    path: String,
+    format: ExportFormat,
+) -> Result<String> {
+    let content = match format {
+        ExportFormat::Json => {
+            let settings = state.settings.lock().await.get().clone();
+            let bundle = transfer::export_bundle(&state.repos, &settings).await?;
+            transfer::bundle_to_json(&bundle)?
+        }
+        ExportFormat::Csv => {
+            let tasks = state.repos.tasks.get_all_tasks().await?;
+            transfer::tasks_to_csv(&tasks)?
+        }
+        ExportFormat::Ical => {
+            let tasks = state.repos.tasks.get_all_tasks().await?;
+            ical::tasks_to_ics(&tasks)
+        }
+    };
+
+    tokio::fs::write(&path, content).await?;
+
+    Ok(path)
+}
+
+/// A file that cannot be parsed is bad input rather than an app fault.
+fn invalid_file(path: &str, e: AppError) -> AppError {
+    AppError::validation("path", format!("Could not read {}: {}", path, e))
+}
+
+#[tauri::command]
+pub async fn import_data(
+    state: State<'_, AppState>,
+    path: String,
+    format: ExportFormat,
+    mode: MergeMode,
+    dry_run: Option<bool>,
+) -> Result<ImportReport> {
+    let content = tokio::fs::read_to_string(&path).await?;
+
+    let mut warnings = vec![];
+    let bundle = match format {
+        ExportFormat::Json => {
+            transfer::bundle_from_json(&content).map_err(|e| invalid_file(&path, e))?
+        }
+        ExportFormat::Csv => transfer::tasks_bundle(
+            transfer::tasks_from_csv(&content).map_err(|e| invalid_file(&path, e))?,
+        ),
+        ExportFormat::Ical => {
+            let imported = ical::tasks_from_ics(&content).map_err(|e| invalid_file(&path, e))?;
+            warnings = imported.warnings;
+            transfer::tasks_bundle(imported.tasks)
+        }
+    };
+
+    // Reject bad settings before any of the bundle is written
+    if let (Some(settings), MergeMode::Overwrite) = (&bundle.settings, mode) {
+        settings::validate(settings)?;
+    }
+
+    let dry_run = dry_run.unwrap_or(false);
+    let (mut report, settings) =
+        transfer::import_bundle(&state.repos, bundle, mode, dry_run).await?;
+    report.warnings.extend(warnings);
+
+    if let (Some(mut settings), false) = (settings, dry_run) {
+        let mut manager = state.settings.lock().await;
+        // Keep this machine's data location and sync folder
+        settings.data_directory = manager.get().data_directory.clone();
+        settings.sync = manager.get().sync.clone();
+        manager.update(settings).await?;
+        let settings = manager.get().clone();
+        drop(manager);
+
+        let mut ai_engine = state.ai_engine.lock().await;
+        ai_engine.set_eisenhower_thresholds(settings.eisenhower);
+        ai_engine.set_ai_settings(&settings.ai);
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn import_tasks(
+    state: State<'_, AppState>,
+    path: String,
+    source: ImportSource,
+    mode: MergeMode,
+    dry_run: Option<bool>,
+) -> Result<ImportReport> {
+    let content = tokio::fs::read_to_string(&path).await?;
+    let imported = importers::parse(source, &content).map_err(|e| invalid_file(&path, e))?;
+
+    let bundle = transfer::tasks_bundle(imported.tasks);
+    let (mut report, _) =
+        transfer::import_bundle(&state.repos, bundle, mode, dry_run.unwrap_or(false)).await?;
+    report.warnings.extend(imported.warnings);
+
+    Ok(report)
+}
+
+/// One-shot migration of the old Node server's `data/tasks.json`. Tasks
+/// already imported are left alone, so it is safe to run again.
+#[tauri::command]
+pub async fn import_legacy_tasks(
+    state: State<'_, AppState>,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<ImportReport> {
+    let content = tokio::fs::read_to_string(&path).await?;
+    let imported = importers::parse_legacy_tasks(&content).map_err(|e| invalid_file(&path, e))?;
+
+    let bundle = transfer::tasks_bundle(imported.tasks);
+    let (mut report, _) = transfer::import_bundle(
+        &state.repos,
+        bundle,
+        MergeMode::Skip,
+        dry_run.unwrap_or(false),
+    )
+    .await?;
+    report.warnings.extend(imported.warnings);
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn update_sync_settings(
+    state: State<'_, AppState>,
+    sync: SyncSettings,
+) -> Result<SyncSettings> {
+    if sync.enabled && sync.directory.is_none() {
+        return Err(AppError::validation(
+            "sync.directory",
+            "Choose a sync folder before enabling sync",
+        ));
+    }
+    if let Some(directory) = &sync.directory {
+        if !directory.is_absolute() {
+            return Err(AppError::validation(
+                "sync.directory",
+                format!(
+                    "Sync folder must be an absolute path, got {}",
+                    directory.display()
+                ),
+            ));
+        }
+    }
+
+    let mut manager = state.settings.lock().await;
+    let mut settings = manager.get().clone();
+    settings.sync = sync;
+    manager.update(settings).await?;
+    Ok(manager.get().sync.clone())
+}
+
+#[tauri::command]
+pub async fn sync_now(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<SyncReport> {
+    let settings = state.settings.lock().await;
+    let directory = settings.get().sync.directory.clone();
+    drop(settings);
+
+    let directory = directory
+        .ok_or_else(|| AppError::validation("sync.directory", "No sync folder is configured"))?;
+    if state.db.encryption_status().await.enabled {
+        return Err(AppError::Unsupported(
+            "Folder sync stores tasks unencrypted and is unavailable while the database is encrypted"
+                .to_string(),
+        ));
+    }
+
+    let engine = state.sync.lock().await;
+    let database_id = state.db.database_id().await?;
+    let report = engine
+        .sync(&directory, &database_id, state.repos.tasks.as_ref())
+        .await?;
+    drop(engine);
+
+    if report.applied > 0 {
+        app_handle
+            .emit_all("tasks_synced", &report)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    Ok(report)
+}
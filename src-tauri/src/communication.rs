@@ -1,8 +1,16 @@
-use anyhow::Result;
+use chrono::{DateTime, NaiveDate, TimeZone, Timelike, Utc};
 use log::{error, info, warn};
+use std::collections::BTreeMap;
 use std::env;
 
-use crate::models::CommunicationActivity;
+use crate::error::{AppError, Result};
+use crate::models::{
+    CommunicationActivity, CommunicationDailyPoint, CommunicationPoint, CommunicationSnapshot,
+    CommunicationTrends,
+};
+
+/// How long sync snapshots are kept for trends.
+pub const HISTORY_DAYS: i64 = 90;
 
 pub struct CommunicationManager {
     gmail_enabled: bool,
@@ -97,7 +105,10 @@ impl CommunicationManager {
             }
             _ => {
                 error!("Unknown service requested: {}", service);
-                Err(anyhow::anyhow!("Unknown service: {}", service))
+                Err(AppError::validation(
+                    "service",
+                    format!("Unknown service: {}", service),
+                ))
             }
         }
     }
@@ -164,7 +175,10 @@ impl CommunicationManager {
                 mentions: 0,
                 keywords_detected: vec![],
             }),
-            _ => Err(anyhow::anyhow!("Unknown service: {}", service)),
+            _ => Err(AppError::validation(
+                "service",
+                format!("Unknown service: {}", service),
+            )),
         }
     }
 
@@ -177,3 +191,162 @@ impl CommunicationManager {
         }
     }
 }
+
+/// Start of the hour containing `at`, in `tz`.
+fn hour_start<Tz: TimeZone>(at: DateTime<Utc>, tz: &Tz) -> DateTime<Utc> {
+    let local = at.with_timezone(tz);
+    let hours = local.with_minute(0).and_then(|dt| dt.with_second(0));
+    hours
+        .and_then(|dt| dt.with_nanosecond(0))
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or(at)
+}
+
+/// Unread backlog at each sync, messages seen per hour and mentions per day,
+/// with hours and days in `tz`. `message_count` and `mentions` are what one
+/// sync picked up, so they are summed; the backlog is a level, so it is not.
+pub fn trends<Tz: TimeZone>(snapshots: &[CommunicationSnapshot], tz: &Tz) -> CommunicationTrends {
+    let mut hourly: BTreeMap<(DateTime<Utc>, &str), i32> = BTreeMap::new();
+    let mut daily: BTreeMap<(NaiveDate, &str), i32> = BTreeMap::new();
+
+    for snapshot in snapshots {
+        let service = snapshot.service.as_str();
+        *hourly
+            .entry((hour_start(snapshot.captured_at, tz), service))
+            .or_default() += snapshot.message_count;
+        *daily
+            .entry((snapshot.captured_at.with_timezone(tz).date_naive(), service))
+            .or_default() += snapshot.mentions;
+    }
+
+    let mut unread_backlog: Vec<CommunicationPoint> = snapshots
+        .iter()
+        .map(|snapshot| CommunicationPoint {
+            service: snapshot.service.clone(),
+            at: snapshot.captured_at,
+            value: snapshot.unread_count,
+        })
+        .collect();
+    unread_backlog.sort_by(|a, b| a.at.cmp(&b.at).then_with(|| a.service.cmp(&b.service)));
+
+    CommunicationTrends {
+        unread_backlog,
+        hourly_volume: hourly
+            .into_iter()
+            .map(|((at, service), value)| CommunicationPoint {
+                service: service.to_string(),
+                at,
+                value,
+            })
+            .collect(),
+        daily_mentions: daily
+            .into_iter()
+            .map(|((date, service), value)| CommunicationDailyPoint {
+                service: service.to_string(),
+                date,
+                value,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    /// A half-hour offset, so local hours do not line up with UTC ones.
+    fn tz() -> FixedOffset {
+        FixedOffset::east_opt(5 * 3600 + 1800).unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 10, hour, minute, 0).unwrap()
+    }
+
+    fn snapshot(
+        service: &str,
+        captured_at: DateTime<Utc>,
+        messages: i32,
+        mentions: i32,
+        unread: i32,
+    ) -> CommunicationSnapshot {
+        CommunicationSnapshot {
+            service: service.to_string(),
+            message_count: messages,
+            unread_count: unread,
+            mentions,
+            keywords_detected: vec![],
+            captured_at,
+        }
+    }
+
+    #[test]
+    fn hour_start_truncates_to_the_local_hour() {
+        let precise = at(18, 40) + chrono::Duration::nanoseconds(123_456_789);
+        assert_eq!(hour_start(precise, &tz()), at(18, 30));
+        assert_eq!(hour_start(at(18, 20), &tz()), at(17, 30));
+        assert_eq!(hour_start(precise, &Utc), at(18, 0));
+    }
+
+    #[test]
+    fn trends_bucket_by_local_hour_and_day() {
+        let snapshots = [
+            snapshot("gmail", at(18, 50), 4, 1, 1),
+            snapshot("discord", at(18, 45), 1, 0, 2),
+            snapshot("gmail", at(18, 20), 3, 1, 5), // 23:50 local, March 10
+            snapshot("gmail", at(18, 40), 2, 2, 4), // 00:10 local, March 11
+        ];
+
+        let trends = trends(&snapshots, &tz());
+
+        let points = |series: &[CommunicationPoint]| -> Vec<(String, DateTime<Utc>, i32)> {
+            series
+                .iter()
+                .map(|p| (p.service.clone(), p.at, p.value))
+                .collect()
+        };
+        let point = |service: &str, at: DateTime<Utc>, value| (service.to_string(), at, value);
+
+        assert_eq!(
+            points(&trends.unread_backlog),
+            [
+                point("gmail", at(18, 20), 5),
+                point("gmail", at(18, 40), 4),
+                point("discord", at(18, 45), 2),
+                point("gmail", at(18, 50), 1),
+            ]
+        );
+        assert_eq!(
+            points(&trends.hourly_volume),
+            [
+                point("gmail", at(17, 30), 3),
+                point("discord", at(18, 30), 1),
+                point("gmail", at(18, 30), 6),
+            ]
+        );
+
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mentions: Vec<(String, NaiveDate, i32)> = trends
+            .daily_mentions
+            .iter()
+            .map(|p| (p.service.clone(), p.date, p.value))
+            .collect();
+        assert_eq!(
+            mentions,
+            [
+                ("gmail".to_string(), day(10), 1),
+                ("discord".to_string(), day(11), 0),
+                ("gmail".to_string(), day(11), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn trends_of_no_snapshots_are_empty() {
+        let trends = trends(&[], &tz());
+        assert!(trends.unread_backlog.is_empty());
+        assert!(trends.hourly_volume.is_empty());
+        assert!(trends.daily_mentions.is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::error::{AppError, Result};
+
+/// Marks a column value as ciphertext, so rows written before encryption was
+/// enabled can still be told apart and read.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// Encrypts individual text columns with XChaCha20-Poly1305, using a key
+/// derived from the user's passphrase with Argon2id.
+pub struct FieldCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl FieldCipher {
+    pub fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(AppError::validation(
+                "passphrase",
+                "Passphrase must not be empty",
+            ));
+        }
+
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| AppError::Internal(format!("Key derivation failed: {}", e)))?;
+
+        Ok(Self {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        })
+    }
+
+    pub fn generate_salt() -> Vec<u8> {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(PREFIX)
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| AppError::Internal("Encryption failed".to_string()))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+
+        Ok(format!("{}{}", PREFIX, BASE64.encode(payload)))
+    }
+
+    /// Decrypts a value produced by `encrypt`. Plaintext values are returned
+    /// unchanged.
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let encoded = match value.strip_prefix(PREFIX) {
+            Some(encoded) => encoded,
+            None => return Ok(value.to_string()),
+        };
+
+        let payload = BASE64
+            .decode(encoded)
+            .map_err(|e| AppError::Internal(format!("Encrypted value is not base64: {}", e)))?;
+        if payload.len() < NONCE_LEN {
+            return Err(AppError::Internal(
+                "Encrypted value is truncated".to_string(),
+            ));
+        }
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                AppError::Internal(
+                    "Decryption failed: wrong passphrase or corrupted data".to_string(),
+                )
+            })?;
+
+        String::from_utf8(plaintext)
+            .map_err(|_| AppError::Internal("Decrypted value is not UTF-8".to_string()))
+    }
+}
@@ -1,234 +1,1496 @@
-use anyhow::Result;
-use chrono::Utc;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Local, Utc};
 use log::info;
-use sqlx::{Row, SqlitePool};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Executor, Row, Sqlite, SqlitePool, Transaction};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
 
+use crate::crypto::FieldCipher;
+use crate::error::{AppError, Result};
+use crate::migrations;
 use crate::models::*;
+use crate::repository::{
+    notification_page, notification_page_size, parse_notification_cursor, AchievementRepository,
+    CommunicationRepository, ImportBatch, ImportRepository, InsightRepository,
+    NotificationRepository, TaskRepository,
+};
+use crate::stats::{self, TaskActivity};
 
+const DB_FILE_NAME: &str = "app.db";
+
+/// Connections in the pool. SQLite allows one writer at a time, but under WAL
+/// any number of readers can run alongside it.
+const MAX_CONNECTIONS: u32 = 8;
+
+/// How long a statement waits on a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Columns shared by `tasks` and `archived_tasks`.
+const TASK_COLUMNS: &str =
+    "id, title, description, priority, priority_pinned, importance, status, \
+     category, project, estimated_time, actual_time, due_date, created_at, updated_at, completed_at";
+
+/// Columns of `task_history` read into `TaskActivity`.
+const ACTIVITY_COLUMNS: &str =
+    "category, project, priority, status, due_date, created_at, completed_at, actual_time";
+
+/// Where the database lives: `app.db` inside a data directory, or a private
+/// in-memory database (used by tests and throwaway sessions).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseLocation {
+    Directory(PathBuf),
+    InMemory,
+}
+
+impl DatabaseLocation {
+    fn parse(value: &str) -> Self {
+        if value == ":memory:" {
+            Self::InMemory
+        } else {
+            Self::Directory(PathBuf::from(value))
+        }
+    }
+
+    /// Resolves the data directory, in order of precedence: the `--data-dir`
+    /// command-line argument, the `DATA_STORAGE_PATH` environment variable,
+    /// the settings file, and finally the default config directory.
+    pub fn resolve(settings: &AppSettings) -> Result<Self> {
+        Ok(Self::resolve_with(
+            Self::override_value(),
+            settings,
+            &Self::default_directory()?,
+        ))
+    }
+
+    /// Relative paths are taken from the default directory rather than the
+    /// working directory, which changes with how the app is launched.
+    fn resolve_with(
+        override_value: Option<String>,
+        settings: &AppSettings,
+        default: &Path,
+    ) -> Self {
+        let location = match override_value {
+            Some(value) => Self::parse(&value),
+            None => match &settings.data_directory {
+                Some(dir) => Self::Directory(dir.clone()),
+                None => return Self::Directory(default.to_path_buf()),
+            },
+        };
+
+        match location {
+            Self::Directory(dir) if dir.is_relative() => Self::Directory(default.join(dir)),
+            location => location,
+        }
+    }
+
+    /// The location forced by the command line or environment, if any. It
+    /// takes precedence over the settings file.
+    pub fn override_value() -> Option<String> {
+        Self::override_from(
+            std::env::args().skip(1),
+            std::env::var("DATA_STORAGE_PATH").ok(),
+        )
+    }
+
+    fn override_from(
+        mut args: impl Iterator<Item = String>,
+        env_value: Option<String>,
+    ) -> Option<String> {
+        while let Some(arg) = args.next() {
+            if arg == "--data-dir" {
+                return args.next();
+            }
+            if let Some(value) = arg.strip_prefix("--data-dir=") {
+                return Some(value.to_string());
+            }
+        }
+
+        env_value.filter(|value| !value.is_empty())
+    }
+
+    pub fn default_directory() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or_else(|| AppError::Internal("Could not find config directory".to_string()))?
+            .join("ChiCanDoIt"))
+    }
+}
+
+/// Columns that hold user content and are encrypted when encryption at rest
+/// is enabled.
+const SENSITIVE_COLUMNS: &[(&str, &[&str])] = &[
+    ("tasks", &["title", "description"]),
+    ("archived_tasks", &["title", "description"]),
+    ("priority_escalations", &["task_title"]),
+    ("notifications", &["message"]),
+    ("ai_insights", &["message"]),
+    ("communication_activity", &["keywords_detected"]),
+    ("communication_snapshots", &["keywords_detected"]),
+];
+
+const PRIORITIES: &[&str] = &["low", "medium", "high", "critical"];
+const IMPORTANCES: &[&str] = &["auto", "important", "unimportant"];
+const TASK_STATUSES: &[&str] = &["pending", "inprogress", "completed", "paused", "cancelled"];
+
+/// How `check` repairs a row holding an unknown enum value.
+#[derive(Clone, Copy)]
+enum Repair {
+    Set(&'static str), // SQL expression for the replacement
+    Delete,            // for rows the app regenerates anyway
+    Manual,
+}
+
+/// Enum columns, the values the app can read, and how to repair others.
+/// Tables created before versioned migrations may lack the CHECK lists.
+const ENUM_COLUMNS: &[(&str, &str, &[&str], Repair)] = &[
+    ("tasks", "priority", PRIORITIES, Repair::Set("'medium'")),
+    (
+        "tasks",
+        "status",
+        TASK_STATUSES,
+        Repair::Set("CASE WHEN completed_at IS NULL THEN 'pending' ELSE 'completed' END"),
+    ),
+    ("tasks", "importance", IMPORTANCES, Repair::Set("'auto'")),
+    (
+        "archived_tasks",
+        "priority",
+        PRIORITIES,
+        Repair::Set("'medium'"),
+    ),
+    (
+        "archived_tasks",
+        "importance",
+        IMPORTANCES,
+        Repair::Set("'auto'"),
+    ),
+    (
+        "ai_insights",
+        "insight_type",
+        &[
+            "productivitytip",
+            "taskprioritization",
+            "timemanagement",
+            "patternrecognition",
+            "accountability",
+        ],
+        Repair::Delete,
+    ),
+    (
+        "notifications",
+        "notification_type",
+        &[
+            "accountability",
+            "taskreminder",
+            "deadline",
+            "achievement",
+            "communication",
+            "insight",
+        ],
+        Repair::Delete,
+    ),
+    (
+        "priority_escalations",
+        "from_priority",
+        PRIORITIES,
+        Repair::Manual,
+    ),
+    (
+        "priority_escalations",
+        "to_priority",
+        PRIORITIES,
+        Repair::Manual,
+    ),
+];
+
+/// Tables holding tasks, for the checks that apply to live and archived
+/// tasks alike.
+const TASK_TABLES: &[&str] = &["tasks", "archived_tasks"];
+
+/// Columns holding a JSON array of keywords.
+const KEYWORD_TABLES: &[&str] = &["communication_activity", "communication_snapshots"];
+
+fn sql_list(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|v| format!("'{}'", v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Known plaintext stored encrypted, used to check a passphrase.
+const ENCRYPTION_VERIFIER: &str = "chicandoit-encryption-check";
+
+/// Encryption state shared by every clone of a `Database`.
+struct Keys {
+    enabled: bool,
+    cipher: Option<FieldCipher>,
+}
+
+impl Keys {
+    /// The cipher for sensitive columns, or `None` when encryption is off.
+    /// Fails while the database is locked.
+    fn cipher(&self) -> Result<Option<&FieldCipher>> {
+        match (&self.cipher, self.enabled) {
+            (Some(cipher), _) => Ok(Some(cipher)),
+            (None, false) => Ok(None),
+            (None, true) => Err(AppError::Locked),
+        }
+    }
+
+    fn seal(&self, value: &str) -> Result<String> {
+        match self.cipher()? {
+            Some(cipher) => Ok(cipher.encrypt(value)?),
+            None => Ok(value.to_string()),
+        }
+    }
+
+    fn seal_opt(&self, value: &Option<String>) -> Result<Option<String>> {
+        value.as_deref().map(|v| self.seal(v)).transpose()
+    }
+
+    fn unseal(&self, value: String) -> Result<String> {
+        if !FieldCipher::is_encrypted(&value) {
+            return Ok(value);
+        }
+
+        match self.cipher()? {
+            Some(cipher) => Ok(cipher.decrypt(&value)?),
+            None => Err(AppError::Internal(
+                "Found encrypted data but encryption is not enabled".to_string(),
+            )),
+        }
+    }
+
+    fn unseal_task(&self, mut task: Task) -> Result<Task> {
+        task.title = self.unseal(task.title)?;
+        task.description = task.description.map(|d| self.unseal(d)).transpose()?;
+        Ok(task)
+    }
+
+    fn unseal_tasks(&self, tasks: Vec<Task>) -> Result<Vec<Task>> {
+        tasks.into_iter().map(|t| self.unseal_task(t)).collect()
+    }
+
+    fn unseal_insights(&self, insights: Vec<AIInsight>) -> Result<Vec<AIInsight>> {
+        insights
+            .into_iter()
+            .map(|mut insight| {
+                insight.message = self.unseal(insight.message)?;
+                Ok(insight)
+            })
+            .collect()
+    }
+
+    fn unseal_archived(&self, tasks: Vec<ArchivedTask>) -> Result<Vec<ArchivedTask>> {
+        tasks
+            .into_iter()
+            .map(|mut archived| {
+                archived.task = self.unseal_task(archived.task)?;
+                Ok(archived)
+            })
+            .collect()
+    }
+}
+
+fn issue(
+    kind: DatabaseIssueKind,
+    table: Option<&str>,
+    row_id: Option<String>,
+    column: Option<&str>,
+    detail: String,
+) -> DatabaseIssue {
+    DatabaseIssue {
+        kind,
+        table: table.map(str::to_string),
+        row_id,
+        column: column.map(str::to_string),
+        detail,
+        fixable: false,
+        fixed: false,
+    }
+}
+
+/// Handle to the app database. Clones share one connection pool, so it can be
+/// handed to every command and background job without a lock around it.
+#[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
+    location: DatabaseLocation,
+    // Writers hold the read side while sealing and storing a row; key changes
+    // take the write side so nothing is stored under a stale key
+    keys: Arc<RwLock<Keys>>,
 }
 
 impl Database {
-    pub async fn new() -> Result<Self> {
-        // Create app data directory
-        let app_dir = dirs::config_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
-            .join("ChiCanDoIt");
+    pub async fn open(location: DatabaseLocation) -> Result<Self> {
+        let pool = match &location {
+            DatabaseLocation::Directory(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
 
-        tokio::fs::create_dir_all(&app_dir).await?;
+                let path = dir.join(DB_FILE_NAME);
+                info!("Initializing database at: {}", path.display());
 
-        let db_path = app_dir.join("app.db");
-        let database_url = format!("sqlite://{}?mode=rwc", db_path.display());
+                // WAL lets readers run alongside a writer; the busy timeout
+                // makes a second writer wait for the first instead of failing
+                let options = SqliteConnectOptions::new()
+                    .filename(&path)
+                    .create_if_missing(true)
+                    .journal_mode(SqliteJournalMode::Wal)
+                    .synchronous(SqliteSynchronous::Normal)
+                    .busy_timeout(BUSY_TIMEOUT);
 
-        info!("Initializing database at: {}", database_url);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(MAX_CONNECTIONS)
+                    .min_connections(1)
+                    .acquire_timeout(BUSY_TIMEOUT)
+                    .connect_with(options)
+                    .await?;
+                migrations::run(&pool, Some(&dir.join("backups").join("pre-migration"))).await?;
+                pool
+            }
+            DatabaseLocation::InMemory => {
+                info!("Initializing in-memory database");
 
-        let pool = SqlitePool::connect(&database_url).await?;
+                // Every connection gets its own in-memory database, so keep
+                // exactly one alive for the lifetime of the pool
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .min_connections(1)
+                    .idle_timeout(None)
+                    .max_lifetime(None)
+                    .connect("sqlite::memory:")
+                    .await?;
+                migrations::run(&pool, None).await?;
+                pool
+            }
+        };
+
+        let encryption_enabled: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM encryption)")
+                .fetch_one(&pool)
+                .await?;
 
-        let db = Self { pool };
-        db.run_migrations().await?;
+        if encryption_enabled {
+            info!("Database is encrypted and locked until a passphrase is entered");
+        }
 
-        Ok(db)
+        Ok(Self {
+            pool,
+            location,
+            keys: Arc::new(RwLock::new(Keys {
+                enabled: encryption_enabled,
+                cipher: None,
+            })),
+        })
     }
 
-    async fn run_migrations(&self) -> Result<()> {
-        info!("Running database migrations...");
+    pub async fn in_memory() -> Result<Self> {
+        Self::open(DatabaseLocation::InMemory).await
+    }
 
-        // Create tasks table
-        sqlx::query(
+    /// Makes every write fail, for exercising error paths.
+    #[cfg(test)]
+    pub async fn set_read_only(&self, read_only: bool) {
+        sqlx::query(&format!("PRAGMA query_only = {}", read_only))
+            .execute(&self.pool)
+            .await
+            .unwrap();
+    }
+
+    pub fn data_dir(&self) -> Option<&Path> {
+        match &self.location {
+            DatabaseLocation::Directory(dir) => Some(dir),
+            DatabaseLocation::InMemory => None,
+        }
+    }
+
+    /// Writes a consistent snapshot of the live database to `path`.
+    pub async fn copy_to(&self, path: &Path) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Opens the database file at `path` read-only and runs SQLite's
+    /// integrity check on it.
+    pub async fn verify_file(path: &Path) -> Result<()> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+            .await?;
+
+        let result: String = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_one(&pool)
+            .await?;
+        pool.close().await;
+
+        if result != "ok" {
+            return Err(AppError::Internal(format!(
+                "Integrity check failed for {}: {}",
+                path.display(),
+                result
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the database can be moved into `new_dir` and returns the
+    /// path it will be moved to.
+    pub async fn prepare_move(&self, new_dir: &Path) -> Result<PathBuf> {
+        if self.data_dir().is_none() {
+            return Err(AppError::Unsupported(
+                "An in-memory database cannot be moved".to_string(),
+            ));
+        }
+        if self.data_dir() == Some(new_dir) {
+            return Err(AppError::validation(
+                "new_directory",
+                format!("Data is already stored in {}", new_dir.display()),
+            ));
+        }
+
+        tokio::fs::create_dir_all(new_dir).await?;
+
+        let target = new_dir.join(DB_FILE_NAME);
+        if tokio::fs::try_exists(&target).await? {
+            return Err(AppError::Conflict(format!(
+                "A database already exists at {}",
+                target.display()
+            )));
+        }
+
+        Ok(target)
+    }
+
+    /// Closes the database and copies its files to `target`, verifying the
+    /// copy. Closing first means no clone can write to the old file after it
+    /// has been copied. The old files are left in place; call `remove_files`
+    /// once the new location has been saved.
+    pub async fn move_files_to(&self, target: &Path) -> Result<()> {
+        let dir = self.data_dir().ok_or_else(|| {
+            AppError::Unsupported("An in-memory database cannot be moved".to_string())
+        })?;
+
+        self.close().await;
+
+        let result = async {
+            let source = dir.join(DB_FILE_NAME);
+            tokio::fs::copy(&source, target).await?;
+
+            // SQLite normally checkpoints on close, but carry over any WAL
+            // that is left behind so committed writes are not lost
+            let wal = dir.join(format!("{}-wal", DB_FILE_NAME));
+            if tokio::fs::try_exists(&wal).await? {
+                tokio::fs::copy(&wal, format!("{}-wal", target.display())).await?;
+            }
+
+            Self::verify_file(target).await
+        }
+        .await;
+
+        if result.is_err() {
+            if let Some(new_dir) = target.parent() {
+                let _ = Self::remove_files(new_dir).await;
+            }
+        }
+
+        result
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Closes the database and overwrites its file with a copy of `source`.
+    pub async fn replace_with(&self, source: &Path) -> Result<()> {
+        let dir = self.data_dir().ok_or_else(|| {
+            AppError::Unsupported("An in-memory database cannot be replaced".to_string())
+        })?;
+
+        self.close().await;
+        Self::remove_files(dir).await?;
+        let path = dir.join(DB_FILE_NAME);
+        tokio::fs::copy(source, &path).await?;
+        Self::assign_new_id(&path).await?;
+
+        Ok(())
+    }
+
+    /// Identifies this database to state kept outside it. Changes when a
+    /// backup is restored over it.
+    pub async fn database_id(&self) -> Result<String> {
+        let id = sqlx::query_scalar("SELECT database_id FROM database_identity WHERE id = 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    /// Gives the database file at `path` a fresh id. Files from before ids
+    /// existed get one when they are migrated.
+    async fn assign_new_id(path: &Path) -> Result<()> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::new().filename(path))
+            .await?;
+
+        let has_id: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'database_identity')",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if has_id {
+            sqlx::query("UPDATE database_identity SET database_id = ? WHERE id = 1")
+                .bind(uuid::Uuid::new_v4().simple().to_string())
+                .execute(&pool)
+                .await?;
+        }
+        pool.close().await;
+
+        Ok(())
+    }
+
+    /// Deletes `app.db` and its WAL/shared-memory files from `dir`.
+    pub async fn remove_files(dir: &Path) -> Result<()> {
+        for suffix in ["", "-wal", "-shm"] {
+            let path = dir.join(format!("{}{}", DB_FILE_NAME, suffix));
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Encryption operations
+    pub async fn encryption_status(&self) -> EncryptionStatus {
+        let keys = self.keys.read().await;
+        EncryptionStatus {
+            enabled: keys.enabled,
+            locked: keys.enabled && keys.cipher.is_none(),
+        }
+    }
+
+    /// Derives the key for `passphrase` and checks it against the stored
+    /// verifier.
+    async fn load_cipher(&self, passphrase: &str) -> Result<FieldCipher> {
+        let (salt, verifier): (String, String) =
+            sqlx::query_as("SELECT salt, verifier FROM encryption WHERE id = 1")
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or_else(|| AppError::Conflict("Encryption is not enabled".to_string()))?;
+
+        let salt = BASE64
+            .decode(salt)
+            .map_err(|e| AppError::Internal(format!("Stored salt is invalid: {}", e)))?;
+        let cipher = FieldCipher::derive(passphrase, &salt)?;
+        match cipher.decrypt(&verifier) {
+            Ok(plaintext) if plaintext == ENCRYPTION_VERIFIER => Ok(cipher),
+            _ => Err(AppError::WrongPassphrase),
+        }
+    }
+
+    pub async fn unlock(&self, passphrase: &str) -> Result<()> {
+        let cipher = self.load_cipher(passphrase).await?;
+        self.keys.write().await.cipher = Some(cipher);
+
+        info!("Database unlocked");
+        Ok(())
+    }
+
+    pub async fn enable_encryption(&self, passphrase: &str) -> Result<()> {
+        let mut keys = self.keys.write().await;
+        if keys.enabled {
+            return Err(AppError::Conflict(
+                "Encryption is already enabled".to_string(),
+            ));
+        }
+
+        let salt = FieldCipher::generate_salt();
+        let cipher = FieldCipher::derive(passphrase, &salt)?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::rewrite_sensitive_columns(&mut tx, None, Some(&cipher)).await?;
+        sqlx::query("INSERT INTO encryption (id, salt, verifier, created_at) VALUES (1, ?, ?, ?)")
+            .bind(BASE64.encode(&salt))
+            .bind(cipher.encrypt(ENCRYPTION_VERIFIER)?)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        keys.enabled = true;
+        keys.cipher = Some(cipher);
+        self.scrub_plaintext().await?;
+
+        info!("Encryption at rest enabled");
+        Ok(())
+    }
+
+    /// Overwrites what the rewrite left behind: the old plaintext sits in
+    /// freed pages and in WAL frames until both are cleared.
+    async fn scrub_plaintext(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA secure_delete = ON")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Re-encrypts every sensitive column under a key derived from
+    /// `new_passphrase`.
+    pub async fn change_passphrase(
+        &self,
+        current_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<()> {
+        let mut keys = self.keys.write().await;
+        let current = self.load_cipher(current_passphrase).await?;
+
+        let salt = FieldCipher::generate_salt();
+        let cipher = FieldCipher::derive(new_passphrase, &salt)?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::rewrite_sensitive_columns(&mut tx, Some(&current), Some(&cipher)).await?;
+        sqlx::query("UPDATE encryption SET salt = ?, verifier = ?, created_at = ? WHERE id = 1")
+            .bind(BASE64.encode(&salt))
+            .bind(cipher.encrypt(ENCRYPTION_VERIFIER)?)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        keys.cipher = Some(cipher);
+
+        info!("Database re-keyed");
+        Ok(())
+    }
+
+    pub async fn disable_encryption(&self, passphrase: &str) -> Result<()> {
+        let mut keys = self.keys.write().await;
+        let current = self.load_cipher(passphrase).await?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::rewrite_sensitive_columns(&mut tx, Some(&current), None).await?;
+        sqlx::query("DELETE FROM encryption")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        keys.enabled = false;
+        keys.cipher = None;
+
+        info!("Encryption at rest disabled");
+        Ok(())
+    }
+
+    async fn rewrite_sensitive_columns(
+        tx: &mut Transaction<'_, Sqlite>,
+        from: Option<&FieldCipher>,
+        to: Option<&FieldCipher>,
+    ) -> Result<()> {
+        for (table, columns) in SENSITIVE_COLUMNS {
+            let rows = sqlx::query(&format!("SELECT id, {} FROM {}", columns.join(", "), table))
+                .fetch_all(&mut **tx)
+                .await?;
+
+            let assignments: Vec<String> = columns.iter().map(|c| format!("{} = ?", c)).collect();
+            let update_sql = format!(
+                "UPDATE {} SET {} WHERE id = ?",
+                table,
+                assignments.join(", ")
+            );
+
+            for row in rows {
+                let mut query = sqlx::query(&update_sql);
+                for column in columns.iter() {
+                    let value: Option<String> = row.get(*column);
+                    let value = match value {
+                        Some(value) => {
+                            let plaintext = match from {
+                                Some(cipher) => cipher.decrypt(&value)?,
+                                None => value,
+                            };
+                            Some(match to {
+                                Some(cipher) => cipher.encrypt(&plaintext)?,
+                                None => plaintext,
+                            })
+                        }
+                        None => None,
+                    };
+                    query = query.bind(value);
+                }
+
+                let id: String = row.get("id");
+                query.bind(id).execute(&mut **tx).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encrypted titles cannot be matched in SQL, so decrypt and filter here.
+    async fn search_encrypted_archive(
+        &self,
+        keys: &Keys,
+        query: &str,
+        limit: i32,
+    ) -> Result<Vec<ArchivedTask>> {
+        let query = query.to_lowercase();
+        let tasks = sqlx::query_as::<_, ArchivedTask>(
+            "SELECT * FROM archived_tasks ORDER BY archived_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let matches = keys
+            .unseal_archived(tasks)?
+            .into_iter()
+            .filter(|archived| {
+                let task = &archived.task;
+                task.title.to_lowercase().contains(&query)
+                    || task.category.to_lowercase().contains(&query)
+                    || task
+                        .description
+                        .as_ref()
+                        .is_some_and(|d| d.to_lowercase().contains(&query))
+            })
+            .take(limit.max(0) as usize)
+            .collect();
+
+        Ok(matches)
+    }
+
+    /// Runs SQLite's integrity and foreign key checks, then looks for rows
+    /// the app would misread. With `fix`, repairs every fixable issue in one
+    /// transaction; damage found by SQLite itself is only reported.
+    pub async fn check(&self, fix: bool) -> Result<DatabaseCheckReport> {
+        let keys = self.keys.read().await;
+        keys.cipher()?; // encrypted keywords cannot be checked while locked
+        let mut tx = self.pool.begin().await?;
+        let mut issues = Vec::new();
+
+        let integrity: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(&mut *tx)
+            .await?;
+        for detail in integrity.into_iter().filter(|r| r != "ok") {
+            issues.push(issue(
+                DatabaseIssueKind::Integrity,
+                None,
+                None,
+                None,
+                detail,
+            ));
+        }
+
+        let foreign_keys = sqlx::query("PRAGMA foreign_key_check")
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in foreign_keys {
+            let table: String = row.get(0);
+            let rowid: Option<i64> = row.get(1);
+            let parent: String = row.get(2);
+            issues.push(issue(
+                DatabaseIssueKind::ForeignKey,
+                Some(&table),
+                rowid.map(|r| r.to_string()),
+                None,
+                format!("References a missing row in {}", parent),
+            ));
+        }
+
+        for &(table, column, allowed, repair) in ENUM_COLUMNS {
+            let rows = sqlx::query(&format!(
+                "SELECT id, CAST({column} AS TEXT) AS value FROM {table} WHERE {column} NOT IN ({})",
+                sql_list(allowed)
+            ))
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for row in rows {
+                let id: String = row.get("id");
+                let value: String = row.get("value");
+                let mut issue = issue(
+                    DatabaseIssueKind::InvalidEnum,
+                    Some(table),
+                    Some(id.clone()),
+                    Some(column),
+                    format!("Unknown value '{}'", value),
+                );
+                let sql = match repair {
+                    Repair::Set(expr) => {
+                        Some(format!("UPDATE {table} SET {column} = {expr} WHERE id = ?"))
+                    }
+                    Repair::Delete => Some(format!("DELETE FROM {table} WHERE id = ?")),
+                    Repair::Manual => None,
+                };
+                if let Some(sql) = sql {
+                    issue.fixable = true;
+                    if fix {
+                        sqlx::query(&sql).bind(&id).execute(&mut *tx).await?;
+                        issue.fixed = true;
+                    }
+                }
+                issues.push(issue);
+            }
+        }
+
+        // Rows with an unknown status were reported above
+        let not_completed: Vec<&str> = TASK_STATUSES
+            .iter()
+            .copied()
+            .filter(|s| *s != "completed")
+            .collect();
+        let not_completed = sql_list(&not_completed);
+        for table in TASK_TABLES {
+            let rows = sqlx::query(&format!(
+                "SELECT id, status FROM {table} WHERE completed_at IS NOT NULL AND status IN ({not_completed})"
+            ))
+            .fetch_all(&mut *tx)
+            .await?;
+            for row in rows {
+                let id: String = row.get("id");
+                let status: String = row.get("status");
+                let mut issue = issue(
+                    DatabaseIssueKind::CompletedAtNotCompleted,
+                    Some(table),
+                    Some(id.clone()),
+                    Some("completed_at"),
+                    format!("Completion time set on a {} task", status),
+                );
+                issue.fixable = true;
+                if fix {
+                    sqlx::query(&format!(
+                        "UPDATE {table} SET completed_at = NULL WHERE id = ?"
+                    ))
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+                    issue.fixed = true;
+                }
+                issues.push(issue);
+            }
+
+            let rows = sqlx::query(&format!(
+                "SELECT id, actual_time FROM {table} WHERE actual_time < 0"
+            ))
+            .fetch_all(&mut *tx)
+            .await?;
+            for row in rows {
+                let id: String = row.get("id");
+                let actual_time: i64 = row.get("actual_time");
+                let mut issue = issue(
+                    DatabaseIssueKind::NegativeActualTime,
+                    Some(table),
+                    Some(id.clone()),
+                    Some("actual_time"),
+                    format!("Tracked time is {} minutes", actual_time),
+                );
+                issue.fixable = true;
+                if fix {
+                    sqlx::query(&format!(
+                        "UPDATE {table} SET actual_time = NULL WHERE id = ?"
+                    ))
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+                    issue.fixed = true;
+                }
+                issues.push(issue);
+            }
+        }
+
+        for table in KEYWORD_TABLES {
+            let rows = sqlx::query(&format!("SELECT id, keywords_detected FROM {table}"))
+                .fetch_all(&mut *tx)
+                .await?;
+            for row in rows {
+                let id: String = row.get("id");
+                let value: Option<String> = row.get("keywords_detected");
+                let problem = match value.map(|v| keys.unseal(v)) {
+                    None => "Missing".to_string(),
+                    Some(Err(e)) => format!("Cannot be decrypted: {}", e),
+                    Some(Ok(json)) => match serde_json::from_str::<Vec<String>>(&json) {
+                        Ok(_) => continue,
+                        Err(e) => format!("Not a JSON list of strings: {}", e),
+                    },
+                };
+
+                // Keywords are detected again on every sync, so nothing is lost
+                let mut issue = issue(
+                    DatabaseIssueKind::InvalidJson,
+                    Some(table),
+                    Some(id.clone()),
+                    Some("keywords_detected"),
+                    problem,
+                );
+                issue.fixable = true;
+                if fix {
+                    sqlx::query(&format!(
+                        "UPDATE {table} SET keywords_detected = ? WHERE id = ?"
+                    ))
+                    .bind(keys.seal("[]")?)
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+                    issue.fixed = true;
+                }
+                issues.push(issue);
+            }
+        }
+
+        tx.commit().await?;
+
+        let fixed = issues.iter().filter(|i| i.fixed).count() as u32;
+        if fixed > 0 {
+            info!("Database check repaired {} issues", fixed);
+        }
+        Ok(DatabaseCheckReport {
+            issues,
+            fixed,
+            backup_id: None,
+        })
+    }
+
+    /// Inserts into `tasks`, or into `archived_tasks` when `archived_at` is
+    /// given.
+    async fn insert_task_row<'e, E>(
+        keys: &Keys,
+        executor: E,
+        task: &Task,
+        archived_at: Option<DateTime<Utc>>,
+    ) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let placeholders = vec!["?"; TASK_COLUMNS.split(',').count()].join(", ");
+        let sql = match archived_at {
+            None => format!(
+                "INSERT INTO tasks ({}) VALUES ({})",
+                TASK_COLUMNS, placeholders
+            ),
+            Some(_) => format!(
+                "INSERT INTO archived_tasks ({}, archived_at) VALUES ({}, ?)",
+                TASK_COLUMNS, placeholders
+            ),
+        };
+
+        let mut query = sqlx::query(&sql)
+            .bind(&task.id)
+            .bind(keys.seal(&task.title)?)
+            .bind(keys.seal_opt(&task.description)?)
+            .bind(task.priority)
+            .bind(task.priority_pinned)
+            .bind(task.importance)
+            .bind(&task.status)
+            .bind(&task.category)
+            .bind(&task.project)
+            .bind(task.estimated_time)
+            .bind(task.actual_time)
+            .bind(task.due_date)
+            .bind(task.created_at)
+            .bind(task.updated_at)
+            .bind(task.completed_at);
+        if let Some(archived_at) = archived_at {
+            query = query.bind(archived_at);
+        }
+        query.execute(executor).await?;
+
+        Ok(())
+    }
+
+    /// Replaces the fields of a task in `table`, either of `TASK_TABLES`,
+    /// returning whether it was there.
+    async fn update_task_row<'e, E>(
+        keys: &Keys,
+        executor: E,
+        table: &str,
+        task: &Task,
+    ) -> Result<bool>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let result = sqlx::query(&format!(
             r#"
-            CREATE TABLE IF NOT EXISTS tasks (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                description TEXT,
-                priority TEXT NOT NULL CHECK (priority IN ('low', 'medium', 'high', 'critical')),
-                status TEXT NOT NULL CHECK (status IN ('pending', 'inprogress', 'completed', 'paused', 'cancelled')),
-                category TEXT NOT NULL,
-                estimated_time INTEGER NOT NULL,
-                actual_time INTEGER,
-                due_date DATETIME,
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL,
-                completed_at DATETIME
-            )
+            UPDATE {} SET
+                title = ?, description = ?, priority = ?, priority_pinned = ?, importance = ?,
+                status = ?, category = ?, project = ?, estimated_time = ?, actual_time = ?,
+                due_date = ?, updated_at = ?, completed_at = ?
+            WHERE id = ?
             "#,
+            table
+        ))
+        .bind(keys.seal(&task.title)?)
+        .bind(keys.seal_opt(&task.description)?)
+        .bind(task.priority)
+        .bind(task.priority_pinned)
+        .bind(task.importance)
+        .bind(&task.status)
+        .bind(&task.category)
+        .bind(&task.project)
+        .bind(task.estimated_time)
+        .bind(task.actual_time)
+        .bind(task.due_date)
+        .bind(task.updated_at)
+        .bind(task.completed_at)
+        .bind(&task.id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_insight<'e, E>(keys: &Keys, executor: E, insight: &AIInsight) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        sqlx::query(
+            "INSERT INTO ai_insights (id, message, insight_type, confidence, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)"
         )
-        .execute(&self.pool)
+        .bind(&insight.id)
+        .bind(keys.seal(&insight.message)?)
+        .bind(insight.insight_type)
+        .bind(insight.confidence)
+        .bind(insight.created_at)
+        .bind(insight.expires_at)
+        .execute(executor)
         .await?;
 
-        // Create AI insights table
+        Ok(())
+    }
+
+    async fn insert_notification<'e, E>(
+        keys: &Keys,
+        executor: E,
+        notification: &NotificationItem,
+    ) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS ai_insights (
-                id TEXT PRIMARY KEY,
-                message TEXT NOT NULL,
-                insight_type TEXT NOT NULL,
-                confidence REAL NOT NULL,
-                created_at DATETIME NOT NULL
-            )
-            "#,
+            INSERT INTO notifications (id, title, message, notification_type, is_read, created_at, action_url)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#
         )
-        .execute(&self.pool)
+        .bind(&notification.id)
+        .bind(&notification.title)
+        .bind(keys.seal(&notification.message)?)
+        .bind(notification.notification_type)
+        .bind(notification.is_read)
+        .bind(notification.created_at)
+        .bind(&notification.action_url)
+        .execute(executor)
         .await?;
 
-        // Create notifications table
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommunicationRepository for Database {
+    async fn save_communication_activity(&self, activity: &CommunicationActivity) -> Result<()> {
+        let keys = self.keys.read().await;
+        let keywords_json = keys.seal(&serde_json::to_string(&activity.keywords_detected)?)?;
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS notifications (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                message TEXT NOT NULL,
-                notification_type TEXT NOT NULL,
-                is_read BOOLEAN NOT NULL DEFAULT FALSE,
-                created_at DATETIME NOT NULL,
-                action_url TEXT
-            )
-            "#,
+            INSERT OR REPLACE INTO communication_activity 
+            (id, service, message_count, unread_count, last_activity, mentions, keywords_detected, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, COALESCE((SELECT created_at FROM communication_activity WHERE service = ?), ?), ?)
+            "#
         )
-        .execute(&self.pool)
+        .bind(&activity.service) // Using service as ID for UPSERT behavior
+        .bind(&activity.service)
+        .bind(activity.message_count)
+        .bind(activity.unread_count)
+        .bind(activity.last_activity)
+        .bind(activity.mentions)
+        .bind(&keywords_json)
+        .bind(&activity.service) // For COALESCE
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
         .await?;
 
-        // Create communication_activity table
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS communication_activity (
-                id TEXT PRIMARY KEY,
-                service TEXT NOT NULL,
-                message_count INTEGER NOT NULL DEFAULT 0,
-                unread_count INTEGER NOT NULL DEFAULT 0,
-                last_activity DATETIME,
-                mentions INTEGER NOT NULL DEFAULT 0,
-                keywords_detected TEXT, -- JSON array
-                created_at DATETIME NOT NULL,
-                updated_at DATETIME NOT NULL
-            )
+            INSERT INTO communication_snapshots
+            (id, service, message_count, unread_count, mentions, keywords_detected, captured_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .execute(&self.pool)
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&activity.service)
+        .bind(activity.message_count)
+        .bind(activity.unread_count)
+        .bind(activity.mentions)
+        .bind(&keywords_json)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_communication_snapshots(
+        &self,
+        since: DateTime<Utc>,
+        service: Option<&str>,
+    ) -> Result<Vec<CommunicationSnapshot>> {
+        let keys = self.keys.read().await;
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM communication_snapshots
+            WHERE captured_at >= ? AND (? IS NULL OR service = ?)
+            ORDER BY captured_at, service
+            "#,
+        )
+        .bind(since)
+        .bind(service)
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let keywords_json = keys.unseal(row.get("keywords_detected"))?;
+            let keywords_detected: Vec<String> =
+                serde_json::from_str(&keywords_json).unwrap_or_default();
+
+            snapshots.push(CommunicationSnapshot {
+                service: row.get("service"),
+                message_count: row.get("message_count"),
+                unread_count: row.get("unread_count"),
+                mentions: row.get("mentions"),
+                keywords_detected,
+                captured_at: row.get("captured_at"),
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    async fn prune_communication_snapshots(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM communication_snapshots WHERE captured_at < ?")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_communication_activity(&self) -> Result<Vec<CommunicationActivity>> {
+        let keys = self.keys.read().await;
+        let rows = sqlx::query("SELECT * FROM communication_activity ORDER BY updated_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut activities = Vec::new();
+        for row in rows {
+            let keywords_json = keys.unseal(row.get("keywords_detected"))?;
+            let keywords_detected: Vec<String> =
+                serde_json::from_str(&keywords_json).unwrap_or_default();
+
+            activities.push(CommunicationActivity {
+                service: row.get("service"),
+                message_count: row.get("message_count"),
+                unread_count: row.get("unread_count"),
+                last_activity: row.get("last_activity"),
+                mentions: row.get("mentions"),
+                keywords_detected,
+            });
+        }
+
+        Ok(activities)
+    }
+}
+
+#[async_trait]
+impl TaskRepository for Database {
+    async fn get_all_tasks(&self) -> Result<Vec<Task>> {
+        let keys = self.keys.read().await;
+        let rows = sqlx::query_as::<_, Task>("SELECT * FROM tasks ORDER BY created_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        keys.unseal_tasks(rows)
+    }
+
+    async fn get_task_by_id(&self, id: &str) -> Result<Option<Task>> {
+        let keys = self.keys.read().await;
+        let task = sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        task.map(|t| keys.unseal_task(t)).transpose()
+    }
+
+    async fn create_task(&self, task: Task) -> Result<Task> {
+        let keys = self.keys.read().await;
+        Self::insert_task_row(&keys, &self.pool, &task, None).await?;
+
+        Ok(task)
+    }
+
+    async fn update_task(&self, task: Task) -> Result<Task> {
+        let keys = self.keys.read().await;
+        Self::update_task_row(&keys, &self.pool, "tasks", &task).await?;
+
+        Ok(task)
+    }
+
+    async fn delete_task(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        let keys = self.keys.read().await;
+        let tasks = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE status = ? ORDER BY created_at DESC",
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        keys.unseal_tasks(tasks)
+    }
+
+    async fn get_open_tasks(&self) -> Result<Vec<Task>> {
+        let keys = self.keys.read().await;
+        let tasks = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE status NOT IN ('completed', 'cancelled') ORDER BY created_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        keys.unseal_tasks(tasks)
+    }
+
+    async fn get_overdue_tasks(&self) -> Result<Vec<Task>> {
+        let keys = self.keys.read().await;
+        let now = Utc::now();
+        let tasks = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE due_date < ? AND status != 'completed' ORDER BY due_date ASC"
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        keys.unseal_tasks(tasks)
+    }
+
+    // Archive operations
+    async fn archive_tasks_older_than(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let condition =
+            "status IN ('completed', 'cancelled') AND COALESCE(completed_at, updated_at) < ?";
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO archived_tasks ({columns}, archived_at) SELECT {columns}, ? FROM tasks WHERE {condition}",
+            columns = TASK_COLUMNS,
+            condition = condition
+        ))
+        .bind(Utc::now())
+        .bind(cutoff)
+        .execute(&mut *tx)
         .await?;
 
-        info!("Database migrations completed successfully");
-        Ok(())
-    } // Task operations
-    pub async fn get_all_tasks(&self) -> Result<Vec<Task>> {
-        let rows = sqlx::query_as::<_, Task>("SELECT * FROM tasks ORDER BY created_at DESC")
-            .fetch_all(&self.pool)
+        let result = sqlx::query(&format!("DELETE FROM tasks WHERE {}", condition))
+            .bind(cutoff)
+            .execute(&mut *tx)
             .await?;
 
-        Ok(rows)
-    }
+        tx.commit().await?;
 
-    pub async fn get_task_by_id(&self, id: &str) -> Result<Option<Task>> {
-        let task = sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = ?")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await?;
+        Ok(result.rows_affected())
+    }
 
-        Ok(task)
+    async fn update_archived_task(&self, task: Task) -> Result<bool> {
+        let keys = self.keys.read().await;
+        Self::update_task_row(&keys, &self.pool, "archived_tasks", &task).await
     }
 
-    pub async fn create_task(&self, task: Task) -> Result<Task> {
-        sqlx::query(
-            r#"
-            INSERT INTO tasks (
-                id, title, description, priority, status, category,
-                estimated_time, actual_time, due_date, created_at, updated_at, completed_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
+    async fn get_archived_tasks(&self, limit: i32, offset: i32) -> Result<Vec<ArchivedTask>> {
+        let keys = self.keys.read().await;
+        let tasks = sqlx::query_as::<_, ArchivedTask>(
+            "SELECT * FROM archived_tasks ORDER BY archived_at DESC, completed_at DESC LIMIT ? OFFSET ?",
         )
-        .bind(&task.id)
-        .bind(&task.title)
-        .bind(&task.description)
-        .bind(&task.priority)
-        .bind(&task.status)
-        .bind(&task.category)
-        .bind(task.estimated_time)
-        .bind(task.actual_time)
-        .bind(task.due_date)
-        .bind(task.created_at)
-        .bind(task.updated_at)
-        .bind(task.completed_at)
-        .execute(&self.pool)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(task)
+        keys.unseal_archived(tasks)
     }
 
-    pub async fn update_task(&self, task: Task) -> Result<Task> {
-        sqlx::query(
+    async fn search_archived_tasks(&self, query: &str, limit: i32) -> Result<Vec<ArchivedTask>> {
+        let keys = self.keys.read().await;
+        if keys.enabled {
+            return self.search_encrypted_archive(&keys, query, limit).await;
+        }
+
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        let tasks = sqlx::query_as::<_, ArchivedTask>(
             r#"
-            UPDATE tasks SET
-                title = ?, description = ?, priority = ?, status = ?, category = ?,
-                estimated_time = ?, actual_time = ?, due_date = ?, updated_at = ?, completed_at = ?
-            WHERE id = ?
+            SELECT * FROM archived_tasks
+            WHERE title LIKE ?1 ESCAPE '\' OR description LIKE ?1 ESCAPE '\' OR category LIKE ?1 ESCAPE '\'
+            ORDER BY archived_at DESC LIMIT ?2
             "#,
         )
-        .bind(&task.title)
-        .bind(&task.description)
-        .bind(&task.priority)
-        .bind(&task.status)
-        .bind(&task.category)
-        .bind(task.estimated_time)
-        .bind(task.actual_time)
-        .bind(task.due_date)
-        .bind(task.updated_at)
-        .bind(task.completed_at)
-        .bind(&task.id)
-        .execute(&self.pool)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(task)
+        keys.unseal_archived(tasks)
     }
 
-    pub async fn delete_task(&self, id: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
+    async fn unarchive_task(&self, id: &str) -> Result<Option<Task>> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(&format!(
+            "INSERT INTO tasks ({columns}) SELECT {columns} FROM archived_tasks WHERE id = ?",
+            columns = TASK_COLUMNS
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        sqlx::query("DELETE FROM archived_tasks WHERE id = ?")
             .bind(id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
 
-        Ok(result.rows_affected() > 0)
+        tx.commit().await?;
+
+        self.get_task_by_id(id).await
     }
 
-    pub async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
-        let tasks = sqlx::query_as::<_, Task>(
-            "SELECT * FROM tasks WHERE status = ? ORDER BY created_at DESC",
+    // Priority escalation operations
+    async fn save_priority_escalation(&self, escalation: &PriorityEscalation) -> Result<()> {
+        let keys = self.keys.read().await;
+        sqlx::query(
+            r#"
+            INSERT INTO priority_escalations
+            (id, task_id, task_title, from_priority, to_priority, rule, escalated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
         )
-        .bind(status)
-        .fetch_all(&self.pool)
+        .bind(&escalation.id)
+        .bind(&escalation.task_id)
+        .bind(keys.seal(&escalation.task_title)?)
+        .bind(escalation.from_priority)
+        .bind(escalation.to_priority)
+        .bind(&escalation.rule)
+        .bind(escalation.escalated_at)
+        .execute(&self.pool)
         .await?;
 
-        Ok(tasks)
+        Ok(())
     }
 
-    pub async fn get_overdue_tasks(&self) -> Result<Vec<Task>> {
-        let now = Utc::now();
-        let tasks = sqlx::query_as::<_, Task>(
-            "SELECT * FROM tasks WHERE due_date < ? AND status != 'completed' ORDER BY due_date ASC"
+    async fn get_priority_escalations(
+        &self,
+        task_id: Option<&str>,
+        limit: i32,
+    ) -> Result<Vec<PriorityEscalation>> {
+        let keys = self.keys.read().await;
+        let escalations = sqlx::query_as::<_, PriorityEscalation>(
+            r#"
+            SELECT * FROM priority_escalations
+            WHERE ? IS NULL OR task_id = ?
+            ORDER BY escalated_at DESC LIMIT ?
+            "#,
         )
-        .bind(now)
+        .bind(task_id)
+        .bind(task_id)
+        .bind(limit)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(tasks)
+        escalations
+            .into_iter()
+            .map(|mut escalation| {
+                escalation.task_title = keys.unseal(escalation.task_title)?;
+                Ok(escalation)
+            })
+            .collect()
     }
 
-    pub async fn get_productivity_stats(&self) -> Result<ProductivityStats> {
-        let total_tasks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks")
-            .fetch_one(&self.pool)
-            .await?;
-
-        let completed_tasks: i64 =
-            sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE status = 'completed'")
-                .fetch_one(&self.pool)
-                .await?;
-
-        let pending_tasks: i64 =
-            sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE status = 'pending'")
-                .fetch_one(&self.pool)
-                .await?;
-
-        let overdue_tasks: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM tasks WHERE due_date < ? AND status != 'completed'",
+    async fn get_productivity_stats(&self) -> Result<ProductivityStats> {
+        // Totals include archived tasks so history is not lost to the archive
+        // job; pending and overdue only count live ones. Completions per local
+        // hour and tasks per category come back as JSON pairs in the same row.
+        let (
+            total_tasks,
+            completed_tasks,
+            pending_tasks,
+            overdue_tasks,
+            avg_completion_time,
+            hours_json,
+            categories_json,
+        ): (i64, i64, i64, i64, Option<f64>, String, String) = sqlx::query_as(
+            r#"
+            WITH history AS (
+                SELECT status, due_date, actual_time, completed_at, 1 AS live FROM tasks
+                UNION ALL
+                SELECT status, due_date, actual_time, completed_at, 0 AS live FROM archived_tasks
+            )
+            SELECT COUNT(*),
+                   COALESCE(SUM(status = 'completed'), 0),
+                   COALESCE(SUM(live AND status = 'pending'), 0),
+                   COALESCE(SUM(live AND status != 'completed' AND due_date < ?), 0),
+                   AVG(CASE WHEN status = 'completed' THEN actual_time END),
+                   -- SQLite's 'localtime' reads the same system time zone as `Local`
+                   (SELECT json_group_array(json_array(hour, completions)) FROM (
+                       SELECT CAST(strftime('%H', completed_at, 'localtime') AS INTEGER) AS hour,
+                              COUNT(*) AS completions
+                       FROM history
+                       WHERE completed_at IS NOT NULL
+                       GROUP BY hour
+                   )),
+                   -- Counted per table so each count can use its category index
+                   (SELECT json_group_array(json_array(category, tasks)) FROM (
+                       SELECT category, COUNT(*) AS tasks FROM tasks GROUP BY category
+                       UNION ALL
+                       SELECT category, COUNT(*) AS tasks FROM archived_tasks GROUP BY category
+                   ))
+            FROM history
+            "#,
         )
         .bind(Utc::now())
         .fetch_one(&self.pool)
@@ -239,14 +1501,12 @@ impl Database {
         } else {
             0.0
         };
+        let hours: Vec<(i64, i64)> = serde_json::from_str(&hours_json)?;
+        let categories: Vec<(String, i64)> = serde_json::from_str(&categories_json)?;
 
-        // Calculate average completion time
-        let avg_completion_time: Option<f64> = sqlx::query_scalar(
-            "SELECT AVG(actual_time) FROM tasks WHERE status = 'completed' AND actual_time IS NOT NULL"
-        )
-        .fetch_optional(&self.pool)
-        .await?
-        .flatten();
+        let now = Local::now();
+        let (since, until) = stats::progress_window(&now);
+        let recent = self.get_task_activity(since, until).await?;
 
         Ok(ProductivityStats {
             total_tasks: total_tasks as i32,
@@ -255,27 +1515,43 @@ impl Database {
             overdue_tasks: overdue_tasks as i32,
             completion_rate,
             average_completion_time: avg_completion_time.map(|t| t as f32),
-            most_productive_hours: vec![], // TODO: Implement based on completion times
-            common_categories: vec![],     // TODO: Implement category analysis
-            weekly_progress: vec![],       // TODO: Implement weekly progress tracking
+            most_productive_hours: stats::most_productive_hours_grouped(&hours),
+            common_categories: stats::common_categories_grouped(&categories),
+            weekly_progress: stats::weekly_progress(&recent, now),
         })
-    } // AI Insights operations
-    pub async fn save_ai_insight(&self, insight: AIInsight) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO ai_insights (id, message, insight_type, confidence, created_at) VALUES (?, ?, ?, ?, ?)"
-        )
-        .bind(&insight.id)
-        .bind(&insight.message)
-        .bind(&insight.insight_type)
-        .bind(insight.confidence)
-        .bind(insight.created_at)
-        .execute(&self.pool)
+    }
+
+    async fn get_task_activity(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TaskActivity>> {
+        let activity = sqlx::query_as(&format!(
+            "SELECT {} FROM task_history
+             WHERE (created_at >= ? AND created_at < ?)
+                OR (completed_at >= ? AND completed_at < ?)",
+            ACTIVITY_COLUMNS
+        ))
+        .bind(since)
+        .bind(until)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(activity)
+    }
+}
+
+#[async_trait]
+impl InsightRepository for Database {
+    async fn save_ai_insight(&self, insight: AIInsight) -> Result<()> {
+        let keys = self.keys.read().await;
+        Self::insert_insight(&keys, &self.pool, &insight).await
     }
 
-    pub async fn get_recent_insights(&self, limit: i32) -> Result<Vec<AIInsight>> {
+    async fn get_recent_insights(&self, limit: i32) -> Result<Vec<AIInsight>> {
+        let keys = self.keys.read().await;
         let insights = sqlx::query_as::<_, AIInsight>(
             "SELECT * FROM ai_insights ORDER BY created_at DESC LIMIT ?",
         )
@@ -283,31 +1559,51 @@ impl Database {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(insights)
+        keys.unseal_insights(insights)
     }
 
-    // Notification operations
-    pub async fn save_notification(&self, notification: &NotificationItem) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO notifications (id, title, message, notification_type, is_read, created_at, action_url)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
-            "#
+    async fn get_active_insights(&self, now: DateTime<Utc>) -> Result<Vec<AIInsight>> {
+        let keys = self.keys.read().await;
+        let insights = sqlx::query_as::<_, AIInsight>(
+            "SELECT * FROM ai_insights WHERE expires_at IS NULL OR expires_at > ? ORDER BY created_at DESC",
         )
-        .bind(&notification.id)
-        .bind(&notification.title)
-        .bind(&notification.message)
-        .bind(&notification.notification_type)
-        .bind(notification.is_read)
-        .bind(notification.created_at)
-        .bind(&notification.action_url)
-        .execute(&self.pool)
+        .bind(now)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(())
+        keys.unseal_insights(insights)
+    }
+
+    async fn get_insight_history(
+        &self,
+        insight_type: Option<InsightType>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<AIInsight>> {
+        let keys = self.keys.read().await;
+        let insights = sqlx::query_as::<_, AIInsight>(
+            "SELECT * FROM ai_insights WHERE ? IS NULL OR insight_type = ? ORDER BY created_at DESC LIMIT ? OFFSET ?",
+        )
+        .bind(insight_type)
+        .bind(insight_type)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        keys.unseal_insights(insights)
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for Database {
+    async fn save_notification(&self, notification: &NotificationItem) -> Result<()> {
+        let keys = self.keys.read().await;
+        Self::insert_notification(&keys, &self.pool, notification).await
     }
 
-    pub async fn get_notifications(&self, limit: i32) -> Result<Vec<NotificationItem>> {
+    async fn get_notifications(&self, limit: i32) -> Result<Vec<NotificationItem>> {
+        let keys = self.keys.read().await;
         let notifications = sqlx::query_as::<_, NotificationItem>(
             "SELECT * FROM notifications ORDER BY created_at DESC LIMIT ?",
         )
@@ -315,10 +1611,16 @@ impl Database {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(notifications)
+        notifications
+            .into_iter()
+            .map(|mut notification| {
+                notification.message = keys.unseal(notification.message)?;
+                Ok(notification)
+            })
+            .collect()
     }
 
-    pub async fn mark_notification_read(&self, id: &str) -> Result<bool> {
+    async fn mark_notification_read(&self, id: &str) -> Result<bool> {
         let result = sqlx::query("UPDATE notifications SET is_read = TRUE WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
@@ -327,7 +1629,7 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
-    pub async fn get_unread_notification_count(&self) -> Result<i64> {
+    async fn get_unread_notification_count(&self) -> Result<i64> {
         let count = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE is_read = FALSE")
             .fetch_one(&self.pool)
             .await?;
@@ -335,55 +1637,534 @@ impl Database {
         Ok(count)
     }
 
-    // Communication activity operations
-    pub async fn save_communication_activity(&self, activity: CommunicationActivity) -> Result<()> {
-        let keywords_json = serde_json::to_string(&activity.keywords_detected)?;
-        let now = Utc::now();
+    async fn query_notifications(&self, query: &NotificationQuery) -> Result<NotificationPage> {
+        let (after_created_at, after_id) = match query.cursor.as_deref() {
+            Some(cursor) => {
+                let (created_at, id) = parse_notification_cursor(cursor)?;
+                (Some(created_at), Some(id))
+            }
+            None => (None, None),
+        };
+        let page_size = notification_page_size(query);
+
+        let keys = self.keys.read().await;
+        let notifications = sqlx::query_as::<_, NotificationItem>(
+            r#"
+            SELECT * FROM notifications
+            WHERE (? IS NULL OR notification_type = ?)
+              AND (? IS NULL OR is_read = ?)
+              AND (? IS NULL OR created_at < ? OR (created_at = ? AND id < ?))
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(query.notification_type)
+        .bind(query.notification_type)
+        .bind(query.is_read)
+        .bind(query.is_read)
+        .bind(after_created_at)
+        .bind(after_created_at)
+        .bind(after_created_at)
+        .bind(&after_id)
+        .bind(page_size + 1)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let notifications = notifications
+            .into_iter()
+            .map(|mut notification| {
+                notification.message = keys.unseal(notification.message)?;
+                Ok(notification)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
+        Ok(notification_page(notifications, page_size))
+    }
+
+    async fn mark_all_notifications_read(
+        &self,
+        notification_type: Option<NotificationType>,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE notifications SET is_read = TRUE WHERE is_read = FALSE AND (? IS NULL OR notification_type = ?)",
+        )
+        .bind(notification_type)
+        .bind(notification_type)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_notifications(&self, ids: &[String]) -> Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let sql = format!("DELETE FROM notifications WHERE id IN ({})", placeholders);
+        let mut query = sqlx::query(&sql);
+        for id in ids {
+            query = query.bind(id);
+        }
+
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+
+    async fn prune_read_notifications(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result =
+            sqlx::query("DELETE FROM notifications WHERE is_read = TRUE AND created_at < ?")
+                .bind(cutoff)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl AchievementRepository for Database {
+    async fn get_unlocked_achievements(&self) -> Result<Vec<UnlockedAchievement>> {
+        let achievements = sqlx::query_as::<_, UnlockedAchievement>(
+            "SELECT id, unlocked_at FROM achievements ORDER BY unlocked_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(achievements)
+    }
+
+    async fn unlock_achievement(
+        &self,
+        id: &str,
+        unlocked_at: DateTime<Utc>,
+        notification: &NotificationItem,
+    ) -> Result<bool> {
+        let keys = self.keys.read().await;
+        let mut tx = self.pool.begin().await?;
+
+        let result =
+            sqlx::query("INSERT OR IGNORE INTO achievements (id, unlocked_at) VALUES (?, ?)")
+                .bind(id)
+                .bind(unlocked_at)
+                .execute(&mut *tx)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        Self::insert_notification(&keys, &mut *tx, notification).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn get_streaks(&self) -> Result<Vec<Streak>> {
+        let streaks =
+            sqlx::query_as::<_, Streak>("SELECT name, current, best, last_date FROM streaks")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(streaks)
+    }
+
+    async fn save_streak(&self, streak: &Streak) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO communication_activity 
-            (id, service, message_count, unread_count, last_activity, mentions, keywords_detected, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, COALESCE((SELECT created_at FROM communication_activity WHERE service = ?), ?), ?)
-            "#
+            INSERT INTO streaks (name, current, best, last_date) VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                current = excluded.current,
+                best = excluded.best,
+                last_date = excluded.last_date
+            "#,
         )
-        .bind(&activity.service) // Using service as ID for UPSERT behavior
-        .bind(&activity.service)
-        .bind(activity.message_count)
-        .bind(activity.unread_count)
-        .bind(activity.last_activity)
-        .bind(activity.mentions)
-        .bind(keywords_json)
-        .bind(&activity.service) // For COALESCE
-        .bind(now)
-        .bind(now)
+        .bind(&streak.name)
+        .bind(streak.current)
+        .bind(streak.best)
+        .bind(streak.last_date)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
+}
 
-    pub async fn get_communication_activity(&self) -> Result<Vec<CommunicationActivity>> {
-        let rows = sqlx::query("SELECT * FROM communication_activity ORDER BY updated_at DESC")
-            .fetch_all(&self.pool)
-            .await?;
+#[async_trait]
+impl ImportRepository for Database {
+    async fn apply_import(&self, batch: ImportBatch) -> Result<()> {
+        let keys = self.keys.read().await;
+        let mut tx = self.pool.begin().await?;
 
-        let mut activities = Vec::new();
-        for row in rows {
-            let keywords_json: String = row.get("keywords_detected");
-            let keywords_detected: Vec<String> =
-                serde_json::from_str(&keywords_json).unwrap_or_default();
+        for task in &batch.created_tasks {
+            Self::insert_task_row(&keys, &mut *tx, task, None).await?;
+        }
+        for task in &batch.updated_tasks {
+            Self::update_task_row(&keys, &mut *tx, "tasks", task).await?;
+        }
+        for archived in &batch.created_archived_tasks {
+            Self::insert_task_row(&keys, &mut *tx, &archived.task, Some(archived.archived_at))
+                .await?;
+        }
+        for task in &batch.updated_archived_tasks {
+            Self::update_task_row(&keys, &mut *tx, "archived_tasks", task).await?;
+        }
+        for insight in &batch.insights {
+            Self::insert_insight(&keys, &mut *tx, insight).await?;
+        }
+        for notification in &batch.notifications {
+            Self::insert_notification(&keys, &mut *tx, notification).await?;
+        }
 
-            activities.push(CommunicationActivity {
-                service: row.get("service"),
-                message_count: row.get("message_count"),
-                unread_count: row.get("unread_count"),
-                last_activity: row.get("last_activity"),
-                mentions: row.get("mentions"),
-                keywords_detected,
-            });
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::BackupManager;
+    use crate::repository::Repositories;
+    use crate::test_support::task;
+
+    const WORKERS: usize = 8;
+    const TASKS_PER_WORKER: usize = 25;
+
+    /// Commands and a backup running at once on a file-backed database, the
+    /// way the app runs them, must wait for each other rather than fail.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn parallel_commands_do_not_fail_busy() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(DatabaseLocation::Directory(dir.path().to_path_buf()))
+            .await
+            .unwrap();
+        let repos = Repositories::sqlite(&db);
+
+        let workers: Vec<_> = (0..WORKERS)
+            .map(|worker| {
+                let repos = repos.clone();
+                tokio::spawn(async move {
+                    for i in 0..TASKS_PER_WORKER {
+                        let mut task = repos
+                            .tasks
+                            .create_task(task(&format!("Task {}-{}", worker, i)))
+                            .await?;
+                        task.status = TaskStatus::Completed;
+                        task.completed_at = Some(Utc::now());
+                        repos.tasks.update_task(task).await?;
+                        repos.tasks.get_all_tasks().await?;
+                        repos.tasks.get_productivity_stats().await?;
+                    }
+                    Ok::<_, AppError>(())
+                })
+            })
+            .collect();
+
+        let backups = BackupManager::for_database(&db).unwrap();
+        let backup = backups.create_snapshot(&db, BackupTier::Hourly).await;
+
+        for worker in workers {
+            if let Err(e) = worker.await.unwrap() {
+                panic!("worker failed with {}: {}", e.code(), e);
+            }
         }
+        backup.unwrap();
 
-        Ok(activities)
+        let tasks = repos.tasks.get_all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), WORKERS * TASKS_PER_WORKER);
+        assert!(tasks.iter().all(|t| t.status == TaskStatus::Completed));
+
+        let stats = repos.tasks.get_productivity_stats().await.unwrap();
+        assert_eq!(stats.total_tasks as usize, WORKERS * TASKS_PER_WORKER);
+        assert_eq!(stats.completed_tasks, stats.total_tasks);
+        assert_eq!(backups.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restoring_a_backup_changes_the_database_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = DatabaseLocation::Directory(dir.path().to_path_buf());
+        let db = Database::open(location.clone()).await.unwrap();
+        let original = db.database_id().await.unwrap();
+
+        let backups = BackupManager::for_database(&db).unwrap();
+        let backup = backups
+            .create_snapshot(&db, BackupTier::Daily)
+            .await
+            .unwrap();
+        backups.restore(&db, &backup.id).await.unwrap();
+
+        let restored = Database::open(location).await.unwrap();
+        assert_ne!(restored.database_id().await.unwrap(), original);
+    }
+
+    #[tokio::test]
+    async fn insight_messages_are_encrypted_at_rest() {
+        let db = Database::in_memory().await.unwrap();
+        let insight = |id: &str, message: &str| AIInsight {
+            id: id.to_string(),
+            message: message.to_string(),
+            insight_type: InsightType::ProductivityTip,
+            confidence: 0.8,
+            created_at: Utc::now(),
+            expires_at: None,
+        };
+        let stored = |id: &'static str| {
+            let pool = db.pool.clone();
+            async move {
+                sqlx::query_scalar::<_, String>("SELECT message FROM ai_insights WHERE id = ?")
+                    .bind(id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        db.save_ai_insight(insight("before", "Written before encryption"))
+            .await
+            .unwrap();
+        db.enable_encryption("correct horse").await.unwrap();
+        db.save_ai_insight(insight("after", "Written after encryption"))
+            .await
+            .unwrap();
+
+        assert!(FieldCipher::is_encrypted(&stored("before").await));
+        assert!(FieldCipher::is_encrypted(&stored("after").await));
+        let mut messages: Vec<String> = db
+            .get_recent_insights(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|i| i.message)
+            .collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            ["Written after encryption", "Written before encryption"]
+        );
+
+        db.disable_encryption("correct horse").await.unwrap();
+        assert_eq!(stored("after").await, "Written after encryption");
+    }
+
+    #[tokio::test]
+    async fn verify_file_reads_paths_a_url_would_mangle() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(DatabaseLocation::Directory(dir.path().to_path_buf()))
+            .await
+            .unwrap();
+        let copy = dir.path().join("my backups #1?");
+        std::fs::create_dir(&copy).unwrap();
+        let copy = copy.join("app.db");
+
+        db.copy_to(&copy).await.unwrap();
+        Database::verify_file(&copy).await.unwrap();
+
+        std::fs::write(&copy, b"not a database").unwrap();
+        assert!(Database::verify_file(&copy).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn enabling_encryption_leaves_no_plaintext_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(DatabaseLocation::Directory(dir.path().to_path_buf()))
+            .await
+            .unwrap();
+        let secret = "Quarterly layoffs plan";
+        db.create_task(task(secret)).await.unwrap();
+
+        db.enable_encryption("correct horse").await.unwrap();
+
+        for name in [DB_FILE_NAME, "app.db-wal"] {
+            let bytes = std::fs::read(dir.path().join(name)).unwrap_or_default();
+            assert!(
+                !bytes.windows(secret.len()).any(|w| w == secret.as_bytes()),
+                "plaintext left in {}",
+                name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pruning_communication_history_keeps_recent_snapshots() {
+        let db = Database::in_memory().await.unwrap();
+        let activity = |service: &str| CommunicationActivity {
+            service: service.to_string(),
+            message_count: 3,
+            unread_count: 1,
+            last_activity: None,
+            mentions: 0,
+            keywords_detected: vec![],
+        };
+
+        db.save_communication_activity(&activity("gmail"))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE communication_snapshots SET captured_at = ?")
+            .bind(Utc::now() - chrono::Duration::days(100))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.save_communication_activity(&activity("discord"))
+            .await
+            .unwrap();
+
+        let cutoff = Utc::now() - chrono::Duration::days(90);
+        assert_eq!(db.prune_communication_snapshots(cutoff).await.unwrap(), 1);
+
+        let since = Utc::now() - chrono::Duration::days(365);
+        let snapshots = db.get_communication_snapshots(since, None).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].service, "discord");
+        assert_eq!(db.get_communication_activity().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn check_reports_and_repairs_rows_the_app_would_misread() {
+        let db = Database::in_memory().await.unwrap();
+        let (reopened, negative, unknown) = (task("reopened"), task("negative"), task("unknown"));
+        for t in [&reopened, &negative, &unknown] {
+            db.create_task(t.clone()).await.unwrap();
+        }
+        db.save_communication_activity(&CommunicationActivity {
+            service: "gmail".to_string(),
+            message_count: 1,
+            unread_count: 0,
+            last_activity: None,
+            mentions: 0,
+            keywords_detected: vec![],
+        })
+        .await
+        .unwrap();
+
+        // Rows written by tables that predate the CHECK lists
+        sqlx::query("PRAGMA ignore_check_constraints = ON")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        for (sql, id) in [
+            (
+                "UPDATE tasks SET completed_at = CURRENT_TIMESTAMP WHERE id = ?",
+                &reopened.id,
+            ),
+            (
+                "UPDATE tasks SET actual_time = -5 WHERE id = ?",
+                &negative.id,
+            ),
+            (
+                "UPDATE tasks SET priority = 'urgent', status = 'someday' WHERE id = ?",
+                &unknown.id,
+            ),
+        ] {
+            sqlx::query(sql).bind(id).execute(&db.pool).await.unwrap();
+        }
+        sqlx::query("UPDATE communication_activity SET keywords_detected = 'not json'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let report = db.check(false).await.unwrap();
+        let mut found: Vec<(DatabaseIssueKind, Option<String>)> = report
+            .issues
+            .iter()
+            .map(|i| (i.kind, i.column.clone()))
+            .collect();
+        found.sort_by_key(|(kind, column)| (format!("{:?}", kind), column.clone()));
+        let column = |c: &str| Some(c.to_string());
+        assert_eq!(
+            found,
+            [
+                (
+                    DatabaseIssueKind::CompletedAtNotCompleted,
+                    column("completed_at")
+                ),
+                (DatabaseIssueKind::InvalidEnum, column("priority")),
+                (DatabaseIssueKind::InvalidEnum, column("status")),
+                (DatabaseIssueKind::InvalidJson, column("keywords_detected")),
+                (DatabaseIssueKind::NegativeActualTime, column("actual_time")),
+            ]
+        );
+        assert!(report.issues.iter().all(|i| i.fixable && !i.fixed));
+        assert_eq!(report.fixed, 0);
+
+        let report = db.check(true).await.unwrap();
+        assert_eq!(report.fixed, 5);
+        assert!(db.check(false).await.unwrap().issues.is_empty());
+
+        let reopened = db.get_task_by_id(&reopened.id).await.unwrap().unwrap();
+        assert_eq!(reopened.completed_at, None);
+        let negative = db.get_task_by_id(&negative.id).await.unwrap().unwrap();
+        assert_eq!(negative.actual_time, None);
+        let unknown = db.get_task_by_id(&unknown.id).await.unwrap().unwrap();
+        assert_eq!(unknown.priority, Priority::Medium);
+        assert_eq!(unknown.status, TaskStatus::Pending);
+        let activity = db.get_communication_activity().await.unwrap();
+        assert!(activity[0].keywords_detected.is_empty());
+    }
+
+    #[test]
+    fn the_data_directory_follows_its_precedence_order() {
+        let args = |list: &[&str]| {
+            list.iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+        let env = Some("/env".to_string());
+
+        assert_eq!(
+            DatabaseLocation::override_from(args(&["--data-dir", "/arg"]), env.clone()),
+            Some("/arg".to_string())
+        );
+        assert_eq!(
+            DatabaseLocation::override_from(args(&["--data-dir=/arg"]), env.clone()),
+            Some("/arg".to_string())
+        );
+        assert_eq!(
+            DatabaseLocation::override_from(args(&[]), env.clone()),
+            Some("/env".to_string())
+        );
+        assert_eq!(
+            DatabaseLocation::override_from(args(&[]), Some(String::new())),
+            None
+        );
+
+        let default = Path::new("/default");
+        let mut settings = AppSettings::default();
+        let dir = |d: &str| DatabaseLocation::Directory(PathBuf::from(d));
+
+        assert_eq!(
+            DatabaseLocation::resolve_with(None, &settings, default),
+            dir("/default")
+        );
+        settings.data_directory = Some(PathBuf::from("/settings"));
+        assert_eq!(
+            DatabaseLocation::resolve_with(None, &settings, default),
+            dir("/settings")
+        );
+        assert_eq!(
+            DatabaseLocation::resolve_with(env, &settings, default),
+            dir("/env")
+        );
+        assert_eq!(
+            DatabaseLocation::resolve_with(Some(":memory:".to_string()), &settings, default),
+            DatabaseLocation::InMemory
+        );
+    }
+
+    #[test]
+    fn relative_data_directories_resolve_against_the_default() {
+        let default = Path::new("/default");
+        let mut settings = AppSettings::default();
+
+        assert_eq!(
+            DatabaseLocation::resolve_with(Some("./data".to_string()), &settings, default),
+            DatabaseLocation::Directory(PathBuf::from("/default/./data"))
+        );
+        settings.data_directory = Some(PathBuf::from("data"));
+        assert_eq!(
+            DatabaseLocation::resolve_with(None, &settings, default),
+            DatabaseLocation::Directory(PathBuf::from("/default/data"))
+        );
     }
 }
@@ -0,0 +1,250 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::error::{AppError, Result};
+use crate::models::{EisenhowerMatrix, EisenhowerQuadrant, EisenhowerThresholds, Importance, Task};
+
+/// Ten years. Far larger windows overflow chrono's date arithmetic.
+const MAX_URGENT_WITHIN_HOURS: i64 = 24 * 3650;
+
+/// Checks thresholds before they are saved or used for a one-off matrix.
+pub fn validate(thresholds: &EisenhowerThresholds) -> Result<()> {
+    if !(0..=MAX_URGENT_WITHIN_HOURS).contains(&thresholds.urgent_within_hours) {
+        return Err(AppError::validation(
+            "eisenhower.urgent_within_hours",
+            format!("Must be between 0 and {} hours", MAX_URGENT_WITHIN_HOURS),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn is_urgent(task: &Task, thresholds: &EisenhowerThresholds, now: DateTime<Utc>) -> bool {
+    match task.due_date {
+        // Overdue tasks are always urgent
+        Some(due_date) => due_date - now <= Duration::hours(thresholds.urgent_within_hours),
+        None => false,
+    }
+}
+
+pub fn is_important(task: &Task, thresholds: &EisenhowerThresholds) -> bool {
+    match task.importance {
+        Importance::Important => true,
+        Importance::Unimportant => false,
+        Importance::Auto => task.priority >= thresholds.important_priority,
+    }
+}
+
+pub fn classify(
+    task: &Task,
+    thresholds: &EisenhowerThresholds,
+    now: DateTime<Utc>,
+) -> EisenhowerQuadrant {
+    match (
+        is_urgent(task, thresholds, now),
+        is_important(task, thresholds),
+    ) {
+        (true, true) => EisenhowerQuadrant::DoFirst,
+        (false, true) => EisenhowerQuadrant::Schedule,
+        (true, false) => EisenhowerQuadrant::Delegate,
+        (false, false) => EisenhowerQuadrant::Eliminate,
+    }
+}
+
+impl EisenhowerMatrix {
+    /// Buckets the open tasks into the four quadrants. Completed and cancelled
+    /// tasks are ignored.
+    pub fn build(tasks: &[Task], thresholds: &EisenhowerThresholds, now: DateTime<Utc>) -> Self {
+        let mut matrix = Self {
+            do_first: vec![],
+            schedule: vec![],
+            delegate: vec![],
+            eliminate: vec![],
+            thresholds: thresholds.clone(),
+            generated_at: now,
+        };
+
+        for task in tasks.iter().filter(|t| t.is_open()) {
+            let bucket = match classify(task, thresholds, now) {
+                EisenhowerQuadrant::DoFirst => &mut matrix.do_first,
+                EisenhowerQuadrant::Schedule => &mut matrix.schedule,
+                EisenhowerQuadrant::Delegate => &mut matrix.delegate,
+                EisenhowerQuadrant::Eliminate => &mut matrix.eliminate,
+            };
+            bucket.push(task.clone());
+        }
+
+        matrix
+    }
+
+    pub fn tasks(&self, quadrant: EisenhowerQuadrant) -> &[Task] {
+        match quadrant {
+            EisenhowerQuadrant::DoFirst => &self.do_first,
+            EisenhowerQuadrant::Schedule => &self.schedule,
+            EisenhowerQuadrant::Delegate => &self.delegate,
+            EisenhowerQuadrant::Eliminate => &self.eliminate,
+        }
+    }
+
+    pub fn task_count(&self) -> usize {
+        self.do_first.len() + self.schedule.len() + self.delegate.len() + self.eliminate.len()
+    }
+
+    /// Share (0.0 - 1.0) of the planned time that falls into `quadrant`,
+    /// based on estimated minutes. Falls back to task counts when nothing
+    /// has an estimate.
+    pub fn time_share(&self, quadrant: EisenhowerQuadrant) -> f32 {
+        let minutes =
+            |tasks: &[Task]| -> i64 { tasks.iter().map(|t| t.estimated_time.max(0) as i64).sum() };
+
+        let total_minutes = minutes(&self.do_first)
+            + minutes(&self.schedule)
+            + minutes(&self.delegate)
+            + minutes(&self.eliminate);
+
+        if total_minutes > 0 {
+            minutes(self.tasks(quadrant)) as f32 / total_minutes as f32
+        } else if self.task_count() > 0 {
+            self.tasks(quadrant).len() as f32 / self.task_count() as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Priority, TaskStatus};
+    use crate::test_support::task;
+
+    fn thresholds() -> EisenhowerThresholds {
+        EisenhowerThresholds {
+            urgent_within_hours: 48,
+            important_priority: Priority::High,
+        }
+    }
+
+    fn task_due(
+        title: &str,
+        priority: Priority,
+        due_in_hours: Option<i64>,
+        estimated_time: i32,
+        now: DateTime<Utc>,
+    ) -> Task {
+        let mut task = task(title);
+        task.priority = priority;
+        task.due_date = due_in_hours.map(|h| now + Duration::hours(h));
+        task.estimated_time = estimated_time;
+        task
+    }
+
+    #[test]
+    fn classify_uses_due_date_priority_and_importance() {
+        let now = Utc::now();
+        let classify_one = |priority, due_in_hours| {
+            classify(
+                &task_due("t", priority, due_in_hours, 30, now),
+                &thresholds(),
+                now,
+            )
+        };
+
+        assert_eq!(
+            classify_one(Priority::High, Some(48)),
+            EisenhowerQuadrant::DoFirst
+        );
+        assert_eq!(
+            classify_one(Priority::Critical, Some(-5)),
+            EisenhowerQuadrant::DoFirst
+        );
+        assert_eq!(
+            classify_one(Priority::High, Some(49)),
+            EisenhowerQuadrant::Schedule
+        );
+        assert_eq!(
+            classify_one(Priority::Medium, Some(1)),
+            EisenhowerQuadrant::Delegate
+        );
+        assert_eq!(
+            classify_one(Priority::Medium, None),
+            EisenhowerQuadrant::Eliminate
+        );
+
+        // An explicit importance overrides the priority
+        let mut pinned = task_due("t", Priority::Low, None, 30, now);
+        pinned.importance = Importance::Important;
+        assert_eq!(
+            classify(&pinned, &thresholds(), now),
+            EisenhowerQuadrant::Schedule
+        );
+        let mut dismissed = task_due("t", Priority::Critical, Some(1), 30, now);
+        dismissed.importance = Importance::Unimportant;
+        assert_eq!(
+            classify(&dismissed, &thresholds(), now),
+            EisenhowerQuadrant::Delegate
+        );
+    }
+
+    #[test]
+    fn build_skips_closed_tasks_and_weights_by_estimate() {
+        let now = Utc::now();
+        let mut done = task_due("done", Priority::High, Some(1), 600, now);
+        done.status = TaskStatus::Completed;
+        let mut cancelled = task_due("cancelled", Priority::Low, None, 600, now);
+        cancelled.status = TaskStatus::Cancelled;
+        let tasks = vec![
+            task_due("urgent", Priority::High, Some(1), 90, now),
+            task_due("later", Priority::High, None, 30, now),
+            task_due("noise", Priority::Low, None, 0, now),
+            done,
+            cancelled,
+        ];
+
+        let matrix = EisenhowerMatrix::build(&tasks, &thresholds(), now);
+        assert_eq!(matrix.task_count(), 3);
+        assert_eq!(matrix.tasks(EisenhowerQuadrant::DoFirst)[0].title, "urgent");
+        assert_eq!(matrix.tasks(EisenhowerQuadrant::Schedule)[0].title, "later");
+        assert_eq!(
+            matrix.tasks(EisenhowerQuadrant::Eliminate)[0].title,
+            "noise"
+        );
+        assert!(matrix.tasks(EisenhowerQuadrant::Delegate).is_empty());
+
+        assert_eq!(matrix.time_share(EisenhowerQuadrant::DoFirst), 0.75);
+        assert_eq!(matrix.time_share(EisenhowerQuadrant::Schedule), 0.25);
+        assert_eq!(matrix.time_share(EisenhowerQuadrant::Eliminate), 0.0);
+    }
+
+    #[test]
+    fn time_share_falls_back_to_task_counts() {
+        let now = Utc::now();
+        let tasks = vec![
+            task_due("a", Priority::High, Some(1), 0, now),
+            task_due("b", Priority::Low, None, 0, now),
+            task_due("c", Priority::Low, None, -10, now),
+            task_due("d", Priority::Low, None, 0, now),
+        ];
+
+        let matrix = EisenhowerMatrix::build(&tasks, &thresholds(), now);
+        assert_eq!(matrix.time_share(EisenhowerQuadrant::DoFirst), 0.25);
+        assert_eq!(matrix.time_share(EisenhowerQuadrant::Eliminate), 0.75);
+
+        let empty = EisenhowerMatrix::build(&[], &thresholds(), now);
+        assert_eq!(empty.time_share(EisenhowerQuadrant::DoFirst), 0.0);
+    }
+
+    #[test]
+    fn validate_bounds_the_urgency_window() {
+        let mut thresholds = thresholds();
+        for (hours, valid) in [
+            (0, true),
+            (MAX_URGENT_WITHIN_HOURS, true),
+            (-1, false),
+            (MAX_URGENT_WITHIN_HOURS + 1, false),
+            (i64::MAX, false),
+        ] {
+            thresholds.urgent_within_hours = hours;
+            assert_eq!(validate(&thresholds).is_ok(), valid, "{} hours", hours);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+/// Result type of the database, the services and every command.
+pub type Result<T, E = AppError> = std::result::Result<T, E>;
+
+/// An error the frontend can act on. Serialized as
+/// `{ "code": ..., "message": ..., "details": ... }`: `code` is stable and
+/// safe to branch on, `message` is for display, and `details` holds the
+/// fields relevant to the code, or null.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{entity} not found")]
+    NotFound { entity: &'static str, id: String },
+
+    #[error("{message}")]
+    Validation {
+        field: Option<String>,
+        message: String,
+    },
+
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("{0}")]
+    Unsupported(String),
+
+    #[error("Database is locked. Enter your passphrase to unlock it.")]
+    Locked,
+
+    #[error("Incorrect passphrase")]
+    WrongPassphrase,
+
+    #[error("The database is busy. Try again in a moment.")]
+    Busy,
+
+    #[error("Database error: {0}")]
+    Database(sqlx::Error),
+
+    #[error("AI service error: {0}")]
+    Ai(String),
+
+    #[error("File error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn not_found(entity: &'static str, id: impl Into<String>) -> Self {
+        AppError::NotFound {
+            entity,
+            id: id.into(),
+        }
+    }
+
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        AppError::Validation {
+            field: Some(field.to_string()),
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound { .. } => "not_found",
+            AppError::Validation { .. } => "validation",
+            AppError::Conflict(_) => "conflict",
+            AppError::Unsupported(_) => "unsupported",
+            AppError::Locked => "locked",
+            AppError::WrongPassphrase => "wrong_passphrase",
+            AppError::Busy => "busy",
+            AppError::Database(_) => "database",
+            AppError::Ai(_) => "ai",
+            AppError::Io(_) => "io",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            AppError::NotFound { entity, id } => Some(json!({ "entity": entity, "id": id })),
+            AppError::Validation {
+                field: Some(field), ..
+            } => Some(json!({ "field": field })),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("AppError", 3)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("details", &self.details())?;
+        error.end()
+    }
+}
+
+// SQLite result codes; extended codes keep the primary one in the low byte
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+const SQLITE_CONSTRAINT_PRIMARYKEY: i32 = 1555;
+const SQLITE_CONSTRAINT_UNIQUE: i32 = 2067;
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        let db = match &e {
+            sqlx::Error::PoolTimedOut => return AppError::Busy,
+            sqlx::Error::Database(db) => db,
+            _ => return AppError::Database(e),
+        };
+
+        match db.code().and_then(|code| code.parse::<i32>().ok()) {
+            Some(code) if matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED) => AppError::Busy,
+            Some(SQLITE_CONSTRAINT_PRIMARYKEY | SQLITE_CONSTRAINT_UNIQUE) => {
+                AppError::Conflict(format!("Already exists ({})", db.message()))
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Internal(format!("Invalid JSON: {}", e))
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Ai(e.to_string())
+    }
+}
+
+/// CSV is only read from files the user picked, so a bad one is bad input.
+impl From<csv::Error> for AppError {
+    fn from(e: csv::Error) -> Self {
+        AppError::Validation {
+            field: None,
+            message: format!("Invalid CSV: {}", e),
+        }
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+/// Modules that still use anyhow can raise an `AppError` inside it; it is
+/// recovered here rather than flattened into `Internal`.
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<AppError>() {
+            Ok(app) => return app,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<sqlx::Error>() {
+            Ok(db) => return db.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<std::io::Error>() {
+            Ok(io) => io.into(),
+            Err(e) => AppError::Internal(format!("{:#}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use std::borrow::Cow;
+
+    /// A driver error carrying just a SQLite result code.
+    #[derive(Debug)]
+    struct SqliteCode(&'static str);
+
+    impl std::fmt::Display for SqliteCode {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "sqlite error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for SqliteCode {}
+
+    impl DatabaseError for SqliteCode {
+        fn message(&self) -> &str {
+            "test error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    fn code_of(code: &'static str) -> &'static str {
+        AppError::from(sqlx::Error::Database(Box::new(SqliteCode(code)))).code()
+    }
+
+    #[test]
+    fn sqlite_codes_map_to_app_errors() {
+        // SQLITE_BUSY, SQLITE_LOCKED and their extended codes
+        for code in ["5", "6", "261", "517", "262"] {
+            assert_eq!(code_of(code), "busy", "code {}", code);
+        }
+        assert_eq!(AppError::from(sqlx::Error::PoolTimedOut).code(), "busy");
+
+        assert_eq!(code_of("1555"), "conflict");
+        assert_eq!(code_of("2067"), "conflict");
+
+        // Other constraints, such as a foreign key, are not duplicates
+        assert_eq!(code_of("787"), "database");
+        assert_eq!(code_of("not a number"), "database");
+        assert_eq!(AppError::from(sqlx::Error::RowNotFound).code(), "database");
+    }
+
+    #[tokio::test]
+    async fn a_duplicate_key_from_sqlite_is_a_conflict() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE t (id TEXT PRIMARY KEY)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let insert = || sqlx::query("INSERT INTO t (id) VALUES ('a')").execute(&pool);
+
+        insert().await.unwrap();
+        let err = AppError::from(insert().await.unwrap_err());
+        assert_eq!(err.code(), "conflict");
+    }
+
+    #[test]
+    fn anyhow_errors_keep_their_kind() {
+        let wrapped = anyhow::Error::from(AppError::not_found("Task", "t1"));
+        assert!(matches!(
+            AppError::from(wrapped),
+            AppError::NotFound { entity: "Task", ref id } if id == "t1"
+        ));
+
+        let db = anyhow::Error::from(sqlx::Error::PoolTimedOut);
+        assert_eq!(AppError::from(db).code(), "busy");
+
+        let io = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(AppError::from(io).code(), "io");
+
+        let other = anyhow::anyhow!("disk on fire").context("Backup failed");
+        match AppError::from(other) {
+            AppError::Internal(message) => assert_eq!(message, "Backup failed: disk on fire"),
+            e => panic!("expected an internal error, got {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::error::{AppError, Result};
+use crate::models::{EscalationRule, EscalationSettings, Priority, Task, TaskStatus};
+
+/// Ten years. Far larger windows overflow chrono's date arithmetic.
+const MAX_DUE_WITHIN_HOURS: i64 = 24 * 3650;
+
+/// Checks the rules before they are saved; the escalation job runs them
+/// every 15 minutes.
+pub fn validate(settings: &EscalationSettings) -> Result<()> {
+    for (index, rule) in settings.rules.iter().enumerate() {
+        if !(0..=MAX_DUE_WITHIN_HOURS).contains(&rule.due_within_hours) {
+            return Err(AppError::validation(
+                &format!("escalation.rules[{}].due_within_hours", index),
+                format!("Must be between 0 and {} hours", MAX_DUE_WITHIN_HOURS),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn rule_matches(rule: &EscalationRule, task: &Task, now: DateTime<Utc>) -> bool {
+    let due_date = match task.due_date {
+        Some(due_date) => due_date,
+        None => return false,
+    };
+
+    // Overdue tasks match every rule
+    if due_date - now > Duration::hours(rule.due_within_hours) {
+        return false;
+    }
+
+    !rule.only_if_not_started || task.status == TaskStatus::Pending
+}
+
+/// Returns the priority the task should be raised to together with the rule
+/// that asked for it, or `None` when the task should be left alone. Priorities
+/// are never lowered, and pinned, closed or undated tasks are skipped.
+pub fn evaluate<'a>(
+    task: &Task,
+    rules: &'a [EscalationRule],
+    now: DateTime<Utc>,
+) -> Option<(Priority, &'a EscalationRule)> {
+    if task.priority_pinned || !task.is_open() {
+        return None;
+    }
+
+    rules
+        .iter()
+        .filter(|rule| rule_matches(rule, task, now))
+        .max_by_key(|rule| rule.escalate_to)
+        .filter(|rule| rule.escalate_to > task.priority)
+        .map(|rule| (rule.escalate_to, rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::task;
+
+    fn rule(
+        due_within_hours: i64,
+        only_if_not_started: bool,
+        escalate_to: Priority,
+    ) -> EscalationRule {
+        EscalationRule {
+            name: format!("within {}h", due_within_hours),
+            due_within_hours,
+            only_if_not_started,
+            escalate_to,
+        }
+    }
+
+    fn task_due(priority: Priority, due_in_hours: Option<i64>, now: DateTime<Utc>) -> Task {
+        let mut task = task("t");
+        task.priority = priority;
+        task.due_date = due_in_hours.map(|h| now + Duration::hours(h));
+        task
+    }
+
+    #[test]
+    fn rule_matches_on_due_date_and_start() {
+        let now = Utc::now();
+        let within_day = rule(24, false, Priority::High);
+
+        assert!(rule_matches(
+            &within_day,
+            &task_due(Priority::Low, Some(24), now),
+            now
+        ));
+        assert!(rule_matches(
+            &within_day,
+            &task_due(Priority::Low, Some(-3), now),
+            now
+        ));
+        assert!(!rule_matches(
+            &within_day,
+            &task_due(Priority::Low, Some(25), now),
+            now
+        ));
+        assert!(!rule_matches(
+            &within_day,
+            &task_due(Priority::Low, None, now),
+            now
+        ));
+
+        let not_started = rule(24, true, Priority::High);
+        let mut started = task_due(Priority::Low, Some(1), now);
+        assert!(rule_matches(&not_started, &started, now));
+        started.status = TaskStatus::InProgress;
+        assert!(!rule_matches(&not_started, &started, now));
+        assert!(rule_matches(&within_day, &started, now));
+    }
+
+    #[test]
+    fn evaluate_picks_the_highest_matching_rule_and_never_lowers() {
+        let now = Utc::now();
+        let rules = vec![
+            rule(72, false, Priority::Medium),
+            rule(24, false, Priority::High),
+            rule(4, false, Priority::Critical),
+        ];
+
+        let soon = task_due(Priority::Low, Some(10), now);
+        let (priority, matched) = evaluate(&soon, &rules, now).unwrap();
+        assert_eq!(priority, Priority::High);
+        assert_eq!(matched.due_within_hours, 24);
+
+        let overdue = task_due(Priority::Low, Some(-1), now);
+        assert_eq!(
+            evaluate(&overdue, &rules, now).unwrap().0,
+            Priority::Critical
+        );
+
+        // Already at or above what the rules ask for
+        assert!(evaluate(&task_due(Priority::High, Some(10), now), &rules, now).is_none());
+        assert!(evaluate(&task_due(Priority::Low, Some(100), now), &rules, now).is_none());
+
+        let mut pinned = task_due(Priority::Low, Some(1), now);
+        pinned.priority_pinned = true;
+        assert!(evaluate(&pinned, &rules, now).is_none());
+
+        let mut done = task_due(Priority::Low, Some(1), now);
+        done.status = TaskStatus::Completed;
+        assert!(evaluate(&done, &rules, now).is_none());
+    }
+
+    #[test]
+    fn validate_bounds_each_rule() {
+        let mut settings = EscalationSettings::default();
+        assert!(validate(&settings).is_ok());
+
+        settings.rules.push(rule(i64::MAX, false, Priority::High));
+        let err = validate(&settings).unwrap_err();
+        let index = settings.rules.len() - 1;
+        assert!(matches!(
+            err,
+            AppError::Validation { field: Some(ref f), .. }
+                if *f == format!("escalation.rules[{}].due_within_hours", index)
+        ));
+
+        settings.rules.last_mut().unwrap().due_within_hours = -1;
+        assert!(validate(&settings).is_err());
+    }
+}
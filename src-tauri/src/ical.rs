@@ -0,0 +1,487 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::HashMap;
+
+use crate::importers::{self, ImportedTasks};
+use crate::models::*;
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const PROJECT_PROPERTY: &str = "X-CHICANDOIT-PROJECT";
+const ESTIMATE_PROPERTY: &str = "X-CHICANDOIT-ESTIMATE"; // minutes
+const STATUS_PROPERTY: &str = "X-CHICANDOIT-STATUS"; // statuses VTODO cannot express
+
+/// Serializes tasks as an RFC 5545 calendar of `VTODO` components. The task
+/// id becomes the UID so a round trip updates the same tasks.
+pub fn tasks_to_ics(tasks: &[Task]) -> String {
+    let now = Utc::now();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//ChiCanDoIt//ChiCanDoIt//EN".to_string(),
+    ];
+
+    for task in tasks {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", escape(&task.id)));
+        lines.push(format!("DTSTAMP:{}", format_date(now)));
+        lines.push(format!("CREATED:{}", format_date(task.created_at)));
+        lines.push(format!("LAST-MODIFIED:{}", format_date(task.updated_at)));
+        lines.push(format!("SUMMARY:{}", escape(&task.title)));
+        if let Some(description) = &task.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        if let Some(due) = task.due_date {
+            lines.push(format!("DUE:{}", format_date(due)));
+        }
+        lines.push(format!("PRIORITY:{}", ical_priority(task.priority)));
+        lines.push(format!("STATUS:{}", ical_status(&task.status)));
+        if task.status == TaskStatus::Paused {
+            lines.push(format!("{}:paused", STATUS_PROPERTY));
+        }
+        lines.push(format!("CATEGORIES:{}", escape(&task.category)));
+        if let Some(project) = &task.project {
+            lines.push(format!("{}:{}", PROJECT_PROPERTY, escape(project)));
+        }
+        lines.push(format!("{}:{}", ESTIMATE_PROPERTY, task.estimated_time));
+        if let Some(completed_at) = task.completed_at {
+            lines.push(format!("COMPLETED:{}", format_date(completed_at)));
+            lines.push("PERCENT-COMPLETE:100".to_string());
+        }
+        lines.push("END:VTODO".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+/// Parses the `VTODO` components of an `.ics` file. Events, journals and
+/// properties with no task equivalent are reported as warnings.
+pub fn tasks_from_ics(content: &str) -> Result<ImportedTasks> {
+    let lines = unfold(content);
+    if !lines
+        .iter()
+        .any(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err(AppError::validation(
+            "content",
+            "Not an iCalendar file: missing BEGIN:VCALENDAR",
+        ));
+    }
+
+    let mut imported = ImportedTasks::default();
+    let mut current: Option<Vec<Property>> = None;
+    // Components nested in a VTODO, such as VALARM
+    let mut depth: usize = 0;
+    // Components outside a VTODO, children included, such as a VTIMEZONE's
+    // STANDARD and DAYLIGHT. Only the outermost one is reported.
+    let mut skipped_depth: usize = 0;
+    let mut skipped: HashMap<String, usize> = HashMap::new();
+
+    for line in &lines {
+        let property = match Property::parse(line) {
+            Some(property) => property,
+            None => continue,
+        };
+
+        match (
+            property.name.as_str(),
+            property.value.to_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VTODO") if current.is_none() && skipped_depth == 0 => current = Some(vec![]),
+            // Also closes nested components left open by a malformed file
+            ("END", "VTODO") => {
+                depth = 0;
+                if let Some(properties) = current.take() {
+                    if let Some(task) = build_task(&properties, &mut imported.warnings) {
+                        imported.tasks.push(task);
+                    }
+                }
+            }
+            ("BEGIN", "VCALENDAR") | ("END", "VCALENDAR") => {}
+            ("BEGIN", component) => {
+                if current.is_some() {
+                    depth += 1;
+                } else {
+                    if skipped_depth == 0 {
+                        *skipped.entry(component.to_string()).or_default() += 1;
+                    }
+                    skipped_depth += 1;
+                }
+            }
+            ("END", _) if current.is_some() => depth = depth.saturating_sub(1),
+            ("END", _) => skipped_depth = skipped_depth.saturating_sub(1),
+            _ if depth == 0 => {
+                if let Some(properties) = current.as_mut() {
+                    properties.push(property);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut skipped: Vec<_> = skipped.into_iter().collect();
+    skipped.sort();
+    for (component, count) in skipped {
+        if component != "VTIMEZONE" {
+            imported
+                .warnings
+                .push(format!("Skipped {} {} component(s)", count, component));
+        }
+    }
+
+    Ok(imported)
+}
+
+struct Property {
+    name: String,
+    params: HashMap<String, String>,
+    value: String,
+}
+
+impl Property {
+    /// Splits `NAME;PARAM=VALUE:value`, ignoring colons inside quoted
+    /// parameter values.
+    fn parse(line: &str) -> Option<Self> {
+        let mut in_quotes = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                None
+            }
+            ':' if !in_quotes => Some(i),
+            _ => None,
+        })?;
+
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+            .collect();
+
+        Some(Self {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn date(&self, warnings: &mut Vec<String>) -> Option<DateTime<Utc>> {
+        let value = self.value.trim();
+        if self.params.get("VALUE").map(String::as_str) == Some("DATE") || value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(importers::end_of_day);
+        }
+
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT) {
+            return Some(dt.and_utc());
+        }
+
+        let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        if let Some(tzid) = self.params.get("TZID") {
+            warnings.push(format!(
+                "{} in time zone {} was read as local time",
+                self.name, tzid
+            ));
+        }
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+fn build_task(properties: &[Property], warnings: &mut Vec<String>) -> Option<Task> {
+    let get = |name: &str| properties.iter().find(|p| p.name == name);
+    let text = |name: &str| get(name).map(|p| unescape(&p.value));
+
+    let title = match text("SUMMARY").filter(|s| !s.trim().is_empty()) {
+        Some(title) => title,
+        None => {
+            warnings.push(format!(
+                "Skipped a VTODO without a summary (UID {})",
+                text("UID").unwrap_or_default()
+            ));
+            return None;
+        }
+    };
+
+    let id = match text("UID") {
+        Some(uid) if !uid.is_empty() => uid,
+        _ => importers::imported_id("ical", &title),
+    };
+    let mut task = importers::new_task(id, &title);
+    task.description = text("DESCRIPTION");
+
+    if let Some(created) = get("CREATED").and_then(|p| p.date(warnings)) {
+        task.created_at = created;
+        task.updated_at = created;
+    }
+    if let Some(modified) = get("LAST-MODIFIED").and_then(|p| p.date(warnings)) {
+        task.updated_at = modified;
+    }
+    task.due_date = get("DUE").and_then(|p| p.date(warnings));
+
+    task.priority = match get("PRIORITY").and_then(|p| p.value.trim().parse::<u8>().ok()) {
+        Some(1..=2) => Priority::Critical,
+        Some(3..=4) => Priority::High,
+        Some(6..=9) => Priority::Low,
+        _ => Priority::Medium,
+    };
+
+    let categories: Vec<String> = get("CATEGORIES")
+        .map(|p| split_list(&p.value))
+        .unwrap_or_default();
+    if let Some(first) = categories.first() {
+        task.category = first.clone();
+    }
+    if categories.len() > 1 {
+        warnings.push(format!(
+            "'{}': only the first category was kept; dropped {}",
+            task.title,
+            categories[1..].join(", ")
+        ));
+    }
+
+    task.project = text(PROJECT_PROPERTY);
+    if let Some(estimate) = get(ESTIMATE_PROPERTY).and_then(|p| p.value.trim().parse().ok()) {
+        task.estimated_time = estimate;
+    }
+
+    let completed_at = get("COMPLETED").and_then(|p| p.date(warnings));
+    match get("STATUS")
+        .map(|p| p.value.trim().to_uppercase())
+        .as_deref()
+    {
+        Some("COMPLETED") => importers::complete(&mut task, completed_at),
+        Some("CANCELLED") => task.status = TaskStatus::Cancelled,
+        Some("IN-PROCESS") => task.status = TaskStatus::InProgress,
+        _ if completed_at.is_some() => importers::complete(&mut task, completed_at),
+        _ => {}
+    }
+    if text(STATUS_PROPERTY).as_deref() == Some("paused") && task.is_open() {
+        task.status = TaskStatus::Paused;
+    }
+
+    if get("RRULE").is_some() {
+        warnings.push(format!(
+            "'{}': recurrence is not supported; imported as a one-off task",
+            task.title
+        ));
+    }
+
+    Some(task)
+}
+
+fn ical_priority(priority: Priority) -> u8 {
+    match priority {
+        Priority::Critical => 1,
+        Priority::High => 3,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn ical_status(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending | TaskStatus::Paused => "NEEDS-ACTION",
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Completed => "COMPLETED",
+        TaskStatus::Cancelled => "CANCELLED",
+    }
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format(DATE_TIME_FORMAT).to_string()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Splits a comma-separated list value, respecting escaped commas.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            ',' if !escaped => items.push(std::mem::take(&mut current)),
+            '\\' if !escaped => {
+                escaped = true;
+                current.push(c);
+                continue;
+            }
+            _ => current.push(c),
+        }
+        escaped = false;
+    }
+    items.push(current);
+
+    items
+        .iter()
+        .map(|item| unescape(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Folds a content line to at most 75 octets per physical line, without
+/// splitting a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines.into_iter().filter(|l| !l.trim().is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::task;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parses_vtodos_and_reports_other_components_once() {
+        let imported = tasks_from_ics(include_str!("../fixtures/import/calendar.ics")).unwrap();
+        assert_eq!(imported.tasks.len(), 2);
+
+        let passport = &imported.tasks[0];
+        assert_eq!(passport.id, "todo-1");
+        assert_eq!(passport.title, "Renew passport, urgently");
+        assert_eq!(
+            passport.description.as_deref(),
+            Some("Photos first\nthen the form")
+        );
+        assert_eq!(passport.created_at, utc("2024-03-01T09:00:00Z"));
+        assert_eq!(passport.updated_at, utc("2024-03-02T10:00:00Z"));
+        assert_eq!(
+            passport.due_date,
+            Some(importers::end_of_day(
+                NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+            ))
+        );
+        assert_eq!(passport.priority, Priority::Critical);
+        assert_eq!(passport.category, "admin");
+        assert_eq!(passport.project.as_deref(), Some("Trip"));
+        assert_eq!(passport.estimated_time, 90);
+        assert_eq!(passport.status, TaskStatus::InProgress);
+
+        let hotel = &imported.tasks[1];
+        assert_eq!(
+            hotel.title,
+            "Book a hotel for the conference in the city centre close to the venue"
+        );
+        assert_eq!(hotel.status, TaskStatus::Completed);
+        assert_eq!(hotel.completed_at, Some(utc("2024-03-05T12:00:00Z")));
+
+        assert_eq!(
+            imported.warnings,
+            [
+                "'Renew passport, urgently': only the first category was kept; dropped travel",
+                "'Book a hotel for the conference in the city centre close to the venue': recurrence is not supported; imported as a one-off task",
+                "Skipped a VTODO without a summary (UID todo-3)",
+                "Skipped 1 VEVENT component(s)",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_a_calendar() {
+        assert!(tasks_from_ics("BEGIN:VTODO\nSUMMARY:x\nEND:VTODO\n").is_err());
+    }
+
+    #[test]
+    fn unbalanced_components_do_not_swallow_later_tasks() {
+        let ics = "BEGIN:VCALENDAR\n\
+                   BEGIN:VTODO\nUID:stray-end\nEND:VALARM\nSUMMARY:Stray end\nEND:VTODO\n\
+                   BEGIN:VTODO\nUID:unclosed\nSUMMARY:Unclosed alarm\nBEGIN:VALARM\nEND:VTODO\n\
+                   END:VEVENT\n\
+                   BEGIN:VTODO\nUID:after\nSUMMARY:After\nEND:VTODO\n\
+                   END:VCALENDAR\n";
+
+        let imported = tasks_from_ics(ics).unwrap();
+        let titles: Vec<&str> = imported.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["Stray end", "Unclosed alarm", "After"]);
+    }
+
+    #[test]
+    fn round_trips_tasks() {
+        let mut paused = task("Naïve café; notes, \"quoted\" and a title long enough to be folded");
+        paused.description = Some("line one\nline two".to_string());
+        paused.project = Some("Home".to_string());
+        paused.status = TaskStatus::Paused;
+        let mut done = task("Done");
+        importers::complete(&mut done, None);
+
+        let ics = tasks_to_ics(&[paused.clone(), done.clone()]);
+        assert!(ics.lines().all(|line| line.len() <= 76)); // 75 octets and the \r
+        let imported = tasks_from_ics(&ics).unwrap();
+        assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+
+        let parsed = &imported.tasks[0];
+        assert_eq!(parsed.id, paused.id);
+        assert_eq!(parsed.title, paused.title);
+        assert_eq!(parsed.description, paused.description);
+        assert_eq!(parsed.project, paused.project);
+        assert_eq!(parsed.status, TaskStatus::Paused);
+        assert_eq!(parsed.priority, paused.priority);
+        assert_eq!(parsed.estimated_time, paused.estimated_time);
+        assert_eq!(imported.tasks[1].status, TaskStatus::Completed);
+    }
+}
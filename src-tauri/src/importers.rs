@@ -0,0 +1,996 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::*;
+
+const DEFAULT_CATEGORY: &str = "general";
+const DEFAULT_ESTIMATE: i32 = 60; // minutes, as in the task form
+
+/// Tasks parsed from another tool's export, plus a note for everything that
+/// had no equivalent here.
+#[derive(Debug, Default)]
+pub struct ImportedTasks {
+    pub tasks: Vec<Task>,
+    pub warnings: Vec<String>,
+}
+
+pub fn parse(source: ImportSource, content: &str) -> Result<ImportedTasks> {
+    match source {
+        ImportSource::Todoist => match content.trim_start().chars().next() {
+            Some('[') | Some('{') => parse_todoist_json(content),
+            _ => parse_todoist_csv(content),
+        },
+        ImportSource::Taskwarrior => parse_taskwarrior(content),
+        ImportSource::TodoTxt => Ok(parse_todo_txt(content)),
+    }
+}
+
+/// Ids are derived from the source's own ids, so importing the same export
+/// twice updates tasks instead of duplicating them.
+pub(crate) fn imported_id(source: &str, source_id: &str) -> String {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_OID,
+        format!("{}:{}", source, source_id).as_bytes(),
+    )
+    .to_string()
+}
+
+pub(crate) fn new_task(id: String, title: &str) -> Task {
+    let mut task = Task::new(CreateTaskRequest {
+        title: title.to_string(),
+        description: None,
+        priority: Priority::Medium,
+        priority_pinned: false,
+        importance: Importance::Auto,
+        category: DEFAULT_CATEGORY.to_string(),
+        project: None,
+        estimated_time: DEFAULT_ESTIMATE,
+        due_date: None,
+    });
+    task.id = id;
+    task
+}
+
+pub(crate) fn complete(task: &mut Task, completed_at: Option<DateTime<Utc>>) {
+    task.status = TaskStatus::Completed;
+    task.completed_at = Some(completed_at.unwrap_or(task.updated_at));
+}
+
+/// A date without a time is due by the end of that day, local time.
+pub(crate) fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let end = date.and_hms_opt(23, 59, 59).unwrap_or_default();
+    Local
+        .from_local_datetime(&end)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| end.and_utc())
+}
+
+/// Accepts RFC 3339, a local date-time without offset, or a bare date.
+pub(crate) fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc));
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(end_of_day)
+}
+
+/// Keeps the first label as the category and reports the rest.
+fn apply_labels(task: &mut Task, labels: &[String], warnings: &mut Vec<String>) {
+    if let Some(first) = labels.first() {
+        task.category = first.clone();
+    }
+    if labels.len() > 1 {
+        warnings.push(format!(
+            "'{}': only the first label became the category; dropped {}",
+            task.title,
+            labels[1..].join(", ")
+        ));
+    }
+}
+
+fn json_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn json_strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(json_string).collect())
+        .unwrap_or_default()
+}
+
+// Todoist
+
+/// Todoist's API counts priority upwards: 4 is p1, the most urgent.
+fn todoist_api_priority(value: i64) -> Priority {
+    match value {
+        4 => Priority::Critical,
+        3 => Priority::High,
+        2 => Priority::Medium,
+        _ => Priority::Low,
+    }
+}
+
+/// Reads the REST API task list, a sync/backup dump with `items` and
+/// `projects`, or an object with a `tasks` array.
+fn parse_todoist_json(content: &str) -> Result<ImportedTasks> {
+    let root: Value = serde_json::from_str(content)?;
+    let items = match &root {
+        Value::Array(items) => items.clone(),
+        Value::Object(map) => map
+            .get("items")
+            .or_else(|| map.get("tasks"))
+            .and_then(|v| v.as_array())
+            .cloned()
+            .ok_or_else(|| {
+                AppError::validation("content", "Todoist export has no items or tasks")
+            })?,
+        _ => {
+            return Err(AppError::validation(
+                "content",
+                "Unrecognised Todoist export",
+            ))
+        }
+    };
+
+    let projects: HashMap<String, String> = root
+        .get("projects")
+        .and_then(|v| v.as_array())
+        .map(|projects| {
+            projects
+                .iter()
+                .filter_map(|p| Some((json_string(p.get("id")?)?, json_string(p.get("name")?)?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut imported = ImportedTasks::default();
+    for (index, item) in items.iter().enumerate() {
+        let title = match item.get("content").and_then(json_string) {
+            Some(title) => title,
+            None => {
+                imported.warnings.push(format!(
+                    "Todoist item {}: skipped, it has no content",
+                    index + 1
+                ));
+                continue;
+            }
+        };
+
+        let source_id = item
+            .get("id")
+            .and_then(json_string)
+            .unwrap_or_else(|| format!("{}:{}", index, title));
+        let mut task = new_task(imported_id("todoist", &source_id), &title);
+
+        task.description = item.get("description").and_then(json_string);
+        task.priority =
+            todoist_api_priority(item.get("priority").and_then(|v| v.as_i64()).unwrap_or(1));
+
+        if let Some(project_id) = item.get("project_id").and_then(json_string) {
+            task.project = Some(projects.get(&project_id).cloned().unwrap_or(project_id));
+        }
+        apply_labels(
+            &mut task,
+            &json_strings(item.get("labels")),
+            &mut imported.warnings,
+        );
+
+        if let Some(created) = item
+            .get("added_at")
+            .or_else(|| item.get("created_at"))
+            .and_then(|v| v.as_str())
+            .and_then(parse_date)
+        {
+            task.created_at = created;
+            task.updated_at = created;
+        }
+
+        if let Some(due) = item.get("due").filter(|d| !d.is_null()) {
+            let date = due
+                .get("datetime")
+                .or_else(|| due.get("date"))
+                .and_then(|v| v.as_str());
+            match date.and_then(parse_date) {
+                Some(date) => task.due_date = Some(date),
+                None => imported
+                    .warnings
+                    .push(format!("'{}': could not read due date {}", task.title, due)),
+            }
+            if due.get("is_recurring").and_then(|v| v.as_bool()) == Some(true) {
+                imported.warnings.push(format!(
+                    "'{}': recurrence is not supported; imported the next occurrence only",
+                    task.title
+                ));
+            }
+        }
+
+        if let Some(duration) = item.get("duration").filter(|d| !d.is_null()) {
+            let amount = duration.get("amount").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+            match duration.get("unit").and_then(|v| v.as_str()) {
+                Some("minute") => task.estimated_time = amount,
+                Some("day") => task.estimated_time = amount * 8 * 60, // working days
+                _ => {}
+            }
+        }
+
+        let completed = item
+            .get("checked")
+            .or_else(|| item.get("is_completed"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if completed {
+            let completed_at = item
+                .get("completed_at")
+                .and_then(|v| v.as_str())
+                .and_then(parse_date);
+            complete(&mut task, completed_at);
+        }
+
+        if item.get("parent_id").is_some_and(|v| !v.is_null()) {
+            imported.warnings.push(format!(
+                "'{}': sub-task imported as a top-level task",
+                task.title
+            ));
+        }
+
+        imported.tasks.push(task);
+    }
+
+    Ok(imported)
+}
+
+/// Reads Todoist's CSV project export. Sections become categories, notes are
+/// appended to the task above them, and priority 1 is the most urgent.
+fn parse_todoist_csv(content: &str) -> Result<ImportedTasks> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_uppercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+
+    let (type_col, content_col) = match (column("TYPE"), column("CONTENT")) {
+        (Some(t), Some(c)) => (t, c),
+        _ => {
+            return Err(AppError::validation(
+                "content",
+                "Not a Todoist CSV export: missing TYPE or CONTENT column",
+            ))
+        }
+    };
+    let description_col = column("DESCRIPTION");
+    let priority_col = column("PRIORITY");
+    let indent_col = column("INDENT");
+    let date_col = column("DATE");
+    let duration_col = column("DURATION");
+    let duration_unit_col = column("DURATION_UNIT");
+
+    let mut imported = ImportedTasks::default();
+    let mut section: Option<String> = None;
+
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let row = index + 2; // after the header
+        let field = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+
+        let content = field(Some(content_col)).unwrap_or_default().to_string();
+        match field(Some(type_col)).unwrap_or_default() {
+            "section" => {
+                section = Some(content);
+                continue;
+            }
+            "note" => {
+                match imported.tasks.last_mut() {
+                    Some(task) => {
+                        let description = task.description.get_or_insert_with(String::new);
+                        if !description.is_empty() {
+                            description.push('\n');
+                        }
+                        description.push_str(&content);
+                    }
+                    None => imported
+                        .warnings
+                        .push(format!("Row {}: note without a task, skipped", row)),
+                }
+                continue;
+            }
+            "task" => {}
+            "" => continue,
+            other => {
+                imported.warnings.push(format!(
+                    "Row {}: unknown row type '{}', skipped",
+                    row, other
+                ));
+                continue;
+            }
+        }
+
+        if content.is_empty() {
+            imported
+                .warnings
+                .push(format!("Row {}: task without content, skipped", row));
+            continue;
+        }
+
+        let mut task = new_task(
+            imported_id("todoist-csv", &format!("{}:{}", row, content)),
+            &content,
+        );
+        task.description = field(description_col).map(str::to_string);
+        task.priority = match field(priority_col).and_then(|p| p.parse::<i64>().ok()) {
+            Some(1) => Priority::Critical,
+            Some(2) => Priority::High,
+            Some(3) => Priority::Medium,
+            _ => Priority::Low,
+        };
+        if let Some(section) = &section {
+            task.category = section.clone();
+        }
+
+        if let Some(date) = field(date_col) {
+            match parse_date(date) {
+                Some(due) => task.due_date = Some(due),
+                None => imported.warnings.push(format!(
+                    "Row {}: due date '{}' is not a calendar date, dropped",
+                    row, date
+                )),
+            }
+        }
+
+        if let Some(amount) = field(duration_col).and_then(|d| d.parse::<i32>().ok()) {
+            match field(duration_unit_col) {
+                Some("day") => task.estimated_time = amount * 8 * 60,
+                _ => task.estimated_time = amount,
+            }
+        }
+
+        if field(indent_col)
+            .and_then(|i| i.parse::<i32>().ok())
+            .unwrap_or(1)
+            > 1
+        {
+            imported.warnings.push(format!(
+                "Row {}: sub-task '{}' imported as a top-level task",
+                row, task.title
+            ));
+        }
+
+        imported.tasks.push(task);
+    }
+
+    Ok(imported)
+}
+
+// Taskwarrior
+
+fn parse_taskwarrior_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|dt| dt.and_utc())
+        .or_else(|| parse_date(value))
+}
+
+/// Reads `task export` output: a JSON array, or one object per line as
+/// older versions print it.
+fn parse_taskwarrior(content: &str) -> Result<ImportedTasks> {
+    let items: Vec<Value> = if content.trim_start().starts_with('[') {
+        serde_json::from_str(content)?
+    } else {
+        content
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+
+    let mut imported = ImportedTasks::default();
+    for (index, item) in items.iter().enumerate() {
+        let date = |key: &str| {
+            item.get(key)
+                .and_then(|v| v.as_str())
+                .and_then(parse_taskwarrior_date)
+        };
+
+        let title = match item.get("description").and_then(json_string) {
+            Some(title) => title,
+            None => {
+                imported.warnings.push(format!(
+                    "Taskwarrior task {}: skipped, it has no description",
+                    index + 1
+                ));
+                continue;
+            }
+        };
+
+        let status = item
+            .get("status")
+            .and_then(|v| v.as_str())
+            .unwrap_or("pending");
+        if status == "recurring" {
+            imported.warnings.push(format!(
+                "'{}': recurring template skipped; its pending instances are imported",
+                title
+            ));
+            continue;
+        }
+
+        let id = match item.get("uuid").and_then(|v| v.as_str()) {
+            Some(uuid) if Uuid::parse_str(uuid).is_ok() => uuid.to_string(),
+            Some(uuid) => imported_id("taskwarrior", uuid),
+            None => imported_id("taskwarrior", &format!("{}:{}", index, title)),
+        };
+        let mut task = new_task(id, &title);
+
+        task.priority = match item.get("priority").and_then(|v| v.as_str()) {
+            Some("H") => Priority::High,
+            Some("M") => Priority::Medium,
+            Some("L") => Priority::Low,
+            _ => Priority::Medium,
+        };
+        task.project = item.get("project").and_then(json_string);
+        apply_labels(
+            &mut task,
+            &json_strings(item.get("tags")),
+            &mut imported.warnings,
+        );
+        task.due_date = date("due");
+
+        if let Some(entry) = date("entry") {
+            task.created_at = entry;
+            task.updated_at = entry;
+        }
+        if let Some(modified) = date("modified") {
+            task.updated_at = modified;
+        }
+
+        let annotations: Vec<String> = item
+            .get("annotations")
+            .and_then(|v| v.as_array())
+            .map(|notes| {
+                notes
+                    .iter()
+                    .filter_map(|n| n.get("description").and_then(json_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !annotations.is_empty() {
+            task.description = Some(annotations.join("\n"));
+        }
+
+        match status {
+            "completed" => complete(&mut task, date("end")),
+            "deleted" => task.status = TaskStatus::Cancelled,
+            "waiting" => task.status = TaskStatus::Paused,
+            _ if item.get("start").is_some() => task.status = TaskStatus::InProgress,
+            _ => {}
+        }
+
+        if item.get("depends").is_some() {
+            imported.warnings.push(format!(
+                "'{}': dependencies are not supported and were dropped",
+                task.title
+            ));
+        }
+        if item.get("recur").is_some() {
+            imported.warnings.push(format!(
+                "'{}': imported as a one-off occurrence of a recurring task",
+                task.title
+            ));
+        }
+
+        imported.tasks.push(task);
+    }
+
+    Ok(imported)
+}
+
+// todo.txt
+
+/// Parses todo.txt lines: `x` completion marker and dates, `(A)` priority,
+/// `+project`, `@context` (becomes the category) and `due:YYYY-MM-DD`.
+fn parse_todo_txt(content: &str) -> ImportedTasks {
+    let mut imported = ImportedTasks::default();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let mut rest = line.trim();
+        if rest.is_empty() {
+            continue;
+        }
+
+        let mut completed = false;
+        let mut completed_at = None;
+        let mut created_at = None;
+        let mut priority = None;
+
+        if let Some(after) = rest.strip_prefix("x ") {
+            completed = true;
+            rest = after.trim_start();
+            if let Some((date, after)) = take_date(rest) {
+                completed_at = Some(date);
+                rest = after;
+            }
+        } else if let Some(letter) = priority_marker(rest) {
+            priority = Some(letter);
+            rest = rest[4..].trim_start();
+        }
+
+        if let Some((date, after)) = take_date(rest) {
+            created_at = Some(date);
+            rest = after;
+        }
+
+        // The id comes from the text alone: due: and pri: change as a task
+        // is rescheduled or completed, and spacing carries no meaning
+        let description: Vec<&str> = rest
+            .split_whitespace()
+            .filter(|w| !w.starts_with("due:") && !w.starts_with("pri:"))
+            .collect();
+        let mut task = new_task(imported_id("todotxt", &description.join(" ")), "");
+        let mut words = Vec::new();
+        let mut projects = Vec::new();
+        let mut contexts = Vec::new();
+
+        for word in rest.split_whitespace() {
+            if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+                projects.push(project.to_string());
+            } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+                contexts.push(context.to_string());
+            } else if let Some(due) = word.strip_prefix("due:") {
+                match parse_date(due) {
+                    Some(due) => task.due_date = Some(due),
+                    None => imported.warnings.push(format!(
+                        "Line {}: could not read due date '{}'",
+                        line_number, due
+                    )),
+                }
+            } else if let Some(pri) = word.strip_prefix("pri:") {
+                // Completed tasks keep their priority as pri:X
+                priority = pri.chars().next();
+            } else {
+                words.push(word);
+            }
+        }
+
+        task.title = words.join(" ");
+        if task.title.is_empty() {
+            imported
+                .warnings
+                .push(format!("Line {}: no task text, skipped", line_number));
+            continue;
+        }
+
+        task.priority = match priority {
+            Some('A') => Priority::Critical,
+            Some('B') => Priority::High,
+            Some('C') => Priority::Medium,
+            Some(_) => Priority::Low,
+            None => Priority::Medium,
+        };
+
+        if let Some(created) = created_at {
+            task.created_at = created;
+            task.updated_at = completed_at.unwrap_or(created);
+        }
+
+        task.project = projects.first().cloned();
+        if projects.len() > 1 {
+            imported.warnings.push(format!(
+                "Line {}: only the first project was kept; dropped {}",
+                line_number,
+                projects[1..].join(", ")
+            ));
+        }
+        apply_labels(&mut task, &contexts, &mut imported.warnings);
+
+        if completed {
+            complete(&mut task, completed_at);
+        }
+
+        imported.tasks.push(task);
+    }
+
+    imported
+}
+
+/// The `(A) ` priority marker at the start of an open task.
+fn priority_marker(value: &str) -> Option<char> {
+    let bytes = value.as_bytes();
+    let is_marker = bytes.len() >= 4
+        && bytes[0] == b'('
+        && bytes[1].is_ascii_uppercase()
+        && bytes[2] == b')'
+        && bytes[3] == b' ';
+    is_marker.then(|| bytes[1] as char)
+}
+
+/// Splits a leading `YYYY-MM-DD` off `value`. todo.txt dates have no time, so
+/// they are taken as the start of that day, local time.
+fn take_date(value: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (date, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let start = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()?
+        .with_timezone(&Utc);
+    Some((start, rest.trim_start()))
+}
+
+// Legacy Node server
+
+fn legacy_priority(value: &str) -> Option<Priority> {
+    match value.trim().to_lowercase().as_str() {
+        "critical" | "urgent" => Some(Priority::Critical),
+        "high" | "h" => Some(Priority::High),
+        "medium" | "med" | "normal" | "m" => Some(Priority::Medium),
+        "low" | "l" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+fn legacy_status(value: &str) -> Option<TaskStatus> {
+    let normalized = value.trim().to_lowercase().replace(['_', ' '], "-");
+    match normalized.as_str() {
+        "pending" | "todo" | "to-do" | "open" | "new" => Some(TaskStatus::Pending),
+        "in-progress" | "inprogress" | "doing" | "started" | "active" => {
+            Some(TaskStatus::InProgress)
+        }
+        "completed" | "complete" | "done" | "finished" => Some(TaskStatus::Completed),
+        "paused" | "on-hold" | "blocked" => Some(TaskStatus::Paused),
+        "cancelled" | "canceled" => Some(TaskStatus::Cancelled),
+        _ => None,
+    }
+}
+
+fn legacy_minutes(value: Option<&Value>) -> Option<i32> {
+    match value? {
+        Value::Number(n) => n.as_f64().map(|m| m.round() as i32),
+        Value::String(s) => s.trim().parse::<f64>().ok().map(|m| m.round() as i32),
+        _ => None,
+    }
+}
+
+/// Reads the `data/tasks.json` file written by the old Node/Express server:
+/// an array of tasks with a `task` title, camelCase timestamps, `tags` and
+/// free-form status and priority strings. The frontend's `{ tasks: [...] }`
+/// export is accepted too.
+pub fn parse_legacy_tasks(content: &str) -> Result<ImportedTasks> {
+    let root: Value = serde_json::from_str(content)?;
+    let items = match &root {
+        Value::Array(items) => items,
+        Value::Object(map) => map
+            .get("tasks")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| AppError::validation("content", "Expected an array of tasks"))?,
+        _ => {
+            return Err(AppError::validation(
+                "content",
+                "Expected an array of tasks",
+            ))
+        }
+    };
+
+    let mut imported = ImportedTasks::default();
+    for (index, item) in items.iter().enumerate() {
+        let record = index + 1;
+        let field = |camel: &str, snake: &str| item.get(camel).or_else(|| item.get(snake));
+        let date = |camel: &str, snake: &str| {
+            field(camel, snake)
+                .and_then(|v| v.as_str())
+                .and_then(parse_date)
+        };
+
+        if !item.is_object() {
+            imported
+                .warnings
+                .push(format!("Record {}: not an object, skipped", record));
+            continue;
+        }
+
+        let title = match field("task", "title").and_then(json_string) {
+            Some(title) => title,
+            None => {
+                imported
+                    .warnings
+                    .push(format!("Record {}: no task text, skipped", record));
+                continue;
+            }
+        };
+
+        let legacy_id = item
+            .get("id")
+            .and_then(json_string)
+            .unwrap_or_else(|| format!("{}:{}", record, title));
+        let mut task = new_task(imported_id("legacy", &legacy_id), &title);
+        task.description = item.get("description").and_then(json_string);
+
+        if let Some(priority) = item.get("priority").and_then(|v| v.as_str()) {
+            match legacy_priority(priority) {
+                Some(priority) => task.priority = priority,
+                None => imported.warnings.push(format!(
+                    "Record {}: unknown priority '{}', using medium",
+                    record, priority
+                )),
+            }
+        }
+
+        let tags = json_strings(item.get("tags"));
+        match item.get("category").and_then(json_string) {
+            Some(category) => {
+                task.category = category;
+                if !tags.is_empty() {
+                    imported.warnings.push(format!(
+                        "Record {}: tags {} dropped, the task already has a category",
+                        record,
+                        tags.join(", ")
+                    ));
+                }
+            }
+            None => apply_labels(&mut task, &tags, &mut imported.warnings),
+        }
+
+        if let Some(minutes) = legacy_minutes(field("estimatedTime", "estimated_time")) {
+            task.estimated_time = minutes;
+        }
+        task.actual_time = legacy_minutes(field("actualTime", "actual_time"));
+        task.due_date = date("dueDate", "due_date");
+
+        // Ids were Date.now(), so they date records that lack createdAt
+        let created_at = date("createdAt", "created_at").or_else(|| {
+            legacy_id
+                .parse::<i64>()
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+        });
+        match created_at {
+            Some(created_at) => {
+                task.created_at = created_at;
+                task.updated_at = created_at;
+            }
+            None => imported.warnings.push(format!(
+                "Record {}: no creation time, using the import time",
+                record
+            )),
+        }
+        if let Some(updated_at) = date("updatedAt", "updated_at") {
+            task.updated_at = updated_at;
+        }
+
+        let status = item
+            .get("status")
+            .and_then(|v| v.as_str())
+            .unwrap_or("pending");
+        match legacy_status(status) {
+            Some(TaskStatus::Completed) => complete(&mut task, date("completedAt", "completed_at")),
+            Some(status) => task.status = status,
+            None => imported.warnings.push(format!(
+                "Record {}: unknown status '{}', imported as pending",
+                record, status
+            )),
+        }
+
+        imported.tasks.push(task);
+    }
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_todoist_json() {
+        let imported = parse(
+            ImportSource::Todoist,
+            include_str!("../fixtures/import/todoist.json"),
+        )
+        .unwrap();
+        assert_eq!(imported.tasks.len(), 2);
+
+        let milk = &imported.tasks[0];
+        assert_eq!(milk.id, imported_id("todoist", "7025"));
+        assert_eq!(milk.title, "Buy milk");
+        assert_eq!(milk.description.as_deref(), Some("Semi-skimmed"));
+        assert_eq!(milk.priority, Priority::Critical);
+        assert_eq!(milk.project.as_deref(), Some("Home"));
+        assert_eq!(milk.category, "errands");
+        assert_eq!(milk.created_at, utc("2024-03-01T09:00:00Z"));
+        assert_eq!(milk.due_date, Some(end_of_day(date("2024-03-05"))));
+        assert_eq!(milk.status, TaskStatus::Pending);
+
+        let plants = &imported.tasks[1];
+        assert_eq!(plants.id, imported_id("todoist", "7026"));
+        assert_eq!(plants.priority, Priority::Low);
+        assert_eq!(plants.project.as_deref(), Some("999"));
+        assert_eq!(plants.category, DEFAULT_CATEGORY);
+        assert_eq!(plants.estimated_time, 15);
+        assert_eq!(plants.status, TaskStatus::Completed);
+        assert_eq!(plants.completed_at, Some(utc("2024-03-02T18:30:00Z")));
+
+        assert_eq!(
+            imported.warnings,
+            [
+                "'Buy milk': only the first label became the category; dropped shop",
+                "'Water plants': recurrence is not supported; imported the next occurrence only",
+                "'Water plants': sub-task imported as a top-level task",
+                "Todoist item 3: skipped, it has no content",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_todoist_csv() {
+        let imported = parse(
+            ImportSource::Todoist,
+            include_str!("../fixtures/import/todoist.csv"),
+        )
+        .unwrap();
+        assert_eq!(imported.tasks.len(), 2);
+
+        let tap = &imported.tasks[0];
+        assert_eq!(tap.id, imported_id("todoist-csv", "3:Fix the tap"));
+        assert_eq!(tap.category, "Kitchen");
+        assert_eq!(tap.priority, Priority::Critical);
+        assert_eq!(
+            tap.description.as_deref(),
+            Some("Washer is worn\nBuy a 1/2 inch washer")
+        );
+        assert_eq!(tap.due_date, Some(end_of_day(date("2024-03-10"))));
+        assert_eq!(tap.estimated_time, 2 * 8 * 60);
+
+        let kettle = &imported.tasks[1];
+        assert_eq!(kettle.category, "Kitchen");
+        assert_eq!(kettle.priority, Priority::Low);
+        assert_eq!(kettle.due_date, None);
+        assert_eq!(kettle.estimated_time, DEFAULT_ESTIMATE);
+
+        assert_eq!(
+            imported.warnings,
+            [
+                "Row 5: due date 'every monday' is not a calendar date, dropped",
+                "Row 5: sub-task 'Descale kettle' imported as a top-level task",
+                "Row 7: unknown row type 'meeting', skipped",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_taskwarrior() {
+        let imported = parse(
+            ImportSource::Taskwarrior,
+            include_str!("../fixtures/import/taskwarrior.json"),
+        )
+        .unwrap();
+        assert_eq!(imported.tasks.len(), 3);
+
+        let report = &imported.tasks[0];
+        assert_eq!(report.id, "5f3b8a3e-1c2d-4e5f-8a9b-0c1d2e3f4a5b");
+        assert_eq!(report.priority, Priority::High);
+        assert_eq!(report.project.as_deref(), Some("work.q1"));
+        assert_eq!(report.category, "office");
+        assert_eq!(report.status, TaskStatus::InProgress);
+        assert_eq!(report.description.as_deref(), Some("Draft in shared drive"));
+        assert_eq!(report.created_at, utc("2024-03-01T09:00:00Z"));
+        assert_eq!(report.updated_at, utc("2024-03-02T10:00:00Z"));
+        assert_eq!(report.due_date, Some(utc("2024-03-10T17:00:00Z")));
+
+        let taxes = &imported.tasks[1];
+        assert_eq!(taxes.id, imported_id("taskwarrior", "not-a-uuid"));
+        assert_eq!(taxes.status, TaskStatus::Completed);
+        assert_eq!(taxes.completed_at, Some(utc("2024-01-15T12:00:00Z")));
+
+        assert_eq!(imported.tasks[2].status, TaskStatus::Cancelled);
+        assert_eq!(
+            imported.warnings,
+            [
+                "'File taxes': dependencies are not supported and were dropped",
+                "'Pay rent': recurring template skipped; its pending instances are imported",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_todo_txt() {
+        let imported = parse(
+            ImportSource::TodoTxt,
+            include_str!("../fixtures/import/todo.txt"),
+        )
+        .unwrap();
+        let titles: Vec<&str> = imported.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Call the bank",
+                "Pay the invoice",
+                "Read a book",
+                "Water plants"
+            ]
+        );
+
+        let bank = &imported.tasks[0];
+        assert_eq!(bank.priority, Priority::Critical);
+        assert_eq!(bank.project.as_deref(), Some("finance"));
+        assert_eq!(bank.category, "phone");
+        assert_eq!(bank.due_date, Some(end_of_day(date("2024-03-04"))));
+        assert_eq!(
+            bank.created_at.with_timezone(&Local).date_naive(),
+            date("2024-03-01")
+        );
+
+        let invoice = &imported.tasks[1];
+        assert_eq!(invoice.status, TaskStatus::Completed);
+        assert_eq!(invoice.priority, Priority::High);
+        assert_eq!(invoice.category, "desk");
+        assert_eq!(
+            invoice
+                .completed_at
+                .map(|c| c.with_timezone(&Local).date_naive()),
+            Some(date("2024-03-03"))
+        );
+
+        assert_eq!(imported.tasks[2].priority, Priority::Medium);
+        assert_eq!(imported.tasks[3].status, TaskStatus::Completed);
+
+        assert_eq!(
+            imported.warnings,
+            [
+                "Line 2: only the first project was kept; dropped work",
+                "Line 4: could not read due date '2024-13-01'",
+                "Line 4: no task text, skipped",
+            ]
+        );
+    }
+
+    #[test]
+    fn todo_txt_ids_survive_completion_and_rescheduling() {
+        let open = parse_todo_txt("(A) 2024-03-01 Call the bank +finance due:2024-03-04");
+        let done =
+            parse_todo_txt("x 2024-03-05 2024-03-01 Call the bank  +finance due:2024-03-06 pri:A");
+        assert_eq!(open.tasks[0].id, done.tasks[0].id);
+
+        let other = parse_todo_txt("Call the bank +home");
+        assert_ne!(open.tasks[0].id, other.tasks[0].id);
+    }
+}
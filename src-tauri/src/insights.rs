@@ -0,0 +1,147 @@
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+use crate::models::AIInsight;
+use crate::repository::Repositories;
+
+/// Share of words two insights of the same type must have in common to count
+/// as the same advice.
+const DUPLICATE_SIMILARITY: f32 = 0.8;
+
+/// Lower-cased words, ignoring numbers, punctuation and emoji, so "3 tasks
+/// are overdue" and "5 tasks are overdue!" compare equal.
+fn words(message: &str) -> HashSet<String> {
+    message
+        .split(|c: char| !c.is_alphabetic() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+pub fn is_near_duplicate(a: &AIInsight, b: &AIInsight) -> bool {
+    if a.insight_type != b.insight_type {
+        return false;
+    }
+
+    let (a, b) = (words(&a.message), words(&b.message));
+    if a.is_empty() && b.is_empty() {
+        return true;
+    }
+
+    let shared = a.intersection(&b).count() as f32;
+    let total = a.union(&b).count() as f32;
+    shared / total >= DUPLICATE_SIMILARITY
+}
+
+/// Saves the generated insights that do not repeat one still active, or one
+/// earlier in the same batch. Returns the insights that were saved.
+pub async fn record(
+    repos: &Repositories,
+    generated: Vec<AIInsight>,
+    now: DateTime<Utc>,
+) -> Result<Vec<AIInsight>> {
+    let mut known = repos.insights.get_active_insights(now).await?;
+    let mut saved = Vec::new();
+
+    for insight in generated {
+        if known.iter().any(|k| is_near_duplicate(k, &insight)) {
+            continue;
+        }
+
+        repos.insights.save_ai_insight(insight.clone()).await?;
+        known.push(insight.clone());
+        saved.push(insight);
+    }
+
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InsightType;
+    use chrono::Duration;
+
+    fn insight(message: &str, insight_type: InsightType) -> AIInsight {
+        AIInsight::new(message.to_string(), insight_type, 0.9)
+    }
+
+    #[tokio::test]
+    async fn record_skips_near_duplicates() {
+        let repos = Repositories::in_memory();
+        let now = Utc::now();
+
+        let first = vec![insight(
+            "3 tasks are overdue. Prioritize these to get back on track.",
+            InsightType::TaskPrioritization,
+        )];
+        assert_eq!(record(&repos, first, now).await.unwrap().len(), 1);
+
+        let saved = record(
+            &repos,
+            vec![
+                // Repeats the active insight with a different count
+                insight(
+                    "5 tasks are overdue! Prioritize these to get back on track.",
+                    InsightType::TaskPrioritization,
+                ),
+                insight(
+                    "Block your mornings for deep work.",
+                    InsightType::TimeManagement,
+                ),
+                // Repeats the previous insight in the same batch
+                insight(
+                    "Block your mornings for deep work!",
+                    InsightType::TimeManagement,
+                ),
+                // Same words, but a different kind of advice
+                insight(
+                    "3 tasks are overdue. Prioritize these to get back on track.",
+                    InsightType::Accountability,
+                ),
+            ],
+            now,
+        )
+        .await
+        .unwrap();
+
+        let messages: Vec<&str> = saved.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Block your mornings for deep work.",
+                "3 tasks are overdue. Prioritize these to get back on track."
+            ]
+        );
+        assert_eq!(
+            repos.insights.get_active_insights(now).await.unwrap().len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn record_repeats_advice_once_the_earlier_insight_expires() {
+        let repos = Repositories::in_memory();
+        let now = Utc::now();
+        let message = "Block your mornings for deep work.";
+
+        record(
+            &repos,
+            vec![insight(message, InsightType::TimeManagement)],
+            now,
+        )
+        .await
+        .unwrap();
+        let later = now + InsightType::TimeManagement.lifetime() + Duration::minutes(1);
+        let saved = record(
+            &repos,
+            vec![insight(message, InsightType::TimeManagement)],
+            later,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(saved.len(), 1);
+    }
+}
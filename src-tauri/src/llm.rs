@@ -0,0 +1,672 @@
+use async_trait::async_trait;
+use log::warn;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::env;
+use std::time::Duration;
+
+use crate::error::{AppError, Result};
+use crate::models::{AiProvider, AiRequestSettings, AiSettings};
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OPENAI_MODEL: &str = "gpt-3.5-turbo";
+const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_MODEL: &str = "claude-3-haiku-20240307";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MOCK_MODEL: &str = "mock";
+
+/// Key sent to a `base_url` other than the provider's own API, so the
+/// OpenAI or Anthropic key is never handed to a third-party server.
+const CUSTOM_API_KEY_VAR: &str = "AI_BASE_URL_API_KEY";
+
+/// Without a timeout an unreachable server would hold the AI engine, and
+/// every command waiting on it, indefinitely.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TOKENS_LIMIT: u32 = 4096;
+
+/// A single system prompt and user message; every feature asks for one
+/// completion at a time.
+pub struct CompletionRequest<'a> {
+    pub system: &'a str,
+    pub prompt: &'a str,
+    pub max_tokens: u32,
+    pub temperature: f64,
+}
+
+impl<'a> CompletionRequest<'a> {
+    pub fn new(system: &'a str, prompt: &'a str, limits: &AiRequestSettings) -> Self {
+        Self {
+            system,
+            prompt,
+            max_tokens: limits.max_tokens,
+            temperature: limits.temperature,
+        }
+    }
+}
+
+/// A chat model behind some API. Implementations return the reply text and
+/// report transport and API failures as `AppError::Ai`.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Name used in logs and error messages.
+    fn name(&self) -> &'static str;
+    fn model(&self) -> &str;
+    async fn complete(&self, request: &CompletionRequest<'_>) -> Result<String>;
+}
+
+/// Builds the provider the settings ask for, or `None` when the AI features
+/// should use their rule-based fallbacks.
+pub fn from_settings(settings: &AiSettings, client: &Client) -> Option<Box<dyn LlmProvider>> {
+    from_settings_with(settings, client, env_var)
+}
+
+/// `from_settings` with API keys read through `lookup` instead of the
+/// environment.
+fn from_settings_with(
+    settings: &AiSettings,
+    client: &Client,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<Box<dyn LlmProvider>> {
+    let base_url = settings
+        .base_url
+        .as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty());
+    let model = |default: &str| {
+        settings
+            .model
+            .as_deref()
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .unwrap_or(default)
+            .to_string()
+    };
+
+    match settings.provider {
+        AiProvider::Disabled => None,
+        AiProvider::Mock => Some(Box::new(MockProvider)),
+        AiProvider::OpenAi => {
+            let api_key = api_key(base_url, OPENAI_BASE_URL, "OPENAI_API_KEY", &lookup);
+            // Local servers such as Ollama and llama.cpp need no key
+            if api_key.is_none() && base_url.is_none() {
+                warn!("OpenAI API key not found. AI features will use fallback responses.");
+                return None;
+            }
+            Some(Box::new(OpenAiCompatible {
+                client: client.clone(),
+                base_url: base_url.unwrap_or(OPENAI_BASE_URL).to_string(),
+                api_key,
+                model: model(OPENAI_MODEL),
+            }))
+        }
+        AiProvider::Anthropic => {
+            let api_key = api_key(base_url, ANTHROPIC_BASE_URL, "ANTHROPIC_API_KEY", &lookup);
+            if api_key.is_none() && base_url.is_none() {
+                warn!("Anthropic API key not found. AI features will use fallback responses.");
+                return None;
+            }
+            Some(Box::new(Anthropic {
+                client: client.clone(),
+                base_url: base_url.unwrap_or(ANTHROPIC_BASE_URL).to_string(),
+                api_key,
+                model: model(ANTHROPIC_MODEL),
+            }))
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The provider's own key when `base_url` is unset or is the provider's API
+/// over https, otherwise the key for custom endpoints, if any.
+fn api_key(
+    base_url: Option<&str>,
+    official_url: &str,
+    official_var: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let host = |url: &str| {
+        reqwest::Url::parse(url)
+            .ok()
+            .filter(|url| url.scheme() == "https")
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+    };
+    let official = match base_url {
+        None => true,
+        Some(url) => host(url).is_some() && host(url) == host(official_url),
+    };
+
+    if official {
+        lookup(official_var)
+    } else {
+        lookup(CUSTOM_API_KEY_VAR)
+    }
+}
+
+/// Checks AI settings before they are saved. Both providers' APIs reject
+/// out-of-range values, but only when a request is made, which for the
+/// accountability check-in may be an hour later.
+pub fn validate(settings: &AiSettings) -> Result<()> {
+    if let Some(base_url) = settings.base_url.as_deref().map(str::trim) {
+        let valid = reqwest::Url::parse(base_url)
+            .map(|url| matches!(url.scheme(), "http" | "https"))
+            .unwrap_or(false);
+        if !base_url.is_empty() && !valid {
+            return Err(AppError::validation(
+                "ai.base_url",
+                format!("Base URL must be an http or https URL, got '{}'", base_url),
+            ));
+        }
+    }
+
+    // Anthropic caps temperature at 1, OpenAI at 2
+    let max_temperature = match settings.provider {
+        AiProvider::Anthropic => 1.0,
+        _ => 2.0,
+    };
+    for (field, limits) in [
+        ("ai.insights", &settings.insights),
+        ("ai.accountability", &settings.accountability),
+    ] {
+        if !(1..=MAX_TOKENS_LIMIT).contains(&limits.max_tokens) {
+            return Err(AppError::validation(
+                &format!("{}.max_tokens", field),
+                format!("max_tokens must be between 1 and {}", MAX_TOKENS_LIMIT),
+            ));
+        }
+        if !(0.0..=max_temperature).contains(&limits.temperature) {
+            return Err(AppError::validation(
+                &format!("{}.temperature", field),
+                format!("temperature must be between 0 and {}", max_temperature),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns a non-2xx response into an error carrying the body, which is where
+/// these APIs explain what went wrong.
+async fn check_status(provider: &str, response: reqwest::Response) -> Result<Value> {
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(AppError::Ai(format!(
+            "{} API error ({}): {}",
+            provider, status, error_text
+        )));
+    }
+    Ok(response.json().await?)
+}
+
+/// A successful response with no text in it, which must not pass for an
+/// empty answer.
+fn no_content(provider: &str, response: &Value) -> AppError {
+    AppError::Ai(format!("{} returned no text: {}", provider, response))
+}
+
+/// The `/chat/completions` API of OpenAI and of the servers that copy it.
+pub struct OpenAiCompatible {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &CompletionRequest<'_>) -> Result<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": request.system },
+                { "role": "user", "content": request.prompt }
+            ],
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature
+        });
+
+        let mut http = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .timeout(REQUEST_TIMEOUT)
+            .json(&body);
+        if let Some(api_key) = &self.api_key {
+            http = http.bearer_auth(api_key);
+        }
+
+        let response = check_status(self.name(), http.send().await?).await?;
+        // A refusal or a tool call comes back with a null content
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| no_content(self.name(), &response))?;
+        Ok(content.trim().to_string())
+    }
+}
+
+/// Anthropic's `/v1/messages` API.
+pub struct Anthropic {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[async_trait]
+impl LlmProvider for Anthropic {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, request: &CompletionRequest<'_>) -> Result<String> {
+        let body = json!({
+            "model": self.model,
+            "system": request.system,
+            "messages": [
+                { "role": "user", "content": request.prompt }
+            ],
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature
+        });
+
+        let mut http = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&body);
+        if let Some(api_key) = &self.api_key {
+            http = http.header("x-api-key", api_key);
+        }
+
+        let response = check_status(self.name(), http.send().await?).await?;
+
+        // The reply is a list of content blocks; only text blocks matter here
+        let texts: Vec<&str> = response["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        if texts.is_empty() {
+            return Err(no_content(self.name(), &response));
+        }
+        Ok(texts.concat().trim().to_string())
+    }
+}
+
+/// Answers without a network call, the same way for the same request, so
+/// the AI code paths can be exercised offline.
+pub struct MockProvider;
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn model(&self) -> &str {
+        MOCK_MODEL
+    }
+
+    async fn complete(&self, request: &CompletionRequest<'_>) -> Result<String> {
+        Ok(format!(
+            "Mock reply to a {}-word prompt.",
+            request.prompt.split_whitespace().count()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// A request as the test server received it: the request line, headers
+    /// with lowercase names, and the JSON body.
+    struct Received {
+        request_line: String,
+        headers: Vec<(String, String)>,
+        body: Value,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Serves one request on a local port with `status` and `reply`,
+    /// returning the base URL and the request once it has been answered.
+    async fn serve_once(status: &'static str, reply: Value) -> (String, JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            let (head, body) = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "connection closed before the request ended");
+                buf.extend_from_slice(&chunk[..n]);
+
+                let text = String::from_utf8_lossy(&buf).to_string();
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break (head.to_string(), body.to_string());
+                }
+            };
+
+            let reply = reply.to_string();
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                reply.len(),
+                reply
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            let mut lines = head.lines();
+            Received {
+                request_line: lines.next().unwrap().to_string(),
+                headers: lines
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(n, v)| (n.trim().to_ascii_lowercase(), v.trim().to_string()))
+                    .collect(),
+                body: serde_json::from_str(&body).unwrap(),
+            }
+        });
+
+        (base_url, handle)
+    }
+
+    fn request() -> CompletionRequest<'static> {
+        CompletionRequest {
+            system: "Be brief.",
+            prompt: "How is my day going?",
+            max_tokens: 50,
+            temperature: 0.5,
+        }
+    }
+
+    fn openai(base_url: String) -> OpenAiCompatible {
+        OpenAiCompatible {
+            client: Client::new(),
+            base_url,
+            api_key: Some("sk-test".to_string()),
+            model: "gpt-test".to_string(),
+        }
+    }
+
+    fn anthropic(base_url: String) -> Anthropic {
+        Anthropic {
+            client: Client::new(),
+            base_url,
+            api_key: Some("ak-test".to_string()),
+            model: "claude-test".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn openai_sends_a_chat_completion_and_reads_the_reply() {
+        let (base_url, server) = serve_once(
+            "200 OK",
+            json!({ "choices": [{ "message": { "role": "assistant", "content": "  Going well.\n" } }] }),
+        )
+        .await;
+
+        let reply = openai(base_url).complete(&request()).await.unwrap();
+        assert_eq!(reply, "Going well.");
+
+        let received = server.await.unwrap();
+        assert_eq!(received.request_line, "POST /chat/completions HTTP/1.1");
+        assert_eq!(received.header("authorization"), Some("Bearer sk-test"));
+        assert_eq!(
+            received.body,
+            json!({
+                "model": "gpt-test",
+                "messages": [
+                    { "role": "system", "content": "Be brief." },
+                    { "role": "user", "content": "How is my day going?" }
+                ],
+                "max_tokens": 50,
+                "temperature": 0.5
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn openai_reports_api_errors_with_the_body() {
+        let (base_url, server) = serve_once(
+            "401 Unauthorized",
+            json!({ "error": { "message": "Incorrect API key" } }),
+        )
+        .await;
+
+        let err = openai(base_url).complete(&request()).await.unwrap_err();
+        assert_eq!(err.code(), "ai");
+        let message = err.to_string();
+        assert!(message.contains("401"), "{}", message);
+        assert!(message.contains("Incorrect API key"), "{}", message);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn anthropic_sends_a_message_and_joins_the_text_blocks() {
+        let (base_url, server) = serve_once(
+            "200 OK",
+            json!({
+                "content": [
+                    { "type": "text", "text": "Going " },
+                    { "type": "tool_use", "id": "t1", "name": "noop", "input": {} },
+                    { "type": "text", "text": "well.\n" }
+                ]
+            }),
+        )
+        .await;
+
+        let reply = anthropic(base_url).complete(&request()).await.unwrap();
+        assert_eq!(reply, "Going well.");
+
+        let received = server.await.unwrap();
+        assert_eq!(received.request_line, "POST /v1/messages HTTP/1.1");
+        assert_eq!(received.header("x-api-key"), Some("ak-test"));
+        assert_eq!(
+            received.header("anthropic-version"),
+            Some(ANTHROPIC_VERSION)
+        );
+        assert_eq!(received.header("authorization"), None);
+        assert_eq!(
+            received.body,
+            json!({
+                "model": "claude-test",
+                "system": "Be brief.",
+                "messages": [{ "role": "user", "content": "How is my day going?" }],
+                "max_tokens": 50,
+                "temperature": 0.5
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn anthropic_reports_api_errors_with_the_body() {
+        let (base_url, server) = serve_once(
+            "529 Overloaded",
+            json!({ "type": "error", "error": { "type": "overloaded_error" } }),
+        )
+        .await;
+
+        let err = anthropic(base_url).complete(&request()).await.unwrap_err();
+        assert_eq!(err.code(), "ai");
+        let message = err.to_string();
+        assert!(message.contains("529"), "{}", message);
+        assert!(message.contains("overloaded_error"), "{}", message);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_reply_without_text_is_an_error() {
+        let (base_url, server) = serve_once(
+            "200 OK",
+            json!({ "choices": [{ "message": { "role": "assistant", "content": null } }] }),
+        )
+        .await;
+        let err = openai(base_url).complete(&request()).await.unwrap_err();
+        assert_eq!(err.code(), "ai");
+        server.await.unwrap();
+
+        let (base_url, server) = serve_once("200 OK", json!({ "choices": [] })).await;
+        let err = openai(base_url).complete(&request()).await.unwrap_err();
+        assert_eq!(err.code(), "ai");
+        server.await.unwrap();
+
+        let (base_url, server) = serve_once(
+            "200 OK",
+            json!({ "content": [{ "type": "tool_use", "id": "t1", "name": "noop", "input": {} }] }),
+        )
+        .await;
+        let err = anthropic(base_url).complete(&request()).await.unwrap_err();
+        assert_eq!(err.code(), "ai");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn from_settings_picks_the_provider_and_routes_its_key() {
+        let client = Client::new();
+        let keys = |name: &str| match name {
+            "OPENAI_API_KEY" => Some("sk-official".to_string()),
+            "ANTHROPIC_API_KEY" => Some("ak-official".to_string()),
+            CUSTOM_API_KEY_VAR => Some("custom-key".to_string()),
+            _ => None,
+        };
+        let settings = |provider, base_url: Option<&str>, model: Option<&str>| AiSettings {
+            provider,
+            base_url: base_url.map(str::to_string),
+            model: model.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert!(
+            from_settings_with(&settings(AiProvider::Disabled, None, None), &client, keys)
+                .is_none()
+        );
+        let mock =
+            from_settings_with(&settings(AiProvider::Mock, None, None), &client, keys).unwrap();
+        assert_eq!(mock.name(), "Mock");
+        let official =
+            from_settings_with(&settings(AiProvider::OpenAi, None, None), &client, keys).unwrap();
+        assert_eq!(
+            (official.name(), official.model()),
+            ("OpenAI", OPENAI_MODEL)
+        );
+        assert!(from_settings_with(
+            &settings(AiProvider::Anthropic, None, None),
+            &client,
+            |_| None
+        )
+        .is_none());
+
+        // A custom endpoint gets the custom key, never the provider's own
+        let reply = json!({ "choices": [{ "message": { "content": "Hi" } }] });
+        let (base_url, server) = serve_once("200 OK", reply).await;
+        let provider = from_settings_with(
+            &settings(
+                AiProvider::OpenAi,
+                Some(&format!("{}/", base_url)),
+                Some(" local "),
+            ),
+            &client,
+            keys,
+        )
+        .unwrap();
+        assert_eq!(provider.complete(&request()).await.unwrap(), "Hi");
+        let received = server.await.unwrap();
+        assert_eq!(received.request_line, "POST /chat/completions HTTP/1.1");
+        assert_eq!(received.header("authorization"), Some("Bearer custom-key"));
+        assert_eq!(received.body["model"], "local");
+
+        let reply = json!({ "content": [{ "type": "text", "text": "Hi" }] });
+        let (base_url, server) = serve_once("200 OK", reply).await;
+        let provider = from_settings_with(
+            &settings(AiProvider::Anthropic, Some(&base_url), None),
+            &client,
+            keys,
+        )
+        .unwrap();
+        assert_eq!(provider.complete(&request()).await.unwrap(), "Hi");
+        let received = server.await.unwrap();
+        assert_eq!(received.request_line, "POST /v1/messages HTTP/1.1");
+        assert_eq!(received.header("x-api-key"), Some("custom-key"));
+        assert_eq!(received.body["model"], ANTHROPIC_MODEL);
+    }
+
+    #[tokio::test]
+    async fn mock_replies_are_deterministic() {
+        let first = MockProvider.complete(&request()).await.unwrap();
+        let second = MockProvider.complete(&request()).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first, "Mock reply to a 5-word prompt.");
+
+        let shorter = CompletionRequest {
+            prompt: "Hello",
+            ..request()
+        };
+        assert_ne!(MockProvider.complete(&shorter).await.unwrap(), first);
+    }
+
+    #[test]
+    fn provider_keys_only_go_to_the_provider() {
+        let lookup = |name: &str| Some(format!("{} value", name));
+        let key = |base_url| api_key(base_url, OPENAI_BASE_URL, "OPENAI_API_KEY", lookup);
+
+        let official = Some("OPENAI_API_KEY value".to_string());
+        let custom = Some(format!("{} value", CUSTOM_API_KEY_VAR));
+        assert_eq!(key(None), official);
+        assert_eq!(key(Some("https://api.openai.com/v1")), official);
+        assert_eq!(key(Some("https://API.openai.com")), official);
+        assert_eq!(key(Some("http://api.openai.com/v1")), custom);
+        assert_eq!(key(Some("http://localhost:11434/v1")), custom);
+        assert_eq!(key(Some("https://api.openai.com.example.net/v1")), custom);
+
+        assert_eq!(
+            api_key(
+                Some("http://localhost:1234"),
+                OPENAI_BASE_URL,
+                "OPENAI_API_KEY",
+                |_| None
+            ),
+            None
+        );
+    }
+}
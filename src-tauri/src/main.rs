@@ -1,30 +1,65 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod achievements;
 mod ai_engine;
+mod backup;
+#[cfg(test)]
+mod benchmarks;
 mod commands;
 mod communication;
+mod crypto;
 mod database;
+mod eisenhower;
+mod error;
+mod escalation;
+mod ical;
+mod importers;
+mod insights;
+mod llm;
+mod migrations;
 mod models;
 mod notifications;
+mod repository;
+mod settings;
+mod stats;
+mod sync;
 mod system_tray;
+#[cfg(test)]
+mod test_support;
+mod transfer;
 
+use chrono::Utc;
 use log::{error, info};
 use std::sync::Arc;
 use tauri::Manager;
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
 use crate::ai_engine::AIEngine;
+use crate::backup::BackupManager;
 use crate::communication::CommunicationManager;
-use crate::database::Database;
+use crate::database::{Database, DatabaseLocation};
+use crate::models::PriorityEscalation;
 use crate::notifications::NotificationManager;
+use crate::repository::Repositories;
+use crate::settings::SettingsManager;
+use crate::sync::SyncEngine;
 // use crate::system_tray::create_system_tray;
 
 pub struct AppState {
-    pub db: Arc<Mutex<Database>>,
+    /// The SQLite database as a file: where it lives, backing it up and
+    /// restoring it, encryption at rest, integrity checks, and the id sync
+    /// tells databases apart by. None of these has a meaning for another
+    /// storage backend, so they stay off the repository traits; everything
+    /// that reads or writes app data goes through `repos`.
+    pub db: Database,
+    pub repos: Repositories,
     pub ai_engine: Arc<Mutex<AIEngine>>,
     pub communication: Arc<Mutex<CommunicationManager>>,
     pub notifications: Arc<Mutex<NotificationManager>>,
+    pub settings: Arc<Mutex<SettingsManager>>,
+    pub sync: Arc<Mutex<SyncEngine>>,
 }
 
 #[tokio::main]
@@ -37,9 +72,25 @@ async fn main() {
 
     info!("Starting ChiCanDoIt application...");
 
+    // Load user settings
+    let settings = match SettingsManager::load().await {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("Failed to load settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Initialize database
-    let db = match Database::new().await {
-        Ok(database) => Arc::new(Mutex::new(database)),
+    let location = match DatabaseLocation::resolve(settings.get()) {
+        Ok(location) => location,
+        Err(e) => {
+            error!("Failed to resolve data directory: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let db = match Database::open(location).await {
+        Ok(database) => database,
         Err(e) => {
             error!("Failed to initialize database: {}", e);
             std::process::exit(1);
@@ -47,7 +98,10 @@ async fn main() {
     };
 
     // Initialize AI engine
-    let ai_engine = Arc::new(Mutex::new(AIEngine::new()));
+    let mut ai_engine = AIEngine::new();
+    ai_engine.set_eisenhower_thresholds(settings.get().eisenhower.clone());
+    ai_engine.set_ai_settings(&settings.get().ai);
+    let ai_engine = Arc::new(Mutex::new(ai_engine));
 
     // Initialize communication manager
     let communication = Arc::new(Mutex::new(CommunicationManager::new()));
@@ -55,11 +109,23 @@ async fn main() {
     // Initialize notification manager
     let notifications = Arc::new(Mutex::new(NotificationManager::new()));
 
+    // Initialize folder sync; its state belongs to this machine
+    let sync = match settings::config_dir() {
+        Ok(dir) => Arc::new(Mutex::new(SyncEngine::new(dir.join("sync_state.json")))),
+        Err(e) => {
+            error!("Failed to resolve sync state location: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let app_state = AppState {
+        repos: Repositories::sqlite(&db),
         db,
         ai_engine,
         communication,
         notifications,
+        settings: Arc::new(Mutex::new(settings)),
+        sync,
     };
 
     // Create system tray (disabled for now)
@@ -74,13 +140,46 @@ async fn main() {
             commands::create_task,
             commands::update_task,
             commands::delete_task,
+            commands::get_achievements,
             commands::get_ai_insights,
+            commands::get_insight_history,
             commands::get_communication_status,
+            commands::get_communication_trends,
             commands::connect_service,
             commands::get_notifications,
             commands::mark_notification_read,
+            commands::query_notifications,
+            commands::mark_all_notifications_read,
+            commands::delete_notifications,
             commands::get_productivity_stats,
-            commands::trigger_accountability_check
+            commands::get_stats_report,
+            commands::trigger_accountability_check,
+            commands::get_eisenhower_matrix,
+            commands::get_settings,
+            commands::update_settings,
+            commands::test_ai_connection,
+            commands::get_priority_escalations,
+            commands::get_archived_tasks,
+            commands::search_archived_tasks,
+            commands::unarchive_task,
+            commands::archive_old_tasks,
+            commands::get_data_directory,
+            commands::move_data_directory,
+            commands::list_backups,
+            commands::create_backup,
+            commands::restore_backup,
+            commands::check_database,
+            commands::get_encryption_status,
+            commands::unlock_database,
+            commands::enable_encryption,
+            commands::change_passphrase,
+            commands::disable_encryption,
+            commands::export_data,
+            commands::import_data,
+            commands::import_tasks,
+            commands::import_legacy_tasks,
+            commands::update_sync_settings,
+            commands::sync_now
         ])
         .setup(|app| {
             let app_handle = app.handle();
@@ -94,7 +193,28 @@ async fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+/// True while an encrypted database waits for its passphrase. The jobs
+/// below skip their runs until then rather than fail each one with `Locked`;
+/// backups still run, as they copy the file without decrypting it.
+async fn database_locked(app_handle: &tauri::AppHandle) -> bool {
+    let state = app_handle.state::<AppState>();
+    let locked = state.db.encryption_status().await.locked;
+    locked
+}
+
 fn setup_periodic_tasks(app_handle: tauri::AppHandle) {
+    // An encrypted database opens locked; the frontend prompts for the
+    // passphrase on this event, or on `get_encryption_status` if it loads later
+    let app_handle_clone0 = app_handle.clone();
+    tokio::spawn(async move {
+        if database_locked(&app_handle_clone0).await {
+            if let Err(e) = app_handle_clone0.emit_all("database_locked", ()) {
+                error!("Failed to announce locked database: {}", e);
+            }
+        }
+    });
+
     let app_handle_clone = app_handle.clone();
 
     // Hourly accountability check
@@ -104,6 +224,10 @@ fn setup_periodic_tasks(app_handle: tauri::AppHandle) {
         loop {
             interval.tick().await;
 
+            if database_locked(&app_handle_clone).await {
+                continue;
+            }
+
             if let Err(e) = perform_accountability_check(&app_handle_clone).await {
                 error!("Accountability check failed: {}", e);
             }
@@ -118,12 +242,125 @@ fn setup_periodic_tasks(app_handle: tauri::AppHandle) {
         loop {
             interval.tick().await;
 
+            if database_locked(&app_handle_clone2).await {
+                continue;
+            }
+
             if let Err(e) = refresh_ai_insights(&app_handle_clone2).await {
                 error!("AI insights refresh failed: {}", e);
             }
         }
     });
 
+    // Deadline-driven priority escalation every 15 minutes
+    let app_handle_clone3 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(900)); // 15 minutes
+
+        loop {
+            interval.tick().await;
+
+            if database_locked(&app_handle_clone3).await {
+                continue;
+            }
+
+            if let Err(e) = escalate_task_priorities(&app_handle_clone3).await {
+                error!("Priority escalation failed: {}", e);
+            }
+        }
+    });
+
+    // Rotating database backups every hour
+    let app_handle_clone5 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600)); // 1 hour
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = backup_database(&app_handle_clone5).await {
+                error!("Database backup failed: {}", e);
+            }
+        }
+    });
+
+    // Archive old completed tasks once a day
+    let app_handle_clone4 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(86400)); // 24 hours
+
+        loop {
+            interval.tick().await;
+
+            if database_locked(&app_handle_clone4).await {
+                continue;
+            }
+
+            if let Err(e) = archive_old_tasks(&app_handle_clone4).await {
+                error!("Task archiving failed: {}", e);
+            }
+        }
+    });
+
+    // Achievements and streaks every hour, so day-based streaks advance
+    // without task activity
+    let app_handle_clone7 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600)); // 1 hour
+
+        loop {
+            interval.tick().await;
+
+            if database_locked(&app_handle_clone7).await {
+                continue;
+            }
+
+            if let Err(e) = achievements::check(&app_handle_clone7).await {
+                error!("Achievement check failed: {}", e);
+            }
+        }
+    });
+
+    // Prune old read notifications and communication history once a day
+    let app_handle_clone8 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(86400)); // 24 hours
+
+        loop {
+            interval.tick().await;
+
+            if database_locked(&app_handle_clone8).await {
+                continue;
+            }
+
+            if let Err(e) = prune_notifications(&app_handle_clone8).await {
+                error!("Notification pruning failed: {}", e);
+            }
+
+            if let Err(e) = prune_communication_history(&app_handle_clone8).await {
+                error!("Communication history pruning failed: {}", e);
+            }
+        }
+    });
+
+    // Folder sync with other devices every 5 minutes
+    let app_handle_clone6 = app_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // 5 minutes
+
+        loop {
+            interval.tick().await;
+
+            if database_locked(&app_handle_clone6).await {
+                continue;
+            }
+
+            if let Err(e) = sync_tasks(&app_handle_clone6).await {
+                error!("Task sync failed: {}", e);
+            }
+        }
+    });
+
     // Communication sync every 15 minutes
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(900)); // 15 minutes
@@ -131,6 +368,10 @@ fn setup_periodic_tasks(app_handle: tauri::AppHandle) {
         loop {
             interval.tick().await;
 
+            if database_locked(&app_handle).await {
+                continue;
+            }
+
             if let Err(e) = sync_communications(&app_handle).await {
                 error!("Communication sync failed: {}", e);
             }
@@ -144,14 +385,11 @@ async fn perform_accountability_check(
     let state = app_handle.state::<AppState>();
 
     // Get current tasks
-    let db = state.db.lock().await;
-    let tasks = db.get_all_tasks().await?;
-    drop(db);
+    let tasks = state.repos.tasks.get_all_tasks().await?;
 
     // Generate accountability message
-    let ai_engine = state.ai_engine.lock().await;
+    let ai_engine = AIEngine::current(&state.ai_engine).await;
     let message = ai_engine.generate_accountability_message(&tasks).await?;
-    drop(ai_engine);
 
     // Send notification
     let notifications = state.notifications.lock().await;
@@ -172,20 +410,217 @@ async fn refresh_ai_insights(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let state = app_handle.state::<AppState>();
 
-    let db = state.db.lock().await;
-    let tasks = db.get_all_tasks().await?;
-    drop(db);
+    let tasks = state.repos.tasks.get_all_tasks().await?;
 
-    let ai_engine = state.ai_engine.lock().await;
-    let insights = ai_engine.generate_insights(&tasks).await?;
-    drop(ai_engine);
+    let ai_engine = AIEngine::current(&state.ai_engine).await;
+    let generated = ai_engine.generate_insights(&tasks).await?;
 
-    app_handle.emit_all("insights_updated", &insights)?;
+    let messages: Vec<String> = generated.iter().map(|i| i.message.clone()).collect();
+    insights::record(&state.repos, generated, Utc::now()).await?;
+
+    app_handle.emit_all("insights_updated", &messages)?;
 
     info!("AI insights refreshed");
     Ok(())
 }
 
+async fn escalate_task_priorities(
+    app_handle: &tauri::AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let settings = state.settings.lock().await;
+    let escalation_settings = settings.get().escalation.clone();
+    drop(settings);
+
+    if !escalation_settings.enabled {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut escalated = Vec::new();
+
+    let repo = &state.repos.tasks;
+    for mut task in repo.get_open_tasks().await? {
+        let (to_priority, rule) = match escalation::evaluate(&task, &escalation_settings.rules, now)
+        {
+            Some(escalation) => escalation,
+            None => continue,
+        };
+
+        // A task that fails to update is logged and skipped, so the tasks
+        // already escalated in this pass still get their notifications
+        let from_priority = task.priority;
+        let title = task.title.clone();
+        task.priority = to_priority;
+        task.updated_at = now;
+        let task = match repo.update_task(task).await {
+            Ok(task) => task,
+            Err(e) => {
+                error!("Failed to escalate task '{}': {}", title, e);
+                continue;
+            }
+        };
+
+        let record = PriorityEscalation {
+            id: Uuid::new_v4().to_string(),
+            task_id: task.id.clone(),
+            task_title: task.title.clone(),
+            from_priority,
+            to_priority,
+            rule: rule.name.clone(),
+            escalated_at: now,
+        };
+        if let Err(e) = repo.save_priority_escalation(&record).await {
+            error!("Failed to record escalation of '{}': {}", task.title, e);
+        }
+
+        info!(
+            "Escalated task '{}' from {:?} to {:?} ({})",
+            task.title, from_priority, to_priority, rule.name
+        );
+        escalated.push((task, from_priority));
+    }
+
+    let notifications = state.notifications.lock().await;
+    for (task, from_priority) in &escalated {
+        if let Err(e) = notifications
+            .send_deadline_notification(task, *from_priority, app_handle)
+            .await
+        {
+            error!("Deadline notification for '{}' failed: {}", task.title, e);
+        }
+    }
+    drop(notifications);
+
+    if !escalated.is_empty() {
+        let tasks: Vec<_> = escalated.into_iter().map(|(task, _)| task).collect();
+        app_handle.emit_all("tasks_escalated", &tasks)?;
+    }
+
+    Ok(())
+}
+
+async fn archive_old_tasks(
+    app_handle: &tauri::AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let settings = state.settings.lock().await;
+    let archive_settings = settings.get().archive.clone();
+    drop(settings);
+
+    if !archive_settings.enabled {
+        return Ok(());
+    }
+
+    let cutoff = Utc::now() - chrono::Duration::days(archive_settings.archive_after_days);
+
+    let archived = state.repos.tasks.archive_tasks_older_than(cutoff).await?;
+
+    if archived > 0 {
+        app_handle.emit_all("tasks_archived", archived)?;
+    }
+
+    info!("Archived {} old tasks", archived);
+    Ok(())
+}
+
+async fn prune_notifications(
+    app_handle: &tauri::AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let settings = state.settings.lock().await;
+    let retention = settings.get().notification_retention.clone();
+    drop(settings);
+
+    if !retention.enabled {
+        return Ok(());
+    }
+
+    let cutoff = Utc::now() - chrono::Duration::days(retention.keep_read_days);
+    let pruned = state
+        .repos
+        .notifications
+        .prune_read_notifications(cutoff)
+        .await?;
+
+    if pruned > 0 {
+        app_handle.emit_all("notifications_pruned", pruned)?;
+    }
+
+    info!("Pruned {} read notifications", pruned);
+    Ok(())
+}
+
+async fn prune_communication_history(
+    app_handle: &tauri::AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let cutoff = Utc::now() - chrono::Duration::days(communication::HISTORY_DAYS);
+    let pruned = state
+        .repos
+        .communication
+        .prune_communication_snapshots(cutoff)
+        .await?;
+
+    info!("Pruned {} communication snapshots", pruned);
+    Ok(())
+}
+
+async fn backup_database(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let settings = state.settings.lock().await;
+    let backup_settings = settings.get().backup.clone();
+    drop(settings);
+
+    if !backup_settings.enabled {
+        return Ok(());
+    }
+
+    let db = &state.db;
+    let manager = match BackupManager::for_database(db) {
+        Some(manager) => manager,
+        None => return Ok(()),
+    };
+    let backup = manager.run_scheduled(db, &backup_settings).await?;
+
+    info!("Database backed up to {}", backup.path);
+    Ok(())
+}
+
+async fn sync_tasks(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app_handle.state::<AppState>();
+
+    let settings = state.settings.lock().await;
+    let sync_settings = settings.get().sync.clone();
+    drop(settings);
+
+    let directory = match (sync_settings.enabled, sync_settings.directory) {
+        (true, Some(directory)) => directory,
+        _ => return Ok(()),
+    };
+    if state.db.encryption_status().await.enabled {
+        return Ok(());
+    }
+
+    let engine = state.sync.lock().await;
+    let database_id = state.db.database_id().await?;
+    let report = engine
+        .sync(&directory, &database_id, state.repos.tasks.as_ref())
+        .await?;
+    drop(engine);
+
+    if report.applied > 0 {
+        app_handle.emit_all("tasks_synced", &report)?;
+    }
+
+    Ok(())
+}
+
 async fn sync_communications(
     app_handle: &tauri::AppHandle,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -195,6 +630,14 @@ async fn sync_communications(
     let activity = communication.sync_all().await?;
     drop(communication);
 
+    for service in &activity {
+        state
+            .repos
+            .communication
+            .save_communication_activity(service)
+            .await?;
+    }
+
     app_handle.emit_all("communication_synced", &activity)?;
 
     info!("Communications synced");
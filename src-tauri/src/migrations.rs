@@ -0,0 +1,233 @@
+use chrono::Utc;
+use log::info;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::path::Path;
+
+use crate::error::{AppError, Result};
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every schema change ever shipped, in order. Applied migrations are
+/// checksummed, so never edit one that has been released - add a new one.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("../migrations/0001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "task priority pinning and importance override",
+        sql: include_str!("../migrations/0002_task_priority_fields.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "priority escalation log",
+        sql: include_str!("../migrations/0003_priority_escalations.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "task archive",
+        sql: include_str!("../migrations/0004_task_archive.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "encryption at rest",
+        sql: include_str!("../migrations/0005_encryption.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "task projects",
+        sql: include_str!("../migrations/0006_task_project.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "achievements and streaks",
+        sql: include_str!("../migrations/0007_achievements.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "insight expiry",
+        sql: include_str!("../migrations/0008_insight_expiry.sql"),
+    },
+    Migration {
+        version: 9,
+        description: "communication activity history",
+        sql: include_str!("../migrations/0009_communication_snapshots.sql"),
+    },
+    Migration {
+        version: 10,
+        description: "query indexes",
+        sql: include_str!("../migrations/0010_query_indexes.sql"),
+    },
+    Migration {
+        version: 11,
+        description: "database id",
+        sql: include_str!("../migrations/0011_database_id.sql"),
+    },
+];
+
+impl Migration {
+    /// Hashed with LF line endings, so a checkout that converts the .sql
+    /// files to CRLF still matches databases created from an LF one.
+    pub fn checksum(&self) -> String {
+        let sql = self.sql.replace("\r\n", "\n");
+        format!("{:x}", Sha256::digest(sql.as_bytes()))
+    }
+}
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Brings the schema up to date. When `backup_dir` is given and an existing
+/// database needs upgrading, a snapshot is written there first.
+pub async fn run(pool: &SqlitePool, backup_dir: Option<&Path>) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at DATETIME NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let applied: Vec<(i64, String)> =
+        sqlx::query_as("SELECT version, checksum FROM schema_migrations ORDER BY version")
+            .fetch_all(pool)
+            .await?;
+
+    let current_version = applied.last().map(|(version, _)| *version).unwrap_or(0);
+    if current_version > latest_version() {
+        return Err(AppError::Unsupported(format!(
+            "Database schema version {} is newer than this version of ChiCanDoIt supports ({}). Please update the app.",
+            current_version,
+            latest_version()
+        )));
+    }
+
+    for (version, checksum) in &applied {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or_else(|| {
+                AppError::Unsupported(format!("Unknown migration {} in database", version))
+            })?;
+
+        if migration.checksum() != *checksum {
+            return Err(AppError::Internal(format!(
+                "Migration {} ({}) was modified after it was applied",
+                version, migration.description
+            )));
+        }
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .filter(|m| !applied.iter().any(|(version, _)| *version == m.version))
+        .collect();
+
+    if pending.is_empty() {
+        info!(
+            "Database schema is up to date (version {})",
+            current_version
+        );
+        return Ok(());
+    }
+
+    if let Some(backup_dir) = backup_dir {
+        if has_user_data(pool).await? {
+            backup_before_upgrade(pool, backup_dir, current_version).await?;
+        }
+    }
+
+    for migration in pending {
+        info!(
+            "Applying migration {}: {}",
+            migration.version, migration.description
+        );
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(migration.sql).execute(&mut *tx).await?;
+
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, description, checksum, applied_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(migration.checksum())
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+
+    info!("Database schema upgraded to version {}", latest_version());
+    Ok(())
+}
+
+async fn has_user_data(pool: &SqlitePool) -> Result<bool> {
+    let tables: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'schema_migrations'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(tables > 0)
+}
+
+async fn backup_before_upgrade(
+    pool: &SqlitePool,
+    backup_dir: &Path,
+    current_version: i64,
+) -> Result<()> {
+    tokio::fs::create_dir_all(backup_dir).await?;
+
+    let backup_path = backup_dir.join(format!(
+        "app-v{}-{}.db",
+        current_version,
+        Utc::now().format(crate::backup::TIMESTAMP_FORMAT)
+    ));
+
+    info!(
+        "Backing up database before upgrade to: {}",
+        backup_path.display()
+    );
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_ignores_line_endings() {
+        let lf = Migration {
+            version: 1,
+            description: "test",
+            sql: "CREATE TABLE a (id TEXT);\nCREATE TABLE b (id TEXT);\n",
+        };
+        let crlf = Migration {
+            sql: "CREATE TABLE a (id TEXT);\r\nCREATE TABLE b (id TEXT);\r\n",
+            ..lf
+        };
+        assert_eq!(lf.checksum(), crlf.checksum());
+    }
+}
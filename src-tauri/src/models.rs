@@ -8,8 +8,11 @@ pub struct Task {
     pub title: String,
     pub description: Option<String>,
     pub priority: Priority,
+    pub priority_pinned: bool, // opts out of automatic escalation
+    pub importance: Importance,
     pub status: TaskStatus,
     pub category: String,
+    pub project: Option<String>,
     pub estimated_time: i32,      // in minutes
     pub actual_time: Option<i32>, // in minutes
     pub due_date: Option<DateTime<Utc>>,
@@ -18,7 +21,9 @@ pub struct Task {
     pub completed_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
 #[sqlx(type_name = "priority", rename_all = "lowercase")]
 pub enum Priority {
     Low,
@@ -27,6 +32,16 @@ pub enum Priority {
     Critical,
 }
 
+/// Per-task override of the importance normally derived from `Priority`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "importance", rename_all = "lowercase")]
+pub enum Importance {
+    #[default]
+    Auto,
+    Important,
+    Unimportant,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "task_status", rename_all = "lowercase")]
 pub enum TaskStatus {
@@ -42,18 +57,27 @@ pub struct CreateTaskRequest {
     pub title: String,
     pub description: Option<String>,
     pub priority: Priority,
+    #[serde(default)]
+    pub priority_pinned: bool,
+    #[serde(default)]
+    pub importance: Importance,
     pub category: String,
+    #[serde(default)]
+    pub project: Option<String>,
     pub estimated_time: i32,
     pub due_date: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct UpdateTaskRequest {
     pub title: Option<String>,
     pub description: Option<String>,
     pub priority: Option<Priority>,
+    pub priority_pinned: Option<bool>,
+    pub importance: Option<Importance>,
     pub status: Option<TaskStatus>,
     pub category: Option<String>,
+    pub project: Option<String>,
     pub estimated_time: Option<i32>,
     pub actual_time: Option<i32>,
     pub due_date: Option<DateTime<Utc>>,
@@ -68,8 +92,11 @@ impl Task {
             title: request.title,
             description: request.description,
             priority: request.priority,
+            priority_pinned: request.priority_pinned,
+            importance: request.importance,
             status: TaskStatus::Pending,
             category: request.category,
+            project: request.project,
             estimated_time: request.estimated_time,
             actual_time: None,
             due_date: request.due_date,
@@ -89,9 +116,17 @@ impl Task {
         if let Some(priority) = request.priority {
             self.priority = priority;
         }
+        if let Some(priority_pinned) = request.priority_pinned {
+            self.priority_pinned = priority_pinned;
+        }
+        if let Some(importance) = request.importance {
+            self.importance = importance;
+        }
         if let Some(status) = request.status {
-            // Set completed_at when task is completed
-            if status == TaskStatus::Completed && self.status != TaskStatus::Completed {
+            // completed_at is only ever set on completed tasks
+            if status != TaskStatus::Completed {
+                self.completed_at = None;
+            } else if self.status != TaskStatus::Completed {
                 self.completed_at = Some(Utc::now());
             }
             self.status = status;
@@ -99,6 +134,9 @@ impl Task {
         if let Some(category) = request.category {
             self.category = category;
         }
+        if let Some(project) = request.project {
+            self.project = Some(project);
+        }
         if let Some(estimated_time) = request.estimated_time {
             self.estimated_time = estimated_time;
         }
@@ -119,6 +157,10 @@ impl Task {
         }
     }
 
+    pub fn is_open(&self) -> bool {
+        !matches!(self.status, TaskStatus::Completed | TaskStatus::Cancelled)
+    }
+
     pub fn days_until_due(&self) -> Option<i64> {
         self.due_date
             .map(|due_date| (due_date - Utc::now()).num_days())
@@ -130,16 +172,33 @@ impl PartialEq for TaskStatus {
         std::mem::discriminant(self) == std::mem::discriminant(other)
     }
 }
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct AIInsight {
     pub id: String,
     pub message: String,
     pub insight_type: InsightType,
-    pub confidence: f32,
+    pub confidence: f32, // 0.0 to 1.0
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+impl AIInsight {
+    pub fn new(message: String, insight_type: InsightType, confidence: f32) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            message,
+            expires_at: Some(now + insight_type.lifetime()),
+            insight_type,
+            confidence,
+            created_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "insight_type", rename_all = "lowercase")]
 pub enum InsightType {
     ProductivityTip,
@@ -149,6 +208,20 @@ pub enum InsightType {
     Accountability,
 }
 
+impl InsightType {
+    /// How long an insight of this type stays relevant. Advice tied to the
+    /// time of day goes stale fastest, observed patterns slowest.
+    pub fn lifetime(self) -> chrono::Duration {
+        match self {
+            InsightType::TimeManagement => chrono::Duration::hours(4),
+            InsightType::TaskPrioritization => chrono::Duration::hours(12),
+            InsightType::Accountability => chrono::Duration::days(1),
+            InsightType::ProductivityTip => chrono::Duration::days(3),
+            InsightType::PatternRecognition => chrono::Duration::days(7),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProductivityStats {
     pub total_tasks: i32,
@@ -170,9 +243,63 @@ pub struct DailyProgress {
     pub total_time: i32, // in minutes
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGranularity {
+    Day,
+    Week, // Monday to Sunday
+    Month,
+}
+
+/// Dates are inclusive and in the user's local time. The filters narrow
+/// every figure in the report, the previous period included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsQuery {
+    pub from: chrono::NaiveDate,
+    pub to: chrono::NaiveDate,
+    pub granularity: StatsGranularity,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsPoint {
+    pub start: chrono::NaiveDate,
+    pub end: chrono::NaiveDate, // inclusive
+    pub completed: i32,
+    pub created: i32,
+    pub tracked_minutes: i32, // actual time of the tasks completed
+    pub completion_rate: f32, // share of the tasks created that are now completed
+}
+
+/// `current` minus `previous`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsDelta {
+    pub completed: i32,
+    pub created: i32,
+    pub tracked_minutes: i32,
+    pub completion_rate: f32, // percentage points
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub series: Vec<StatsPoint>,
+    pub current: StatsPoint,
+    /// The same span immediately before the query: as many days, or as many
+    /// months for monthly reports.
+    pub previous: StatsPoint,
+    pub delta: StatsDelta,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommunicationActivity {
     pub service: String,
+    /// Messages picked up by this sync, not a running total. Trends sum it
+    /// across snapshots, so a service must report only what is new.
     pub message_count: i32,
     pub unread_count: i32,
     pub last_activity: Option<DateTime<Utc>>,
@@ -180,7 +307,41 @@ pub struct CommunicationActivity {
     pub keywords_detected: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+/// The state of one service as of one sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationSnapshot {
+    pub service: String,
+    pub message_count: i32, // new since the previous sync
+    pub unread_count: i32,
+    pub mentions: i32,
+    pub keywords_detected: Vec<String>,
+    pub captured_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationPoint {
+    pub service: String,
+    pub at: DateTime<Utc>, // the snapshot, or the start of the local hour
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationDailyPoint {
+    pub service: String,
+    pub date: chrono::NaiveDate, // local
+    pub value: i32,
+}
+
+/// Series are ordered by time, then service. Periods without a sync are
+/// left out rather than reported as zero.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommunicationTrends {
+    pub unread_backlog: Vec<CommunicationPoint>,
+    pub hourly_volume: Vec<CommunicationPoint>,
+    pub daily_mentions: Vec<CommunicationDailyPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct NotificationItem {
     pub id: String,
     pub title: String,
@@ -191,7 +352,7 @@ pub struct NotificationItem {
     pub action_url: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "notification_type", rename_all = "lowercase")]
 pub enum NotificationType {
     Accountability,
@@ -202,7 +363,341 @@ pub enum NotificationType {
     Insight,
 }
 
+/// A page of the notification inbox. Leave `cursor` empty for the first
+/// page and pass back `next_cursor` for the following ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationQuery {
+    #[serde(default)]
+    pub notification_type: Option<NotificationType>,
+    #[serde(default)]
+    pub is_read: Option<bool>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationPage {
+    pub items: Vec<NotificationItem>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRetentionSettings {
+    pub enabled: bool,
+    pub keep_read_days: i64,
+}
+
+impl Default for NotificationRetentionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep_read_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UnlockedAchievement {
+    pub id: String,
+    pub unlocked_at: DateTime<Utc>,
+}
+
+/// A run of consecutive local days. `last_date` is the last day counted,
+/// or the day the run was broken.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Streak {
+    pub name: String,
+    pub current: i32,
+    pub best: i32,
+    pub last_date: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementProgress {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub progress: i32, // capped at target
+    pub target: i32,
+    pub unlocked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedTask {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub task: Task,
+    pub archived_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EisenhowerQuadrant {
+    DoFirst,   // urgent and important
+    Schedule,  // important, not urgent
+    Delegate,  // urgent, not important
+    Eliminate, // neither urgent nor important
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EisenhowerThresholds {
+    pub urgent_within_hours: i64,
+    pub important_priority: Priority,
+}
+
+impl Default for EisenhowerThresholds {
+    fn default() -> Self {
+        Self {
+            urgent_within_hours: 48,
+            important_priority: Priority::High,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+pub struct EisenhowerMatrix {
+    pub do_first: Vec<Task>,
+    pub schedule: Vec<Task>,
+    pub delegate: Vec<Task>,
+    pub eliminate: Vec<Task>,
+    pub thresholds: EisenhowerThresholds,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscalationRule {
+    pub name: String,
+    pub due_within_hours: i64,
+    pub only_if_not_started: bool,
+    pub escalate_to: Priority,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscalationSettings {
+    pub enabled: bool,
+    pub rules: Vec<EscalationRule>,
+}
+
+impl Default for EscalationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                EscalationRule {
+                    name: "Due within 3 days".to_string(),
+                    due_within_hours: 72,
+                    only_if_not_started: false,
+                    escalate_to: Priority::Medium,
+                },
+                EscalationRule {
+                    name: "Due within 24h and not started".to_string(),
+                    due_within_hours: 24,
+                    only_if_not_started: true,
+                    escalate_to: Priority::High,
+                },
+                EscalationRule {
+                    name: "Due within 2h".to_string(),
+                    due_within_hours: 2,
+                    only_if_not_started: false,
+                    escalate_to: Priority::Critical,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PriorityEscalation {
+    pub id: String,
+    pub task_id: String,
+    pub task_title: String,
+    pub from_priority: Priority,
+    pub to_priority: Priority,
+    pub rule: String,
+    pub escalated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    pub enabled: bool,
+    pub archive_after_days: i64,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            archive_after_days: 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+/// What `enable_encryption` could not cover: backups taken before it was
+/// enabled still hold plaintext until they are pruned or deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnableEncryptionResult {
+    pub status: EncryptionStatus,
+    pub plaintext_backups: Vec<String>, // backup ids
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseIssueKind {
+    Integrity,   // reported by PRAGMA integrity_check
+    ForeignKey,  // reported by PRAGMA foreign_key_check
+    InvalidEnum, // a value the app has no variant for
+    CompletedAtNotCompleted,
+    NegativeActualTime,
+    InvalidJson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseIssue {
+    pub kind: DatabaseIssueKind,
+    pub table: Option<String>,
+    pub row_id: Option<String>,
+    pub column: Option<String>,
+    pub detail: String,
+    pub fixable: bool, // can be repaired without guessing at lost data
+    pub fixed: bool,
+}
+
+/// What `check_database` found, and with `fix` set, what it repaired.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseCheckReport {
+    pub issues: Vec<DatabaseIssue>,
+    pub fixed: u32,
+    pub backup_id: Option<String>, // taken before repairing
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub hourly_to_keep: usize,
+    pub daily_to_keep: usize,
+    pub weekly_to_keep: usize,
+    pub pre_migration_to_keep: usize,
+    pub pre_restore_to_keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hourly_to_keep: 24,
+            daily_to_keep: 7,
+            weekly_to_keep: 4,
+            pre_migration_to_keep: 5,
+            pre_restore_to_keep: 5,
+        }
+    }
+}
+
+/// Folder sync writes a change log per device into `directory`, which is
+/// expected to be shared by Syncthing, Dropbox or similar.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSettings {
+    pub enabled: bool,
+    pub directory: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub device_id: String,
+    pub pushed: u32,  // local changes appended to this device's log
+    pub applied: u32, // remote changes merged into the tasks table
+    pub conflicts: u32,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupTier {
+    Hourly,
+    Daily,
+    Weekly,
+    PreMigration,
+    PreRestore,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub id: String, // "<tier>/<file name>"
+    pub tier: BackupTier,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiProvider {
+    Disabled,
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi, // also any server with an OpenAI-compatible API, e.g. Ollama
+    Anthropic,
+    Mock,
+}
+
+/// Limits for one kind of completion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiRequestSettings {
+    pub max_tokens: u32,
+    pub temperature: f64,
+}
+
+/// API keys stay in the environment (`OPENAI_API_KEY`, `ANTHROPIC_API_KEY`);
+/// `base_url` points the provider at another server, such as a local model,
+/// which gets `AI_BASE_URL_API_KEY` instead, and `model` overrides the
+/// provider's default model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiSettings {
+    pub provider: AiProvider,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub insights: AiRequestSettings,
+    pub accountability: AiRequestSettings,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Self {
+            provider: AiProvider::default(),
+            base_url: None,
+            model: None,
+            insights: AiRequestSettings {
+                max_tokens: 300,
+                temperature: 0.7,
+            },
+            accountability: AiRequestSettings {
+                max_tokens: 100,
+                temperature: 0.8,
+            },
+        }
+    }
+}
+
+/// Outcome of a minimal request to the configured provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiConnectionTest {
+    pub provider: Option<String>, // None when AI is disabled or has no key
+    pub model: Option<String>,
+    pub success: bool,
+    pub latency_ms: Option<u64>,
+    pub reply: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub accountability_interval: i32, // minutes
     pub enable_notifications: bool,
@@ -211,11 +706,140 @@ pub struct AppSettings {
     pub auto_start: bool,
     pub minimize_to_tray: bool,
     pub theme: AppTheme,
+    #[serde(default)]
+    pub eisenhower: EisenhowerThresholds,
+    #[serde(default)]
+    pub escalation: EscalationSettings,
+    #[serde(default)]
+    pub archive: ArchiveSettings,
+    #[serde(default)]
+    pub data_directory: Option<std::path::PathBuf>,
+    #[serde(default)]
+    pub backup: BackupSettings,
+    #[serde(default)]
+    pub sync: SyncSettings,
+    #[serde(default)]
+    pub notification_retention: NotificationRetentionSettings,
+    #[serde(default)]
+    pub ai: AiSettings,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            accountability_interval: 60,
+            enable_notifications: true,
+            work_hours_start: chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            work_hours_end: chrono::NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            auto_start: false,
+            minimize_to_tray: true,
+            theme: AppTheme::Auto,
+            eisenhower: EisenhowerThresholds::default(),
+            escalation: EscalationSettings::default(),
+            archive: ArchiveSettings::default(),
+            data_directory: None,
+            backup: BackupSettings::default(),
+            sync: SyncSettings::default(),
+            notification_retention: NotificationRetentionSettings::default(),
+            ai: AiSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AppTheme {
     Light,
     Dark,
     Auto,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,  // tasks only
+    Ical, // tasks only, as VTODO components
+}
+
+/// How an import treats records whose id already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    Skip,
+    Overwrite,
+    KeepNewest, // by updated_at
+}
+
+/// Versioned JSON export of everything a user would want to move between
+/// machines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBundle {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub archived_tasks: Vec<ArchivedTask>,
+    #[serde(default)]
+    pub insights: Vec<AIInsight>,
+    #[serde(default)]
+    pub notifications: Vec<NotificationItem>,
+    #[serde(default)]
+    pub settings: Option<AppSettings>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportCounts {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// What an import changed, or with `dry_run` set, what it would change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub tasks: ImportCounts,
+    pub insights: ImportCounts,
+    pub notifications: ImportCounts,
+    pub settings_updated: bool,
+    #[serde(default)]
+    pub warnings: Vec<String>, // records or fields that could not be mapped
+}
+
+/// Another task manager's export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    Todoist,     // CSV or JSON export
+    Taskwarrior, // `task export`
+    TodoTxt,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::task;
+
+    #[test]
+    fn completed_at_follows_the_status() {
+        let mut task = task("t");
+        let status = |status| UpdateTaskRequest {
+            status: Some(status),
+            ..Default::default()
+        };
+
+        task.update(status(TaskStatus::Completed));
+        let completed_at = task.completed_at.expect("completion time set");
+
+        // Completing again keeps the original time
+        task.update(status(TaskStatus::Completed));
+        assert_eq!(task.completed_at, Some(completed_at));
+
+        task.update(status(TaskStatus::InProgress));
+        assert_eq!(task.completed_at, None);
+
+        task.update(status(TaskStatus::Completed));
+        task.update(status(TaskStatus::Cancelled));
+        assert_eq!(task.completed_at, None);
+    }
+}
@@ -4,7 +4,7 @@ use log::{error, info};
 use notify_rust::Notification;
 use uuid::Uuid;
 
-use crate::models::{NotificationItem, NotificationType};
+use crate::models::{NotificationItem, NotificationType, Priority, Task};
 use tauri::Manager;
 
 pub struct NotificationManager {
@@ -57,8 +57,12 @@ impl NotificationManager {
 
         // Save to database (through app state)
         let state = app_handle.state::<crate::AppState>();
-        let db = state.db.lock().await;
-        if let Err(e) = db.save_notification(&notification).await {
+        if let Err(e) = state
+            .repos
+            .notifications
+            .save_notification(&notification)
+            .await
+        {
             error!("Failed to save notification to database: {}", e);
         }
 
@@ -118,8 +122,12 @@ impl NotificationManager {
 
         // Save to database and emit to frontend
         let state = app_handle.state::<crate::AppState>();
-        let db = state.db.lock().await;
-        if let Err(e) = db.save_notification(&notification).await {
+        if let Err(e) = state
+            .repos
+            .notifications
+            .save_notification(&notification)
+            .await
+        {
             error!("Failed to save task reminder notification: {}", e);
         }
 
@@ -128,46 +136,59 @@ impl NotificationManager {
         Ok(())
     }
 
-    pub async fn send_achievement_notification(
+    pub async fn send_deadline_notification(
         &self,
-        achievement: &str,
+        task: &Task,
+        from: Priority,
         app_handle: &tauri::AppHandle,
     ) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        let message = format!("🎉 Achievement unlocked: {}", achievement);
+        let due = match task.due_date {
+            Some(due_date) if due_date <= Utc::now() => "is overdue".to_string(),
+            Some(due_date) => format!("is due {}", due_date.format("%b %d %H:%M UTC")),
+            None => "has no due date".to_string(),
+        };
+        let message = format!(
+            "⬆️ '{}' {} - priority raised from {:?} to {:?}",
+            task.title, due, from, task.priority
+        );
 
         // Create desktop notification
         let notification_result = Notification::new()
-            .summary("ChiCanDoIt - Achievement!")
+            .summary("ChiCanDoIt - Deadline Approaching")
             .body(&message)
-            .icon("achievement")
-            .timeout(notify_rust::Timeout::Milliseconds(8000))
+            .icon("task")
+            .timeout(notify_rust::Timeout::Milliseconds(7000))
             .show();
 
         match notification_result {
-            Ok(_) => info!("Achievement notification sent successfully"),
-            Err(e) => error!("Failed to send achievement notification: {}", e),
+            Ok(_) => info!("Deadline notification sent successfully"),
+            Err(e) => error!("Failed to send deadline notification: {}", e),
         }
 
         // Create notification record
         let notification = NotificationItem {
             id: Uuid::new_v4().to_string(),
-            title: "Achievement Unlocked!".to_string(),
+            title: "Priority Escalated".to_string(),
             message,
-            notification_type: NotificationType::Achievement,
+            notification_type: NotificationType::Deadline,
             is_read: false,
             created_at: Utc::now(),
-            action_url: None,
+            action_url: Some(format!("app://task/{}", task.id)),
         };
 
         // Save to database and emit to frontend
         let state = app_handle.state::<crate::AppState>();
-        let db = state.db.lock().await;
-        if let Err(e) = db.save_notification(&notification).await {
-            error!("Failed to save achievement notification: {}", e);
+        if let Err(e) = state
+            .repos
+            .notifications
+            .save_notification(&notification)
+            .await
+        {
+            error!("Failed to save deadline notification: {}", e);
         }
 
         app_handle.emit_all("notification", &notification)?;
@@ -175,6 +196,48 @@ impl NotificationManager {
         Ok(())
     }
 
+    /// The inbox entry for an unlock. It is saved together with the unlock
+    /// itself, so it cannot be lost between the two.
+    pub fn achievement_notification(achievement: &str) -> NotificationItem {
+        NotificationItem {
+            id: Uuid::new_v4().to_string(),
+            title: "Achievement Unlocked!".to_string(),
+            message: format!("🎉 Achievement unlocked: {}", achievement),
+            notification_type: NotificationType::Achievement,
+            is_read: false,
+            created_at: Utc::now(),
+            action_url: None,
+        }
+    }
+
+    /// Shows an achievement notification already saved by the unlock.
+    pub async fn send_achievement_notification(
+        &self,
+        notification: &NotificationItem,
+        app_handle: &tauri::AppHandle,
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        // Create desktop notification
+        let notification_result = Notification::new()
+            .summary("ChiCanDoIt - Achievement!")
+            .body(&notification.message)
+            .icon("achievement")
+            .timeout(notify_rust::Timeout::Milliseconds(8000))
+            .show();
+
+        match notification_result {
+            Ok(_) => info!("Achievement notification sent successfully"),
+            Err(e) => error!("Failed to send achievement notification: {}", e),
+        }
+
+        app_handle.emit_all("notification", notification)?;
+
+        Ok(())
+    }
+
     pub async fn send_communication_alert(
         &self,
         service: &str,
@@ -221,8 +284,12 @@ impl NotificationManager {
 
         // Save to database and emit to frontend
         let state = app_handle.state::<crate::AppState>();
-        let db = state.db.lock().await;
-        if let Err(e) = db.save_notification(&notification).await {
+        if let Err(e) = state
+            .repos
+            .notifications
+            .save_notification(&notification)
+            .await
+        {
             error!("Failed to save communication alert: {}", e);
         }
 
@@ -0,0 +1,380 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use crate::models::*;
+use crate::stats::TaskActivity;
+
+#[cfg(test)]
+mod memory;
+
+/// Storage for tasks, their archive, and the escalation log.
+#[async_trait]
+pub trait TaskRepository: Send + Sync {
+    async fn get_all_tasks(&self) -> Result<Vec<Task>>;
+    async fn get_task_by_id(&self, id: &str) -> Result<Option<Task>>;
+    async fn create_task(&self, task: Task) -> Result<Task>;
+    async fn update_task(&self, task: Task) -> Result<Task>;
+    async fn delete_task(&self, id: &str) -> Result<bool>;
+    async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>>;
+    async fn get_open_tasks(&self) -> Result<Vec<Task>>;
+    async fn get_overdue_tasks(&self) -> Result<Vec<Task>>;
+
+    /// Moves completed and cancelled tasks last touched before `cutoff` into
+    /// the archive, returning how many were moved.
+    async fn archive_tasks_older_than(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+
+    /// Replaces an archived task's fields, keeping it archived. Returns false
+    /// if no archived task has that id.
+    async fn update_archived_task(&self, task: Task) -> Result<bool>;
+    async fn get_archived_tasks(&self, limit: i32, offset: i32) -> Result<Vec<ArchivedTask>>;
+    async fn search_archived_tasks(&self, query: &str, limit: i32) -> Result<Vec<ArchivedTask>>;
+    async fn unarchive_task(&self, id: &str) -> Result<Option<Task>>;
+
+    async fn save_priority_escalation(&self, escalation: &PriorityEscalation) -> Result<()>;
+    async fn get_priority_escalations(
+        &self,
+        task_id: Option<&str>,
+        limit: i32,
+    ) -> Result<Vec<PriorityEscalation>>;
+
+    async fn get_productivity_stats(&self) -> Result<ProductivityStats>;
+
+    /// Tasks, archived ones included, created or completed in `since..until`.
+    async fn get_task_activity(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TaskActivity>>;
+}
+
+#[async_trait]
+pub trait NotificationRepository: Send + Sync {
+    async fn save_notification(&self, notification: &NotificationItem) -> Result<()>;
+    async fn get_notifications(&self, limit: i32) -> Result<Vec<NotificationItem>>;
+    async fn mark_notification_read(&self, id: &str) -> Result<bool>;
+    async fn get_unread_notification_count(&self) -> Result<i64>;
+
+    /// Newest first, filtered by type and read state, resuming after the
+    /// query's cursor.
+    async fn query_notifications(&self, query: &NotificationQuery) -> Result<NotificationPage>;
+
+    /// Marks every unread notification, or every one of a type, as read.
+    async fn mark_all_notifications_read(
+        &self,
+        notification_type: Option<NotificationType>,
+    ) -> Result<u64>;
+    async fn delete_notifications(&self, ids: &[String]) -> Result<u64>;
+
+    /// Deletes read notifications created before `cutoff`. Unread ones are
+    /// kept however old they are.
+    async fn prune_read_notifications(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+}
+
+/// Page size when a notification query does not set one, and the largest
+/// page a query may ask for.
+const DEFAULT_NOTIFICATION_PAGE: i32 = 20;
+const MAX_NOTIFICATION_PAGE: i32 = 200;
+
+pub(crate) fn notification_page_size(query: &NotificationQuery) -> i32 {
+    query
+        .limit
+        .unwrap_or(DEFAULT_NOTIFICATION_PAGE)
+        .clamp(1, MAX_NOTIFICATION_PAGE)
+}
+
+/// The position after `notification` in newest-first order. Callers treat it
+/// as opaque.
+pub(crate) fn notification_cursor(notification: &NotificationItem) -> String {
+    format!(
+        "{}|{}",
+        notification.created_at.to_rfc3339(),
+        notification.id
+    )
+}
+
+pub(crate) fn parse_notification_cursor(cursor: &str) -> Result<(DateTime<Utc>, String)> {
+    let (created_at, id) = cursor
+        .split_once('|')
+        .ok_or_else(|| AppError::validation("cursor", "Invalid notification cursor"))?;
+    let created_at = DateTime::parse_from_rfc3339(created_at)
+        .map_err(|_| AppError::validation("cursor", "Invalid notification cursor"))?;
+
+    Ok((created_at.with_timezone(&Utc), id.to_string()))
+}
+
+/// Trims a page fetched with one extra row down to size, deriving the cursor
+/// for the next page from whether that extra row was there.
+pub(crate) fn notification_page(
+    mut items: Vec<NotificationItem>,
+    page_size: i32,
+) -> NotificationPage {
+    let next_cursor = if items.len() > page_size as usize {
+        items.truncate(page_size as usize);
+        items.last().map(notification_cursor)
+    } else {
+        None
+    };
+
+    NotificationPage { items, next_cursor }
+}
+
+#[async_trait]
+pub trait InsightRepository: Send + Sync {
+    async fn save_ai_insight(&self, insight: AIInsight) -> Result<()>;
+    async fn get_recent_insights(&self, limit: i32) -> Result<Vec<AIInsight>>;
+
+    /// Insights without an expiry or expiring after `now`, newest first.
+    async fn get_active_insights(&self, now: DateTime<Utc>) -> Result<Vec<AIInsight>>;
+    async fn get_insight_history(
+        &self,
+        insight_type: Option<InsightType>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<AIInsight>>;
+}
+
+#[async_trait]
+pub trait AchievementRepository: Send + Sync {
+    async fn get_unlocked_achievements(&self) -> Result<Vec<UnlockedAchievement>>;
+
+    /// Records an unlock together with the notification announcing it, in
+    /// one transaction. Returns false and writes nothing if it was already
+    /// unlocked, so each achievement is announced once however many
+    /// evaluations race.
+    async fn unlock_achievement(
+        &self,
+        id: &str,
+        unlocked_at: DateTime<Utc>,
+        notification: &NotificationItem,
+    ) -> Result<bool>;
+    async fn get_streaks(&self) -> Result<Vec<Streak>>;
+    async fn save_streak(&self, streak: &Streak) -> Result<()>;
+}
+
+/// Each connected service's latest state and the snapshot history trends
+/// are drawn from.
+#[async_trait]
+pub trait CommunicationRepository: Send + Sync {
+    /// Updates the service's latest state and appends a snapshot to its
+    /// history.
+    async fn save_communication_activity(&self, activity: &CommunicationActivity) -> Result<()>;
+
+    /// The latest state of every service, most recently updated first.
+    async fn get_communication_activity(&self) -> Result<Vec<CommunicationActivity>>;
+
+    /// Snapshots taken since `since`, oldest first, optionally for one
+    /// service.
+    async fn get_communication_snapshots(
+        &self,
+        since: DateTime<Utc>,
+        service: Option<&str>,
+    ) -> Result<Vec<CommunicationSnapshot>>;
+
+    /// Deletes snapshots taken before `cutoff`. The latest state of each
+    /// service is kept regardless.
+    async fn prune_communication_snapshots(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+}
+
+/// Everything an import writes, resolved against the existing data
+/// beforehand so it can be applied all or nothing.
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    pub created_tasks: Vec<Task>,
+    pub updated_tasks: Vec<Task>,
+    pub created_archived_tasks: Vec<ArchivedTask>,
+    /// Replace the fields of tasks that are already archived. They stay in
+    /// the archive with their original `archived_at`.
+    pub updated_archived_tasks: Vec<Task>,
+    pub insights: Vec<AIInsight>,
+    pub notifications: Vec<NotificationItem>,
+}
+
+#[async_trait]
+pub trait ImportRepository: Send + Sync {
+    /// Writes the whole batch in one transaction.
+    async fn apply_import(&self, batch: ImportBatch) -> Result<()>;
+}
+
+/// The repositories commands and background jobs work against.
+#[derive(Clone)]
+pub struct Repositories {
+    pub tasks: Arc<dyn TaskRepository>,
+    pub notifications: Arc<dyn NotificationRepository>,
+    pub insights: Arc<dyn InsightRepository>,
+    pub achievements: Arc<dyn AchievementRepository>,
+    pub imports: Arc<dyn ImportRepository>,
+    pub communication: Arc<dyn CommunicationRepository>,
+}
+
+impl Repositories {
+    pub fn sqlite(db: &Database) -> Self {
+        Self {
+            tasks: Arc::new(db.clone()),
+            notifications: Arc::new(db.clone()),
+            insights: Arc::new(db.clone()),
+            achievements: Arc::new(db.clone()),
+            imports: Arc::new(db.clone()),
+            communication: Arc::new(db.clone()),
+        }
+    }
+
+    /// Repositories backed by plain collections, for exercising business
+    /// logic without SQLite.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let store = Arc::new(memory::InMemoryRepository::default());
+        Self {
+            tasks: store.clone(),
+            notifications: store.clone(),
+            insights: store.clone(),
+            achievements: store.clone(),
+            imports: store.clone(),
+            communication: store,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn notification(
+        id: &str,
+        notification_type: NotificationType,
+        is_read: bool,
+        created_at: DateTime<Utc>,
+    ) -> NotificationItem {
+        NotificationItem {
+            id: id.to_string(),
+            title: id.to_string(),
+            message: String::new(),
+            notification_type,
+            is_read,
+            created_at,
+            action_url: None,
+        }
+    }
+
+    async fn all_pages(repo: &dyn NotificationRepository, query: NotificationQuery) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut query = query;
+        loop {
+            let page = repo.query_notifications(&query).await.unwrap();
+            ids.extend(page.items.into_iter().map(|n| n.id));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return ids,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn notification_paging_is_the_same_for_both_backends() {
+        let now = Utc::now();
+        let minutes = |m: i64| now - Duration::minutes(m);
+        let db = Database::in_memory().await.unwrap();
+
+        for repos in [Repositories::in_memory(), Repositories::sqlite(&db)] {
+            let repo = repos.notifications.as_ref();
+            for n in [
+                notification("d1", NotificationType::Deadline, false, minutes(5)),
+                notification("d2", NotificationType::Deadline, true, minutes(3)),
+                notification("d3", NotificationType::Deadline, false, minutes(2)),
+                notification("d4", NotificationType::Deadline, false, minutes(2)),
+                notification("d5", NotificationType::Deadline, false, minutes(1)),
+                notification("i1", NotificationType::Insight, false, minutes(4)),
+                notification("i2", NotificationType::Insight, true, minutes(6)),
+            ] {
+                repo.save_notification(&n).await.unwrap();
+            }
+
+            // d3 and d4 share a timestamp, so the larger id comes first.
+            let deadlines = all_pages(
+                repo,
+                NotificationQuery {
+                    notification_type: Some(NotificationType::Deadline),
+                    limit: Some(2),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert_eq!(deadlines, ["d5", "d4", "d3", "d2", "d1"]);
+
+            let unread = all_pages(
+                repo,
+                NotificationQuery {
+                    is_read: Some(false),
+                    limit: Some(3),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert_eq!(unread, ["d5", "d4", "d3", "i1", "d1"]);
+
+            let err = repo
+                .query_notifications(&NotificationQuery {
+                    cursor: Some("not a cursor".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), "validation");
+        }
+    }
+
+    #[tokio::test]
+    async fn communication_history_is_the_same_for_both_backends() {
+        let activity = |service: &str, unread| CommunicationActivity {
+            service: service.to_string(),
+            message_count: 1,
+            unread_count: unread,
+            last_activity: None,
+            mentions: 0,
+            keywords_detected: vec!["urgent".to_string()],
+        };
+        let db = Database::in_memory().await.unwrap();
+
+        for repos in [Repositories::in_memory(), Repositories::sqlite(&db)] {
+            let repo = repos.communication.as_ref();
+            let start = Utc::now();
+            for (service, unread) in [("gmail", 5), ("discord", 2), ("gmail", 3)] {
+                repo.save_communication_activity(&activity(service, unread))
+                    .await
+                    .unwrap();
+            }
+
+            let latest: Vec<(String, i32)> = repo
+                .get_communication_activity()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|a| (a.service, a.unread_count))
+                .collect();
+            assert_eq!(
+                latest,
+                [("gmail".to_string(), 3), ("discord".to_string(), 2)]
+            );
+
+            let gmail = repo
+                .get_communication_snapshots(start, Some("gmail"))
+                .await
+                .unwrap();
+            let unread: Vec<i32> = gmail.iter().map(|s| s.unread_count).collect();
+            assert_eq!(unread, [5, 3]);
+            assert_eq!(gmail[0].keywords_detected, ["urgent"]);
+
+            let later = Utc::now() + Duration::minutes(1);
+            assert!(repo
+                .get_communication_snapshots(later, None)
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(repo.prune_communication_snapshots(later).await.unwrap(), 3);
+            assert_eq!(repo.get_communication_activity().await.unwrap().len(), 2);
+        }
+    }
+}
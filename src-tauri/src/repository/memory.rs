@@ -0,0 +1,576 @@
+//! The in-memory backend the unit tests run business logic against.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, Utc};
+use tokio::sync::RwLock;
+
+use super::*;
+use crate::stats;
+
+#[derive(Default)]
+struct Store {
+    tasks: Vec<Task>,
+    archived_tasks: Vec<ArchivedTask>,
+    escalations: Vec<PriorityEscalation>,
+    notifications: Vec<NotificationItem>,
+    insights: Vec<AIInsight>,
+    achievements: Vec<UnlockedAchievement>,
+    streaks: Vec<Streak>,
+    communication: Vec<CommunicationActivity>, // most recently updated first
+    snapshots: Vec<CommunicationSnapshot>,
+}
+
+/// Keeps everything in memory. Queries mirror the ordering and filtering of
+/// the SQLite implementation.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    store: RwLock<Store>,
+}
+
+fn newest_first(mut tasks: Vec<Task>) -> Vec<Task> {
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    tasks
+}
+
+fn is_overdue(task: &Task, now: DateTime<Utc>) -> bool {
+    task.status != TaskStatus::Completed && task.due_date.is_some_and(|due| due < now)
+}
+
+fn page<T>(items: Vec<T>, limit: i32, offset: i32) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+#[async_trait]
+impl TaskRepository for InMemoryRepository {
+    async fn get_all_tasks(&self) -> Result<Vec<Task>> {
+        Ok(newest_first(self.store.read().await.tasks.clone()))
+    }
+
+    async fn get_task_by_id(&self, id: &str) -> Result<Option<Task>> {
+        let store = self.store.read().await;
+        Ok(store.tasks.iter().find(|t| t.id == id).cloned())
+    }
+
+    async fn create_task(&self, task: Task) -> Result<Task> {
+        let mut store = self.store.write().await;
+        if store.tasks.iter().any(|t| t.id == task.id) {
+            return Err(AppError::Conflict(format!(
+                "Task {} already exists",
+                task.id
+            )));
+        }
+
+        store.tasks.push(task.clone());
+        Ok(task)
+    }
+
+    async fn update_task(&self, task: Task) -> Result<Task> {
+        let mut store = self.store.write().await;
+        if let Some(existing) = store.tasks.iter_mut().find(|t| t.id == task.id) {
+            *existing = task.clone();
+        }
+
+        Ok(task)
+    }
+
+    async fn delete_task(&self, id: &str) -> Result<bool> {
+        let mut store = self.store.write().await;
+        let before = store.tasks.len();
+        store.tasks.retain(|t| t.id != id);
+        Ok(store.tasks.len() < before)
+    }
+
+    async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        let store = self.store.read().await;
+        let tasks = store
+            .tasks
+            .iter()
+            .filter(|t| t.status == status)
+            .cloned()
+            .collect();
+        Ok(newest_first(tasks))
+    }
+
+    async fn get_open_tasks(&self) -> Result<Vec<Task>> {
+        let store = self.store.read().await;
+        let tasks = store
+            .tasks
+            .iter()
+            .filter(|t| t.is_open())
+            .cloned()
+            .collect();
+        Ok(newest_first(tasks))
+    }
+
+    async fn get_overdue_tasks(&self) -> Result<Vec<Task>> {
+        let now = Utc::now();
+        let store = self.store.read().await;
+        let mut tasks: Vec<Task> = store
+            .tasks
+            .iter()
+            .filter(|t| is_overdue(t, now))
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|t| t.due_date);
+        Ok(tasks)
+    }
+
+    async fn archive_tasks_older_than(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let now = Utc::now();
+        let mut store = self.store.write().await;
+
+        let (old, kept): (Vec<Task>, Vec<Task>) = std::mem::take(&mut store.tasks)
+            .into_iter()
+            .partition(|t| !t.is_open() && t.completed_at.unwrap_or(t.updated_at) < cutoff);
+
+        store.tasks = kept;
+        let archived = old.len() as u64;
+        store
+            .archived_tasks
+            .extend(old.into_iter().map(|task| ArchivedTask {
+                task,
+                archived_at: now,
+            }));
+
+        Ok(archived)
+    }
+
+    async fn get_archived_tasks(&self, limit: i32, offset: i32) -> Result<Vec<ArchivedTask>> {
+        let mut tasks = self.store.read().await.archived_tasks.clone();
+        tasks.sort_by_key(|a| std::cmp::Reverse((a.archived_at, a.task.completed_at)));
+        Ok(page(tasks, limit, offset))
+    }
+
+    async fn search_archived_tasks(&self, query: &str, limit: i32) -> Result<Vec<ArchivedTask>> {
+        let query = query.to_lowercase();
+        let mut tasks: Vec<ArchivedTask> = self
+            .store
+            .read()
+            .await
+            .archived_tasks
+            .iter()
+            .filter(|archived| {
+                let task = &archived.task;
+                task.title.to_lowercase().contains(&query)
+                    || task.category.to_lowercase().contains(&query)
+                    || task
+                        .description
+                        .as_ref()
+                        .is_some_and(|d| d.to_lowercase().contains(&query))
+            })
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|a| std::cmp::Reverse(a.archived_at));
+        Ok(page(tasks, limit, 0))
+    }
+
+    async fn update_archived_task(&self, task: Task) -> Result<bool> {
+        let mut store = self.store.write().await;
+        match store
+            .archived_tasks
+            .iter_mut()
+            .find(|a| a.task.id == task.id)
+        {
+            Some(existing) => {
+                existing.task = task;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn unarchive_task(&self, id: &str) -> Result<Option<Task>> {
+        let mut store = self.store.write().await;
+        let position = match store.archived_tasks.iter().position(|a| a.task.id == id) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        let task = store.archived_tasks.remove(position).task;
+        store.tasks.push(task.clone());
+        Ok(Some(task))
+    }
+
+    async fn save_priority_escalation(&self, escalation: &PriorityEscalation) -> Result<()> {
+        self.store
+            .write()
+            .await
+            .escalations
+            .push(escalation.clone());
+        Ok(())
+    }
+
+    async fn get_priority_escalations(
+        &self,
+        task_id: Option<&str>,
+        limit: i32,
+    ) -> Result<Vec<PriorityEscalation>> {
+        let mut escalations: Vec<PriorityEscalation> = self
+            .store
+            .read()
+            .await
+            .escalations
+            .iter()
+            .filter(|e| task_id.is_none_or(|id| e.task_id == id))
+            .cloned()
+            .collect();
+        escalations.sort_by_key(|e| std::cmp::Reverse(e.escalated_at));
+        Ok(page(escalations, limit, 0))
+    }
+
+    async fn get_productivity_stats(&self) -> Result<ProductivityStats> {
+        let now = Utc::now();
+        let store = self.store.read().await;
+
+        // Totals include archived tasks, as task_history does in SQLite
+        let history: Vec<&Task> = store
+            .tasks
+            .iter()
+            .chain(store.archived_tasks.iter().map(|a| &a.task))
+            .collect();
+
+        let total_tasks = history.len() as i32;
+        let completed: Vec<&&Task> = history
+            .iter()
+            .filter(|t| t.status == TaskStatus::Completed)
+            .collect();
+        let completed_tasks = completed.len() as i32;
+
+        let pending_tasks = store
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Pending)
+            .count() as i32;
+        let overdue_tasks = store.tasks.iter().filter(|t| is_overdue(t, now)).count() as i32;
+
+        let completion_rate = if total_tasks > 0 {
+            (completed_tasks as f32 / total_tasks as f32) * 100.0
+        } else {
+            0.0
+        };
+
+        let actual_times: Vec<i32> = completed.iter().filter_map(|t| t.actual_time).collect();
+        let average_completion_time = if actual_times.is_empty() {
+            None
+        } else {
+            Some(actual_times.iter().sum::<i32>() as f32 / actual_times.len() as f32)
+        };
+
+        let activity: Vec<TaskActivity> = history.iter().map(|t| TaskActivity::from(*t)).collect();
+
+        Ok(ProductivityStats {
+            total_tasks,
+            completed_tasks,
+            pending_tasks,
+            overdue_tasks,
+            completion_rate,
+            average_completion_time,
+            most_productive_hours: stats::most_productive_hours(&activity, &Local),
+            common_categories: stats::common_categories(&activity),
+            weekly_progress: stats::weekly_progress(&activity, now.with_timezone(&Local)),
+        })
+    }
+
+    async fn get_task_activity(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TaskActivity>> {
+        let store = self.store.read().await;
+        let within = |dt: DateTime<Utc>| since <= dt && dt < until;
+        Ok(store
+            .tasks
+            .iter()
+            .chain(store.archived_tasks.iter().map(|a| &a.task))
+            .filter(|t| within(t.created_at) || t.completed_at.is_some_and(within))
+            .map(TaskActivity::from)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl NotificationRepository for InMemoryRepository {
+    async fn save_notification(&self, notification: &NotificationItem) -> Result<()> {
+        self.store
+            .write()
+            .await
+            .notifications
+            .push(notification.clone());
+        Ok(())
+    }
+
+    async fn get_notifications(&self, limit: i32) -> Result<Vec<NotificationItem>> {
+        let mut notifications = self.store.read().await.notifications.clone();
+        notifications.sort_by_key(|n| std::cmp::Reverse(n.created_at));
+        Ok(page(notifications, limit, 0))
+    }
+
+    async fn mark_notification_read(&self, id: &str) -> Result<bool> {
+        let mut store = self.store.write().await;
+        match store.notifications.iter_mut().find(|n| n.id == id) {
+            Some(notification) => {
+                notification.is_read = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_unread_notification_count(&self) -> Result<i64> {
+        let store = self.store.read().await;
+        Ok(store.notifications.iter().filter(|n| !n.is_read).count() as i64)
+    }
+
+    async fn query_notifications(&self, query: &NotificationQuery) -> Result<NotificationPage> {
+        let after = query
+            .cursor
+            .as_deref()
+            .map(parse_notification_cursor)
+            .transpose()?;
+        let page_size = notification_page_size(query);
+
+        let mut notifications: Vec<NotificationItem> = self
+            .store
+            .read()
+            .await
+            .notifications
+            .iter()
+            .filter(|n| {
+                query
+                    .notification_type
+                    .is_none_or(|t| n.notification_type == t)
+            })
+            .filter(|n| query.is_read.is_none_or(|read| n.is_read == read))
+            .filter(|n| {
+                after
+                    .as_ref()
+                    .is_none_or(|(created_at, id)| (n.created_at, &n.id) < (*created_at, id))
+            })
+            .cloned()
+            .collect();
+        notifications.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+
+        Ok(notification_page(
+            page(notifications, page_size + 1, 0),
+            page_size,
+        ))
+    }
+
+    async fn mark_all_notifications_read(
+        &self,
+        notification_type: Option<NotificationType>,
+    ) -> Result<u64> {
+        let mut store = self.store.write().await;
+        let mut marked = 0;
+        for notification in store
+            .notifications
+            .iter_mut()
+            .filter(|n| !n.is_read && notification_type.is_none_or(|t| n.notification_type == t))
+        {
+            notification.is_read = true;
+            marked += 1;
+        }
+        Ok(marked)
+    }
+
+    async fn delete_notifications(&self, ids: &[String]) -> Result<u64> {
+        let mut store = self.store.write().await;
+        let before = store.notifications.len();
+        store.notifications.retain(|n| !ids.contains(&n.id));
+        Ok((before - store.notifications.len()) as u64)
+    }
+
+    async fn prune_read_notifications(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let mut store = self.store.write().await;
+        let before = store.notifications.len();
+        store
+            .notifications
+            .retain(|n| !(n.is_read && n.created_at < cutoff));
+        Ok((before - store.notifications.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl InsightRepository for InMemoryRepository {
+    async fn save_ai_insight(&self, insight: AIInsight) -> Result<()> {
+        self.store.write().await.insights.push(insight);
+        Ok(())
+    }
+
+    async fn get_recent_insights(&self, limit: i32) -> Result<Vec<AIInsight>> {
+        let mut insights = self.store.read().await.insights.clone();
+        insights.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        Ok(page(insights, limit, 0))
+    }
+
+    async fn get_active_insights(&self, now: DateTime<Utc>) -> Result<Vec<AIInsight>> {
+        let mut insights: Vec<AIInsight> = self
+            .store
+            .read()
+            .await
+            .insights
+            .iter()
+            .filter(|i| i.expires_at.is_none_or(|expires_at| expires_at > now))
+            .cloned()
+            .collect();
+        insights.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        Ok(insights)
+    }
+
+    async fn get_insight_history(
+        &self,
+        insight_type: Option<InsightType>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<AIInsight>> {
+        let mut insights: Vec<AIInsight> = self
+            .store
+            .read()
+            .await
+            .insights
+            .iter()
+            .filter(|i| insight_type.is_none_or(|t| i.insight_type == t))
+            .cloned()
+            .collect();
+        insights.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        Ok(page(insights, limit, offset))
+    }
+}
+
+#[async_trait]
+impl AchievementRepository for InMemoryRepository {
+    async fn get_unlocked_achievements(&self) -> Result<Vec<UnlockedAchievement>> {
+        let mut achievements = self.store.read().await.achievements.clone();
+        achievements.sort_by_key(|a| a.unlocked_at);
+        Ok(achievements)
+    }
+
+    async fn unlock_achievement(
+        &self,
+        id: &str,
+        unlocked_at: DateTime<Utc>,
+        notification: &NotificationItem,
+    ) -> Result<bool> {
+        let mut store = self.store.write().await;
+        if store.achievements.iter().any(|a| a.id == id) {
+            return Ok(false);
+        }
+
+        store.achievements.push(UnlockedAchievement {
+            id: id.to_string(),
+            unlocked_at,
+        });
+        store.notifications.push(notification.clone());
+        Ok(true)
+    }
+
+    async fn get_streaks(&self) -> Result<Vec<Streak>> {
+        Ok(self.store.read().await.streaks.clone())
+    }
+
+    async fn save_streak(&self, streak: &Streak) -> Result<()> {
+        let mut store = self.store.write().await;
+        store.streaks.retain(|s| s.name != streak.name);
+        store.streaks.push(streak.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ImportRepository for InMemoryRepository {
+    async fn apply_import(&self, batch: ImportBatch) -> Result<()> {
+        let mut store = self.store.write().await;
+
+        // Check before writing anything, like a rolled back transaction
+        let created = batch
+            .created_tasks
+            .iter()
+            .chain(batch.created_archived_tasks.iter().map(|a| &a.task));
+        for task in created {
+            let exists = store.tasks.iter().any(|t| t.id == task.id)
+                || store.archived_tasks.iter().any(|a| a.task.id == task.id);
+            if exists {
+                return Err(AppError::Conflict(format!(
+                    "Task {} already exists",
+                    task.id
+                )));
+            }
+        }
+
+        store.tasks.extend(batch.created_tasks);
+        store.archived_tasks.extend(batch.created_archived_tasks);
+        for task in batch.updated_tasks {
+            if let Some(existing) = store.tasks.iter_mut().find(|t| t.id == task.id) {
+                *existing = task;
+            }
+        }
+        for task in batch.updated_archived_tasks {
+            if let Some(existing) = store
+                .archived_tasks
+                .iter_mut()
+                .find(|a| a.task.id == task.id)
+            {
+                existing.task = task;
+            }
+        }
+        store.insights.extend(batch.insights);
+        store.notifications.extend(batch.notifications);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommunicationRepository for InMemoryRepository {
+    async fn save_communication_activity(&self, activity: &CommunicationActivity) -> Result<()> {
+        let mut store = self.store.write().await;
+
+        store
+            .communication
+            .retain(|a| a.service != activity.service);
+        store.communication.insert(0, activity.clone());
+        store.snapshots.push(CommunicationSnapshot {
+            service: activity.service.clone(),
+            message_count: activity.message_count,
+            unread_count: activity.unread_count,
+            mentions: activity.mentions,
+            keywords_detected: activity.keywords_detected.clone(),
+            captured_at: Utc::now(),
+        });
+
+        Ok(())
+    }
+
+    async fn get_communication_activity(&self) -> Result<Vec<CommunicationActivity>> {
+        Ok(self.store.read().await.communication.clone())
+    }
+
+    async fn get_communication_snapshots(
+        &self,
+        since: DateTime<Utc>,
+        service: Option<&str>,
+    ) -> Result<Vec<CommunicationSnapshot>> {
+        let mut snapshots: Vec<CommunicationSnapshot> = self
+            .store
+            .read()
+            .await
+            .snapshots
+            .iter()
+            .filter(|s| s.captured_at >= since && service.is_none_or(|name| s.service == name))
+            .cloned()
+            .collect();
+        snapshots.sort_by(|a, b| (a.captured_at, &a.service).cmp(&(b.captured_at, &b.service)));
+        Ok(snapshots)
+    }
+
+    async fn prune_communication_snapshots(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let mut store = self.store.write().await;
+        let before = store.snapshots.len();
+        store.snapshots.retain(|s| s.captured_at >= cutoff);
+        Ok((before - store.snapshots.len()) as u64)
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use crate::error::AppError;
+use crate::models::{AppSettings, ArchiveSettings, BackupSettings, NotificationRetentionSettings};
+use crate::{eisenhower, escalation, llm};
+
+pub struct SettingsManager {
+    path: PathBuf,
+    settings: AppSettings,
+}
+
+/// `ChiCanDoIt` inside the platform config directory, for state that belongs
+/// to this machine rather than to the data directory.
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
+        .join("ChiCanDoIt"))
+}
+
+/// Longest span a day-based setting may cover. Far larger values overflow
+/// chrono's date arithmetic and would panic the background jobs.
+const MAX_DAYS: i64 = 3650;
+
+/// Checks app settings before they are saved, whether edited or imported.
+pub fn validate(settings: &AppSettings) -> crate::error::Result<()> {
+    llm::validate(&settings.ai)?;
+    eisenhower::validate(&settings.eisenhower)?;
+    escalation::validate(&settings.escalation)?;
+    validate_archive(&settings.archive)?;
+    validate_retention(&settings.notification_retention)?;
+    validate_backup(&settings.backup)
+}
+
+fn check_range(
+    field: &str,
+    value: i64,
+    range: RangeInclusive<i64>,
+    unit: &str,
+) -> crate::error::Result<()> {
+    if range.contains(&value) {
+        return Ok(());
+    }
+
+    Err(AppError::validation(
+        field,
+        format!(
+            "Must be between {} and {} {}",
+            range.start(),
+            range.end(),
+            unit
+        ),
+    ))
+}
+
+fn validate_archive(archive: &ArchiveSettings) -> crate::error::Result<()> {
+    // Zero days would archive every finished task at once
+    check_range(
+        "archive.archive_after_days",
+        archive.archive_after_days,
+        1..=MAX_DAYS,
+        "days",
+    )
+}
+
+fn validate_retention(retention: &NotificationRetentionSettings) -> crate::error::Result<()> {
+    // A zero-day retention would prune a notification the moment it is read
+    check_range(
+        "notification_retention.keep_read_days",
+        retention.keep_read_days,
+        1..=MAX_DAYS,
+        "days",
+    )
+}
+
+fn validate_backup(backup: &BackupSettings) -> crate::error::Result<()> {
+    // Keeping none would delete each scheduled backup as soon as it is taken
+    for (field, keep) in [
+        ("backup.hourly_to_keep", backup.hourly_to_keep),
+        ("backup.daily_to_keep", backup.daily_to_keep),
+        ("backup.weekly_to_keep", backup.weekly_to_keep),
+        ("backup.pre_migration_to_keep", backup.pre_migration_to_keep),
+        ("backup.pre_restore_to_keep", backup.pre_restore_to_keep),
+    ] {
+        if keep == 0 {
+            return Err(AppError::validation(field, "Must keep at least one backup"));
+        }
+    }
+    Ok(())
+}
+
+/// Puts back the defaults for any section of a settings file that fails
+/// validation, such as one edited by hand, rather than running with it.
+fn repair(settings: &mut AppSettings) {
+    if let Err(e) = llm::validate(&settings.ai) {
+        warn!("Invalid AI settings, using defaults: {}", e);
+        settings.ai = Default::default();
+    }
+    if let Err(e) = eisenhower::validate(&settings.eisenhower) {
+        warn!("Invalid Eisenhower thresholds, using defaults: {}", e);
+        settings.eisenhower = Default::default();
+    }
+    if let Err(e) = escalation::validate(&settings.escalation) {
+        warn!("Invalid escalation rules, using defaults: {}", e);
+        settings.escalation = Default::default();
+    }
+    if let Err(e) = validate_archive(&settings.archive) {
+        warn!("Invalid archive settings, using defaults: {}", e);
+        settings.archive = Default::default();
+    }
+    if let Err(e) = validate_retention(&settings.notification_retention) {
+        warn!("Invalid notification retention, using defaults: {}", e);
+        settings.notification_retention = Default::default();
+    }
+    if let Err(e) = validate_backup(&settings.backup) {
+        warn!("Invalid backup settings, using defaults: {}", e);
+        settings.backup = Default::default();
+    }
+}
+
+impl SettingsManager {
+    pub async fn load() -> Result<Self> {
+        let app_dir = config_dir()?;
+
+        tokio::fs::create_dir_all(&app_dir).await?;
+
+        let path = app_dir.join("settings.json");
+
+        let mut settings = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Invalid settings file, using defaults: {}", e);
+                AppSettings::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => AppSettings::default(),
+            Err(e) => return Err(e.into()),
+        };
+        repair(&mut settings);
+
+        info!("Settings loaded from: {}", path.display());
+
+        Ok(Self { path, settings })
+    }
+
+    pub fn get(&self) -> &AppSettings {
+        &self.settings
+    }
+
+    pub async fn update(&mut self, settings: AppSettings) -> Result<()> {
+        let contents = serde_json::to_string_pretty(&settings)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, contents).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        self.settings = settings;
+        info!("Settings saved");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_bounds_the_notification_retention() {
+        let mut settings = AppSettings::default();
+        assert!(validate(&settings).is_ok());
+
+        for days in [0, -7] {
+            settings.notification_retention.keep_read_days = days;
+            let err = validate(&settings).unwrap_err();
+            assert_eq!(err.code(), "validation");
+        }
+
+        settings.notification_retention.keep_read_days = 1;
+        assert!(validate(&settings).is_ok());
+        settings.notification_retention.keep_read_days = MAX_DAYS;
+        assert!(validate(&settings).is_ok());
+
+        for days in [MAX_DAYS + 1, i64::MAX] {
+            settings.notification_retention.keep_read_days = days;
+            assert_eq!(validate(&settings).unwrap_err().code(), "validation");
+        }
+        settings.notification_retention.keep_read_days = 30;
+
+        settings.ai.insights.max_tokens = 0;
+        assert_eq!(validate(&settings).unwrap_err().code(), "validation");
+    }
+
+    #[test]
+    fn validate_bounds_the_archive_age() {
+        let mut settings = AppSettings::default();
+        for (days, valid) in [
+            (0, false),
+            (-1, false),
+            (1, true),
+            (3650, true),
+            (3651, false),
+        ] {
+            settings.archive.archive_after_days = days;
+            assert_eq!(validate(&settings).is_ok(), valid, "{} days", days);
+        }
+
+        settings.archive.archive_after_days = i64::MAX;
+        let err = validate(&settings).unwrap_err();
+        assert!(
+            matches!(err, AppError::Validation { field: Some(ref f), .. } if f == "archive.archive_after_days")
+        );
+    }
+
+    #[test]
+    fn validate_requires_keeping_a_backup_of_each_tier() {
+        let mut settings = AppSettings::default();
+        assert!(validate(&settings).is_ok());
+
+        settings.backup.weekly_to_keep = 0;
+        let err = validate(&settings).unwrap_err();
+        assert!(
+            matches!(err, AppError::Validation { field: Some(ref f), .. } if f == "backup.weekly_to_keep")
+        );
+
+        settings.backup.weekly_to_keep = 1;
+        assert!(validate(&settings).is_ok());
+
+        settings.backup.pre_restore_to_keep = 0;
+        repair(&mut settings);
+        assert_eq!(
+            settings.backup.pre_restore_to_keep,
+            BackupSettings::default().pre_restore_to_keep
+        );
+    }
+
+    #[test]
+    fn repair_resets_only_invalid_sections() {
+        let mut settings = AppSettings::default();
+        settings.ai.model = Some("custom-model".to_string());
+        settings.ai.accountability.temperature = 5.0;
+        settings.notification_retention.enabled = false;
+        settings.notification_retention.keep_read_days = 0;
+        settings.archive.archive_after_days = 7;
+        settings.backup.daily_to_keep = 3;
+
+        repair(&mut settings);
+
+        assert!(validate(&settings).is_ok());
+        assert_eq!(settings.ai.model, None);
+        assert!(settings.notification_retention.enabled);
+        assert_eq!(settings.archive.archive_after_days, 7);
+        assert_eq!(settings.backup.daily_to_keep, 3);
+
+        settings.archive.archive_after_days = 0;
+        repair(&mut settings);
+        assert_eq!(settings.archive.archive_after_days, 90);
+    }
+}
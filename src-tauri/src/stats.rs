@@ -0,0 +1,680 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+/// How many entries `most_productive_hours` and `common_categories` return.
+const TOP_HOURS: usize = 3;
+pub const TOP_CATEGORIES: usize = 5;
+
+/// Days covered by `weekly_progress`, today included.
+const PROGRESS_DAYS: i64 = 7;
+
+/// Upper bound on the points in a report's series.
+const MAX_POINTS: usize = 1000;
+
+/// The parts of a task the time-based statistics look at.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TaskActivity {
+    pub category: String,
+    pub project: Option<String>,
+    pub priority: Priority,
+    pub status: TaskStatus,
+    pub due_date: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub actual_time: Option<i32>,
+}
+
+impl From<&Task> for TaskActivity {
+    fn from(task: &Task) -> Self {
+        Self {
+            category: task.category.clone(),
+            project: task.project.clone(),
+            priority: task.priority,
+            status: task.status.clone(),
+            due_date: task.due_date,
+            created_at: task.created_at,
+            completed_at: task.completed_at,
+            actual_time: task.actual_time,
+        }
+    }
+}
+
+/// Hours of the day (0-23, in `tz`) in which the most tasks were completed,
+/// busiest first. Ties go to the earlier hour. The database groups by hour
+/// in SQL; this is the definition that query is checked against.
+#[cfg(test)]
+pub fn most_productive_hours<Tz: TimeZone>(tasks: &[TaskActivity], tz: &Tz) -> Vec<i32> {
+    use chrono::Timelike;
+
+    let mut completions = [0usize; 24];
+    for completed_at in tasks.iter().filter_map(|t| t.completed_at) {
+        completions[completed_at.with_timezone(tz).hour() as usize] += 1;
+    }
+    top_hours(&completions)
+}
+
+/// `most_productive_hours` from completion counts already grouped by local
+/// hour of the day.
+pub fn most_productive_hours_grouped(counts: &[(i64, i64)]) -> Vec<i32> {
+    let mut completions = [0usize; 24];
+    for &(hour, count) in counts {
+        if let Some(slot) = completions.get_mut(hour as usize) {
+            *slot += count as usize;
+        }
+    }
+    top_hours(&completions)
+}
+
+fn top_hours(completions: &[usize; 24]) -> Vec<i32> {
+    let mut hours: Vec<(usize, usize)> = completions
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(hour, &count)| (hour, count))
+        .collect();
+    hours.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    hours
+        .into_iter()
+        .take(TOP_HOURS)
+        .map(|(hour, _)| hour as i32)
+        .collect()
+}
+
+/// Categories with the most tasks, most used first. Ties are alphabetical.
+/// Like `most_productive_hours`, only the in-memory backend uses it.
+#[cfg(test)]
+pub fn common_categories(tasks: &[TaskActivity]) -> Vec<String> {
+    let counts: Vec<(String, i64)> = tasks.iter().map(|t| (t.category.clone(), 1)).collect();
+    common_categories_grouped(&counts)
+}
+
+/// `common_categories` from task counts already grouped by category. A
+/// category may appear more than once; its counts are added up.
+pub fn common_categories_grouped(counts: &[(String, i64)]) -> Vec<String> {
+    use std::collections::HashMap;
+
+    let mut totals: HashMap<&str, i64> = HashMap::new();
+    for (category, count) in counts {
+        *totals.entry(category.as_str()).or_default() += count;
+    }
+
+    let mut categories: Vec<(&str, i64)> = totals.into_iter().collect();
+    categories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    categories
+        .into_iter()
+        .take(TOP_CATEGORIES)
+        .map(|(category, _)| category.to_string())
+        .collect()
+}
+
+/// One entry per day for the week ending on `now`'s date, oldest first, with
+/// days in `now`'s time zone. Days without activity are included as zeros.
+/// `total_time` sums the actual time of tasks completed that day.
+pub fn weekly_progress<Tz: TimeZone>(
+    tasks: &[TaskActivity],
+    now: DateTime<Tz>,
+) -> Vec<DailyProgress> {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let first_day = today - Duration::days(PROGRESS_DAYS - 1);
+
+    let mut days: Vec<DailyProgress> = (0..PROGRESS_DAYS)
+        .map(|offset| DailyProgress {
+            date: first_day + Duration::days(offset),
+            completed: 0,
+            created: 0,
+            total_time: 0,
+        })
+        .collect();
+
+    let index = |date: NaiveDate| {
+        let offset = (date - first_day).num_days();
+        (0..PROGRESS_DAYS)
+            .contains(&offset)
+            .then_some(offset as usize)
+    };
+
+    for task in tasks {
+        if let Some(i) = index(task.created_at.with_timezone(&tz).date_naive()) {
+            days[i].created += 1;
+        }
+        if let Some(completed_at) = task.completed_at {
+            if let Some(i) = index(completed_at.with_timezone(&tz).date_naive()) {
+                days[i].completed += 1;
+                days[i].total_time += task.actual_time.unwrap_or(0);
+            }
+        }
+    }
+
+    days
+}
+
+/// UTC bounds of the days `weekly_progress` covers.
+pub fn progress_window<Tz: TimeZone>(now: &DateTime<Tz>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    (
+        start_of_day(today - Duration::days(PROGRESS_DAYS - 1), &tz),
+        start_of_day(today + Duration::days(1), &tz),
+    )
+}
+
+/// UTC instant at which `date` starts in `tz`.
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+fn validate(query: &StatsQuery) -> Result<()> {
+    if query.from > query.to {
+        return Err(AppError::validation(
+            "from",
+            "The start date is after the end date",
+        ));
+    }
+    if periods(query.from, query.to, query.granularity).len() > MAX_POINTS {
+        return Err(AppError::validation(
+            "granularity",
+            format!(
+                "The range has more than {} points; use a coarser granularity",
+                MAX_POINTS
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn months_spanned(from: NaiveDate, to: NaiveDate) -> u32 {
+    ((to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32 + 1) as u32
+}
+
+fn previous_range(query: &StatsQuery) -> (NaiveDate, NaiveDate) {
+    if query.granularity == StatsGranularity::Month {
+        let months = Months::new(months_spanned(query.from, query.to));
+        if let (Some(from), Some(to)) = (
+            query.from.checked_sub_months(months),
+            query.to.checked_sub_months(months),
+        ) {
+            return (from, to);
+        }
+    }
+
+    let days = (query.to - query.from).num_days() + 1;
+    (
+        query.from - Duration::days(days),
+        query.to - Duration::days(days),
+    )
+}
+
+/// Splits `from..=to` into calendar days, weeks or months, clipping the first
+/// and last period to the range.
+fn periods(
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: StatsGranularity,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut periods = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = match granularity {
+            StatsGranularity::Day => start,
+            StatsGranularity::Week => {
+                start + Duration::days(6 - start.weekday().num_days_from_monday() as i64)
+            }
+            StatsGranularity::Month => {
+                let first = start.with_day(1).unwrap_or(start);
+                first
+                    .checked_add_months(Months::new(1))
+                    .map(|next| next - Duration::days(1))
+                    .unwrap_or(to)
+            }
+        }
+        .min(to);
+
+        periods.push((start, end));
+        if periods.len() > MAX_POINTS {
+            break;
+        }
+        start = end + Duration::days(1);
+    }
+    periods
+}
+
+/// UTC bounds that cover a query and its previous period, for fetching the
+/// activity `report` needs.
+pub fn activity_window<Tz: TimeZone>(
+    query: &StatsQuery,
+    tz: &Tz,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    validate(query)?;
+    let (previous_from, _) = previous_range(query);
+    Ok((
+        start_of_day(previous_from, tz),
+        start_of_day(query.to + Duration::days(1), tz),
+    ))
+}
+
+struct LocalActivity<'a> {
+    task: &'a TaskActivity,
+    created: NaiveDate,
+    completed: Option<NaiveDate>,
+}
+
+fn matches(task: &TaskActivity, query: &StatsQuery) -> bool {
+    query
+        .category
+        .as_ref()
+        .is_none_or(|c| task.category.eq_ignore_ascii_case(c))
+        && query
+            .project
+            .as_ref()
+            .is_none_or(|p| task.project.as_ref() == Some(p))
+        && query.priority.is_none_or(|p| task.priority == p)
+}
+
+fn point(tasks: &[LocalActivity], start: NaiveDate, end: NaiveDate) -> StatsPoint {
+    let mut point = StatsPoint {
+        start,
+        end,
+        ..Default::default()
+    };
+    let mut created_completed = 0;
+
+    for activity in tasks {
+        if (start..=end).contains(&activity.created) {
+            point.created += 1;
+            if activity.task.status == TaskStatus::Completed {
+                created_completed += 1;
+            }
+        }
+        if activity
+            .completed
+            .is_some_and(|date| (start..=end).contains(&date))
+        {
+            point.completed += 1;
+            point.tracked_minutes += activity.task.actual_time.unwrap_or(0);
+        }
+    }
+
+    if point.created > 0 {
+        point.completion_rate = created_completed as f32 / point.created as f32 * 100.0;
+    }
+    point
+}
+
+/// Builds the series, totals and period-over-period change for `query`,
+/// with days in `tz`. `tasks` should cover `activity_window`.
+pub fn report<Tz: TimeZone>(
+    tasks: &[TaskActivity],
+    query: &StatsQuery,
+    tz: &Tz,
+) -> Result<StatsReport> {
+    validate(query)?;
+
+    let tasks: Vec<LocalActivity> = tasks
+        .iter()
+        .filter(|task| matches(task, query))
+        .map(|task| LocalActivity {
+            task,
+            created: task.created_at.with_timezone(tz).date_naive(),
+            completed: task
+                .completed_at
+                .map(|dt| dt.with_timezone(tz).date_naive()),
+        })
+        .collect();
+
+    let series = periods(query.from, query.to, query.granularity)
+        .into_iter()
+        .map(|(start, end)| point(&tasks, start, end))
+        .collect();
+    let current = point(&tasks, query.from, query.to);
+    let (previous_from, previous_to) = previous_range(query);
+    let previous = point(&tasks, previous_from, previous_to);
+
+    let delta = StatsDelta {
+        completed: current.completed - previous.completed,
+        created: current.created - previous.created,
+        tracked_minutes: current.tracked_minutes - previous.tracked_minutes,
+        completion_rate: current.completion_rate - previous.completion_rate,
+    };
+
+    Ok(StatsReport {
+        series,
+        current,
+        previous,
+        delta,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn tz() -> FixedOffset {
+        FixedOffset::east_opt(2 * 3600).unwrap()
+    }
+
+    /// `hour:minute` on 2024-03-`day` in `tz()`.
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        tz().with_ymd_and_hms(2024, 3, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn activity(
+        category: &str,
+        created_at: DateTime<Utc>,
+        completed_at: Option<DateTime<Utc>>,
+        actual_time: Option<i32>,
+    ) -> TaskActivity {
+        TaskActivity {
+            category: category.to_string(),
+            project: None,
+            priority: Priority::Medium,
+            status: match completed_at {
+                Some(_) => TaskStatus::Completed,
+                None => TaskStatus::Pending,
+            },
+            due_date: None,
+            created_at,
+            completed_at,
+            actual_time,
+        }
+    }
+
+    fn completed_at(at: DateTime<Utc>) -> TaskActivity {
+        activity("work", at, Some(at), None)
+    }
+
+    #[test]
+    fn productive_hours_are_local_and_ties_go_to_the_earlier_hour() {
+        let tasks = [
+            completed_at(local(4, 9, 10)),
+            completed_at(local(5, 9, 50)),
+            completed_at(local(4, 14, 0)),
+            completed_at(local(6, 14, 30)),
+            completed_at(local(6, 22, 0)),
+            completed_at(local(6, 23, 59)),
+            // 23:30 UTC, so 01:30 the next day here
+            completed_at(Utc.with_ymd_and_hms(2024, 3, 6, 23, 30, 0).unwrap()),
+            activity("work", local(6, 8, 0), None, None),
+        ];
+
+        assert_eq!(most_productive_hours(&tasks, &tz()), [9, 14, 1]);
+        assert_eq!(most_productive_hours(&tasks, &Utc), [7, 12, 20]);
+        assert!(most_productive_hours(&[], &tz()).is_empty());
+    }
+
+    #[test]
+    fn common_categories_are_counted_then_alphabetical() {
+        let tasks: Vec<TaskActivity> = [
+            "work", "home", "errands", "work", "study", "home", "admin", "health", "work",
+            "errands", "home",
+        ]
+        .iter()
+        .map(|category| activity(category, local(4, 9, 0), None, None))
+        .collect();
+
+        assert_eq!(
+            common_categories(&tasks),
+            ["home", "work", "errands", "admin", "health"]
+        );
+        assert!(common_categories(&[]).is_empty());
+    }
+
+    fn summary(days: &[DailyProgress]) -> Vec<(u32, i32, i32, i32)> {
+        days.iter()
+            .map(|d| (d.date.day(), d.created, d.completed, d.total_time))
+            .collect()
+    }
+
+    #[test]
+    fn weekly_progress_buckets_by_local_day() {
+        let now = tz().with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let tasks = [
+            activity("work", local(4, 8, 0), Some(local(5, 10, 0)), Some(30)),
+            // Created and completed just after local midnight, which is still
+            // the previous day in UTC
+            activity(
+                "work",
+                Utc.with_ymd_and_hms(2024, 3, 6, 23, 30, 0).unwrap(),
+                Some(Utc.with_ymd_and_hms(2024, 3, 9, 22, 30, 0).unwrap()),
+                Some(45),
+            ),
+            // Created before the week
+            activity("work", local(3, 8, 0), Some(local(4, 18, 0)), None),
+            activity("work", local(10, 11, 0), None, None),
+        ];
+
+        assert_eq!(
+            summary(&weekly_progress(&tasks, now)),
+            [
+                (4, 1, 1, 0),
+                (5, 0, 1, 30),
+                (6, 0, 0, 0),
+                (7, 1, 0, 0),
+                (8, 0, 0, 0),
+                (9, 0, 0, 0),
+                (10, 1, 1, 45),
+            ]
+        );
+        assert_eq!(
+            progress_window(&now),
+            (local(4, 0, 0), local(10, 0, 0) + Duration::days(1))
+        );
+    }
+
+    #[test]
+    fn weekly_progress_includes_days_without_activity() {
+        let now = tz().with_ymd_and_hms(2024, 3, 10, 0, 30, 0).unwrap();
+        assert_eq!(
+            summary(&weekly_progress(&[], now)),
+            (4..=10).map(|day| (day, 0, 0, 0)).collect::<Vec<_>>()
+        );
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn query(from: NaiveDate, to: NaiveDate, granularity: StatsGranularity) -> StatsQuery {
+        StatsQuery {
+            from,
+            to,
+            granularity,
+            category: None,
+            project: None,
+            priority: None,
+        }
+    }
+
+    fn validation_field(query: &StatsQuery) -> Option<String> {
+        match report(&[], query, &tz()).unwrap_err() {
+            AppError::Validation { field, .. } => field,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_reversed_and_oversized_ranges() {
+        let reversed = query(date(3, 10), date(3, 9), StatsGranularity::Day);
+        assert_eq!(validation_field(&reversed).as_deref(), Some("from"));
+        assert!(activity_window(&reversed, &tz()).is_err());
+
+        let years = query(
+            date(1, 1),
+            date(1, 1) + Duration::days(1500),
+            StatsGranularity::Day,
+        );
+        assert_eq!(validation_field(&years).as_deref(), Some("granularity"));
+        let weekly = StatsQuery {
+            granularity: StatsGranularity::Week,
+            ..years
+        };
+        assert!(report(&[], &weekly, &tz()).is_ok());
+
+        let one_day = query(date(3, 10), date(3, 10), StatsGranularity::Day);
+        assert_eq!(report(&[], &one_day, &tz()).unwrap().series.len(), 1);
+    }
+
+    fn bounds(query: &StatsQuery) -> Vec<(NaiveDate, NaiveDate)> {
+        report(&[], query, &tz())
+            .unwrap()
+            .series
+            .iter()
+            .map(|p| (p.start, p.end))
+            .collect()
+    }
+
+    #[test]
+    fn buckets_are_calendar_days_weeks_and_months_clipped_to_the_range() {
+        assert_eq!(
+            bounds(&query(date(3, 8), date(3, 10), StatsGranularity::Day)),
+            [
+                (date(3, 8), date(3, 8)),
+                (date(3, 9), date(3, 9)),
+                (date(3, 10), date(3, 10)),
+            ]
+        );
+        // Weeks start on Monday; 2024-03-06 is a Wednesday
+        assert_eq!(
+            bounds(&query(date(3, 6), date(3, 19), StatsGranularity::Week)),
+            [
+                (date(3, 6), date(3, 10)),
+                (date(3, 11), date(3, 17)),
+                (date(3, 18), date(3, 19)),
+            ]
+        );
+        assert_eq!(
+            bounds(&query(date(1, 15), date(3, 10), StatsGranularity::Month)),
+            [
+                (date(1, 15), date(1, 31)),
+                (date(2, 1), date(2, 29)),
+                (date(3, 1), date(3, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_previous_period_has_as_many_days_or_months() {
+        let days = report(
+            &[],
+            &query(date(3, 8), date(3, 10), StatsGranularity::Day),
+            &tz(),
+        )
+        .unwrap();
+        assert_eq!(
+            (days.previous.start, days.previous.end),
+            (date(3, 5), date(3, 7))
+        );
+
+        let month = report(
+            &[],
+            &query(date(3, 1), date(3, 31), StatsGranularity::Month),
+            &tz(),
+        )
+        .unwrap();
+        assert_eq!(
+            (month.previous.start, month.previous.end),
+            (date(2, 1), date(2, 29))
+        );
+
+        let query = query(date(3, 8), date(3, 10), StatsGranularity::Day);
+        assert_eq!(
+            activity_window(&query, &tz()).unwrap(),
+            (local(5, 0, 0), local(11, 0, 0))
+        );
+    }
+
+    fn sample() -> Vec<TaskActivity> {
+        let mut planned = activity("work", local(8, 9, 0), Some(local(9, 10, 0)), Some(20));
+        planned.priority = Priority::High;
+        planned.project = Some("Alpha".to_string());
+        vec![
+            planned,
+            // 01:30 on the 9th here, still the 8th in UTC
+            activity(
+                "Work",
+                Utc.with_ymd_and_hms(2024, 3, 8, 23, 30, 0).unwrap(),
+                None,
+                None,
+            ),
+            activity("home", local(10, 8, 0), Some(local(10, 9, 0)), Some(15)),
+            // In the previous period
+            activity("work", local(6, 8, 0), Some(local(6, 12, 0)), Some(50)),
+        ]
+    }
+
+    fn counts(point: &StatsPoint) -> (i32, i32, i32, i32) {
+        (
+            point.created,
+            point.completed,
+            point.tracked_minutes,
+            point.completion_rate.round() as i32,
+        )
+    }
+
+    #[test]
+    fn reports_series_totals_and_deltas() {
+        let report = report(
+            &sample(),
+            &query(date(3, 8), date(3, 10), StatsGranularity::Day),
+            &tz(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.series.iter().map(counts).collect::<Vec<_>>(),
+            [(1, 0, 0, 100), (1, 1, 20, 0), (1, 1, 15, 100)]
+        );
+        assert_eq!(counts(&report.current), (3, 2, 35, 67));
+        assert_eq!(counts(&report.previous), (1, 1, 50, 100));
+
+        let delta = &report.delta;
+        assert_eq!(
+            (delta.created, delta.completed, delta.tracked_minutes),
+            (2, 1, -15)
+        );
+        assert_eq!(delta.completion_rate.round() as i32, -33);
+    }
+
+    #[test]
+    fn filters_apply_to_both_periods() {
+        let base = query(date(3, 8), date(3, 10), StatsGranularity::Day);
+        let filtered = |query: StatsQuery| {
+            let report = report(&sample(), &query, &tz()).unwrap();
+            (counts(&report.current), counts(&report.previous))
+        };
+
+        // Categories match regardless of case
+        assert_eq!(
+            filtered(StatsQuery {
+                category: Some("WORK".to_string()),
+                ..base.clone()
+            }),
+            ((2, 1, 20, 50), (1, 1, 50, 100))
+        );
+        assert_eq!(
+            filtered(StatsQuery {
+                project: Some("Alpha".to_string()),
+                ..base.clone()
+            }),
+            ((1, 1, 20, 100), (0, 0, 0, 0))
+        );
+        assert_eq!(
+            filtered(StatsQuery {
+                priority: Some(Priority::High),
+                ..base
+            }),
+            ((1, 1, 20, 100), (0, 0, 0, 0))
+        );
+    }
+}
@@ -0,0 +1,686 @@
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::models::{SyncReport, Task};
+use crate::repository::TaskRepository;
+
+const LOG_EXTENSION: &str = "jsonl";
+
+/// Changes seen from each device, keyed by device id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct VectorClock(BTreeMap<String, u64>);
+
+impl VectorClock {
+    fn get(&self, device: &str) -> u64 {
+        self.0.get(device).copied().unwrap_or(0)
+    }
+
+    fn increment(&mut self, device: &str) -> u64 {
+        let counter = self.0.entry(device.to_string()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    fn merge(&mut self, other: &VectorClock) {
+        for (device, &counter) in &other.0 {
+            let entry = self.0.entry(device.clone()).or_insert(0);
+            *entry = (*entry).max(counter);
+        }
+    }
+
+    /// True when every change in `self` is also in `other`.
+    fn happened_before_or_equal(&self, other: &VectorClock) -> bool {
+        self.0
+            .iter()
+            .all(|(device, &counter)| counter <= other.get(device))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Change {
+    Upsert { task: Box<Task> },
+    Delete { id: String },
+}
+
+impl Change {
+    fn task_id(&self) -> &str {
+        match self {
+            Change::Upsert { task } => &task.id,
+            Change::Delete { id } => id,
+        }
+    }
+}
+
+/// One line of a device's log. `clock` includes the change itself, so
+/// `clock[device]` is its sequence number within the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LogEntry {
+    device: String,
+    clock: VectorClock,
+    at: DateTime<Utc>, // the task's updated_at, or when the delete was seen
+    #[serde(flatten)]
+    change: Change,
+}
+
+impl LogEntry {
+    fn seq(&self) -> u64 {
+        self.clock.get(&self.device)
+    }
+
+    fn wins_over(&self, current: &Version) -> Resolution {
+        if self.clock.happened_before_or_equal(&current.clock) {
+            Resolution::Stale
+        } else if current.clock.happened_before_or_equal(&self.clock) {
+            Resolution::Apply
+        } else if (self.at, self.device.as_str()) > (current.at, current.device.as_str()) {
+            // Concurrent edits: the later one wins, ties broken by device id,
+            // so every device settles on the same version
+            Resolution::ConflictWon
+        } else {
+            Resolution::ConflictLost
+        }
+    }
+}
+
+enum Resolution {
+    Apply,
+    Stale,
+    ConflictWon,
+    ConflictLost,
+}
+
+/// The change currently reflected in the tasks table for one task id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Version {
+    clock: VectorClock,
+    device: String,
+    at: DateTime<Utc>,
+    digest: Option<String>, // None once deleted or archived
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncState {
+    device_id: String,
+    /// The database `versions` describes. Missing in state files written
+    /// before databases had ids.
+    #[serde(default)]
+    database_id: Option<String>,
+    clock: VectorClock,
+    /// Highest sequence number merged from each other device's log.
+    applied: BTreeMap<String, u64>,
+    versions: BTreeMap<String, Version>,
+}
+
+impl SyncState {
+    fn new() -> Self {
+        Self {
+            device_id: Uuid::new_v4().to_string(),
+            database_id: None,
+            clock: VectorClock::default(),
+            applied: BTreeMap::new(),
+            versions: BTreeMap::new(),
+        }
+    }
+
+    fn record(&mut self, change: Change, at: DateTime<Utc>, digest: Option<String>) -> LogEntry {
+        self.clock.increment(&self.device_id);
+        self.versions.insert(
+            change.task_id().to_string(),
+            Version {
+                clock: self.clock.clone(),
+                device: self.device_id.clone(),
+                at,
+                digest,
+            },
+        );
+
+        LogEntry {
+            device: self.device_id.clone(),
+            clock: self.clock.clone(),
+            at,
+            change,
+        }
+    }
+}
+
+/// Keeps the tasks table of several devices in step through a shared
+/// folder. Each device appends its changes to `<folder>/<device id>.jsonl`
+/// and never touches another device's log, so a file-sync tool never has
+/// to merge a file. Logs are plain JSON even when the database is encrypted.
+///
+/// Local changes are found by comparing the tasks table against the digest
+/// of the last version this device wrote or merged, so edits made by
+/// commands and background jobs alike are picked up without hooks.
+pub struct SyncEngine {
+    state_path: PathBuf,
+}
+
+impl SyncEngine {
+    /// `state_path` holds this device's id and merge progress. It must not
+    /// live in the shared folder.
+    pub fn new(state_path: PathBuf) -> Self {
+        Self { state_path }
+    }
+
+    /// Appends local changes to this device's log, then merges every other
+    /// device's log into `tasks`. `database_id` identifies the database
+    /// behind `tasks`.
+    pub async fn sync(
+        &self,
+        folder: &Path,
+        database_id: &str,
+        tasks: &dyn TaskRepository,
+    ) -> Result<SyncReport> {
+        tokio::fs::create_dir_all(folder).await?;
+        let mut state = self.load_state().await?;
+
+        // After switching data directories or restoring a backup the state
+        // describes other data, and tasks missing from this database would
+        // be published as deletes. Join again as a new device instead; its
+        // tasks then meet the other devices' edits as ordinary conflicts.
+        if state
+            .database_id
+            .as_deref()
+            .is_some_and(|id| id != database_id)
+        {
+            info!("Database changed since the last sync, syncing as a new device");
+            state = SyncState::new();
+        }
+        state.database_id = Some(database_id.to_string());
+        let own_log = log_path(folder, &state.device_id);
+
+        // A new or emptied folder has none of our history: publish everything
+        // again and re-read whatever the other devices have written there
+        if state.clock.get(&state.device_id) > 0 && !tokio::fs::try_exists(&own_log).await? {
+            info!("Sync folder has no log for this device, republishing all tasks");
+            state.versions.clear();
+            state.applied.clear();
+        }
+
+        let mut report = SyncReport {
+            device_id: state.device_id.clone(),
+            ..Default::default()
+        };
+
+        let entries = collect_local_changes(&mut state, tasks).await?;
+        report.pushed = entries.len() as u32;
+        append_entries(&own_log, &entries).await?;
+        // The log now uses these sequence numbers; save the clock before
+        // merging so a failed merge cannot hand them out again
+        self.save_state(&state).await?;
+
+        let mut merged = Ok(());
+        for (device, path) in other_logs(folder, &state.device_id).await? {
+            merged = merge_log(&mut state, &device, &path, tasks, &mut report).await;
+            if merged.is_err() {
+                break;
+            }
+        }
+
+        // Keep what was merged before any failure
+        self.save_state(&state).await?;
+        merged?;
+
+        info!(
+            "Sync pushed {} and applied {} changes ({} conflicts)",
+            report.pushed, report.applied, report.conflicts
+        );
+        Ok(report)
+    }
+
+    async fn load_state(&self) -> Result<SyncState> {
+        match tokio::fs::read_to_string(&self.state_path).await {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SyncState::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save_state(&self, state: &SyncState) -> Result<()> {
+        if let Some(parent) = self.state_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let contents = serde_json::to_string(state)?;
+        let tmp_path = self.state_path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, contents).await?;
+        tokio::fs::rename(&tmp_path, &self.state_path).await?;
+        Ok(())
+    }
+}
+
+fn log_path(folder: &Path, device_id: &str) -> PathBuf {
+    folder.join(format!("{}.{}", device_id, LOG_EXTENSION))
+}
+
+fn digest(task: &Task) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(serde_json::to_vec(task)?)))
+}
+
+async fn collect_local_changes(
+    state: &mut SyncState,
+    tasks: &dyn TaskRepository,
+) -> Result<Vec<LogEntry>> {
+    let mut entries = Vec::new();
+    let mut present = HashSet::new();
+
+    for task in tasks.get_all_tasks().await? {
+        present.insert(task.id.clone());
+        let digest = digest(&task)?;
+        let known = state.versions.get(&task.id).and_then(|v| v.digest.as_ref());
+        if known == Some(&digest) {
+            continue;
+        }
+
+        let at = task.updated_at;
+        entries.push(state.record(
+            Change::Upsert {
+                task: Box::new(task),
+            },
+            at,
+            Some(digest),
+        ));
+    }
+
+    let missing: Vec<String> = state
+        .versions
+        .iter()
+        .filter(|(id, version)| version.digest.is_some() && !present.contains(*id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    if missing.is_empty() {
+        return Ok(entries);
+    }
+
+    // Archiving is local housekeeping: forget the task without telling the
+    // other devices to delete it
+    let archived: HashSet<String> = tasks
+        .get_archived_tasks(i32::MAX, 0)
+        .await?
+        .into_iter()
+        .map(|a| a.task.id)
+        .collect();
+    let now = Utc::now();
+    for id in missing {
+        if archived.contains(&id) {
+            if let Some(version) = state.versions.get_mut(&id) {
+                version.digest = None;
+            }
+            continue;
+        }
+
+        entries.push(state.record(Change::Delete { id }, now, None));
+    }
+
+    Ok(entries)
+}
+
+async fn append_entries(path: &Path, entries: &[LogEntry]) -> Result<()> {
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+
+    // Create the log even when empty; its absence means a fresh folder
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(lines.as_bytes()).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Other devices' logs in `folder`, sorted by device id.
+async fn other_logs(folder: &Path, device_id: &str) -> Result<Vec<(String, PathBuf)>> {
+    let mut logs = Vec::new();
+    let mut entries = tokio::fs::read_dir(folder).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(LOG_EXTENSION) {
+            continue;
+        }
+        if let Some(device) = path.file_stem().and_then(|s| s.to_str()) {
+            if device != device_id {
+                logs.push((device.to_string(), path));
+            }
+        }
+    }
+
+    logs.sort();
+    Ok(logs)
+}
+
+async fn merge_log(
+    state: &mut SyncState,
+    device: &str,
+    path: &Path,
+    tasks: &dyn TaskRepository,
+    report: &mut SyncReport,
+) -> Result<()> {
+    // A log this device cannot read now is retried next sync rather than
+    // holding up the others
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            report.warnings.push(format!(
+                "{}: unreadable ({}); will retry next sync",
+                path.display(),
+                e
+            ));
+            return Ok(());
+        }
+    };
+    let applied = state.applied.get(device).copied().unwrap_or(0);
+
+    for (index, line) in contents.split(|&b| b == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        // The file-sync tool may still be writing the tail of the file, and
+        // may have cut it mid-character
+        let entry: LogEntry = match serde_json::from_slice(line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.warnings.push(format!(
+                    "{}: line {} unreadable ({}); will retry next sync",
+                    path.display(),
+                    index + 1,
+                    e
+                ));
+                break;
+            }
+        };
+
+        if entry.device != device {
+            report.warnings.push(format!(
+                "{}: line {} belongs to device {}, skipped",
+                path.display(),
+                index + 1,
+                entry.device
+            ));
+            continue;
+        }
+        if entry.seq() <= applied {
+            continue;
+        }
+
+        // Only count the entry as merged once it has been applied
+        let (seq, clock) = (entry.seq(), entry.clock.clone());
+        apply_entry(state, entry, tasks, report).await?;
+        state.clock.merge(&clock);
+        state.applied.insert(device.to_string(), seq);
+    }
+
+    Ok(())
+}
+
+async fn apply_entry(
+    state: &mut SyncState,
+    entry: LogEntry,
+    tasks: &dyn TaskRepository,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let id = entry.change.task_id().to_string();
+    if let Some(current) = state.versions.get(&id) {
+        match entry.wins_over(current) {
+            Resolution::Apply => {}
+            Resolution::Stale => return Ok(()),
+            Resolution::ConflictWon => report.conflicts += 1,
+            Resolution::ConflictLost => {
+                report.conflicts += 1;
+                return Ok(());
+            }
+        }
+    }
+
+    let digest = match entry.change {
+        Change::Upsert { task } => {
+            // A task archived here stays archived while it is done, and comes
+            // back if another device reopened it
+            let task = *task;
+            let kept_archived = !task.is_open() && tasks.update_archived_task(task.clone()).await?;
+            if !kept_archived {
+                if tasks.get_task_by_id(&id).await?.is_some()
+                    || tasks.unarchive_task(&id).await?.is_some()
+                {
+                    tasks.update_task(task).await?;
+                } else {
+                    tasks.create_task(task).await?;
+                }
+            }
+
+            // Digest what was stored so the next sync does not see a change
+            match tasks.get_task_by_id(&id).await? {
+                Some(stored) => Some(digest(&stored)?),
+                None => None,
+            }
+        }
+        Change::Delete { .. } => {
+            tasks.delete_task(&id).await?;
+            None
+        }
+    };
+
+    state.versions.insert(
+        id,
+        Version {
+            clock: entry.clock,
+            device: entry.device,
+            at: entry.at,
+            digest,
+        },
+    );
+    report.applied += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::TaskStatus;
+    use crate::test_support::{task, task_at};
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    struct Device {
+        db: Database,
+        engine: SyncEngine,
+        _state_dir: TempDir,
+    }
+
+    impl Device {
+        async fn new() -> Self {
+            let state_dir = TempDir::new().unwrap();
+            Self {
+                db: Database::in_memory().await.unwrap(),
+                engine: SyncEngine::new(state_dir.path().join("sync_state.json")),
+                _state_dir: state_dir,
+            }
+        }
+
+        async fn sync(&self, folder: &Path) -> SyncReport {
+            let database_id = self.db.database_id().await.unwrap();
+            self.engine
+                .sync(folder, &database_id, &self.db)
+                .await
+                .unwrap()
+        }
+
+        async fn task(&self, id: &str) -> Option<Task> {
+            self.db.get_task_by_id(id).await.unwrap()
+        }
+
+        async fn edit(&self, id: &str, title: &str, at: DateTime<Utc>) {
+            let mut task = self.task(id).await.unwrap();
+            task.title = title.to_string();
+            task.updated_at = at;
+            self.db.update_task(task).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn two_devices_converge() {
+        let folder = TempDir::new().unwrap();
+        let (a, b) = (Device::new().await, Device::new().await);
+
+        // Push from one device, merge on the other
+        let shared = task("shared");
+        a.db.create_task(shared.clone()).await.unwrap();
+        assert_eq!(a.sync(folder.path()).await.pushed, 1);
+        let report = b.sync(folder.path()).await;
+        assert_eq!((report.pushed, report.applied), (0, 1));
+        assert_eq!(b.task(&shared.id).await.unwrap().title, "shared");
+
+        // Concurrent edits: the later one wins on both devices
+        let base = shared.updated_at;
+        a.edit(&shared.id, "edited on a", base + Duration::minutes(1))
+            .await;
+        b.edit(&shared.id, "edited on b", base + Duration::minutes(2))
+            .await;
+        a.sync(folder.path()).await;
+        let report = b.sync(folder.path()).await;
+        assert_eq!((report.applied, report.conflicts), (0, 1));
+        let report = a.sync(folder.path()).await;
+        assert_eq!((report.applied, report.conflicts), (1, 1));
+        assert_eq!(a.task(&shared.id).await.unwrap().title, "edited on b");
+        assert_eq!(b.task(&shared.id).await.unwrap().title, "edited on b");
+
+        // Nothing left to exchange
+        assert_eq!(a.sync(folder.path()).await.pushed, 0);
+        assert_eq!(b.sync(folder.path()).await.pushed, 0);
+
+        // Deletes propagate
+        b.db.delete_task(&shared.id).await.unwrap();
+        assert_eq!(b.sync(folder.path()).await.pushed, 1);
+        assert_eq!(a.sync(folder.path()).await.applied, 1);
+        assert!(a.task(&shared.id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn edits_to_a_task_archived_here_stay_in_the_archive() {
+        let folder = TempDir::new().unwrap();
+        let (a, b) = (Device::new().await, Device::new().await);
+
+        let completed = Utc::now() - Duration::days(100);
+        let done = task_at("done", completed, Some(completed));
+        a.db.create_task(done.clone()).await.unwrap();
+        a.sync(folder.path()).await;
+        b.sync(folder.path()).await;
+
+        // Archiving is not a delete
+        a.db.archive_tasks_older_than(Utc::now()).await.unwrap();
+        assert_eq!(a.sync(folder.path()).await.pushed, 0);
+
+        b.edit(&done.id, "done, renamed", Utc::now()).await;
+        b.sync(folder.path()).await;
+        assert_eq!(a.sync(folder.path()).await.applied, 1);
+        assert!(a.task(&done.id).await.is_none());
+        let archived = a.db.get_archived_tasks(10, 0).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].task.title, "done, renamed");
+
+        // Reopening it elsewhere brings it back
+        let mut reopened = b.task(&done.id).await.unwrap();
+        reopened.status = TaskStatus::Pending;
+        reopened.completed_at = None;
+        reopened.updated_at = Utc::now();
+        b.db.update_task(reopened).await.unwrap();
+        b.sync(folder.path()).await;
+        a.sync(folder.path()).await;
+        assert_eq!(
+            a.task(&done.id).await.map(|t| t.status),
+            Some(TaskStatus::Pending)
+        );
+        assert!(a.db.get_archived_tasks(10, 0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_different_database_syncs_as_a_new_device() {
+        let folder = TempDir::new().unwrap();
+        let device = Device::new().await;
+        let kept = task("kept");
+        device.db.create_task(kept.clone()).await.unwrap();
+        let first = device.sync(folder.path()).await;
+
+        // Same state file, other data: nothing is published as deleted
+        let other = Database::in_memory().await.unwrap();
+        let database_id = other.database_id().await.unwrap();
+        let report = device
+            .engine
+            .sync(folder.path(), &database_id, &other)
+            .await
+            .unwrap();
+        assert_ne!(report.device_id, first.device_id);
+        assert_eq!(report.pushed, 0);
+        assert_eq!(report.applied, 1);
+        assert!(other.get_task_by_id(&kept.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn a_failed_merge_does_not_reuse_sequence_numbers() {
+        let folder = TempDir::new().unwrap();
+        let (a, b) = (Device::new().await, Device::new().await);
+
+        let shared = task("shared");
+        a.db.create_task(shared.clone()).await.unwrap();
+        a.sync(folder.path()).await;
+        b.sync(folder.path()).await;
+        let from_b = task("from b");
+        b.db.create_task(from_b.clone()).await.unwrap();
+        b.sync(folder.path()).await;
+
+        // The edit reaches a's log, then merging b's task fails
+        a.edit(&shared.id, "first edit", Utc::now()).await;
+        a.db.set_read_only(true).await;
+        let database_id = a.db.database_id().await.unwrap();
+        assert!(a
+            .engine
+            .sync(folder.path(), &database_id, &a.db)
+            .await
+            .is_err());
+        a.db.set_read_only(false).await;
+        b.sync(folder.path()).await;
+        assert_eq!(b.task(&shared.id).await.unwrap().title, "first edit");
+
+        a.edit(&shared.id, "second edit", Utc::now()).await;
+        let report = a.sync(folder.path()).await;
+        assert_eq!((report.pushed, report.applied), (1, 1));
+        assert!(a.task(&from_b.id).await.is_some());
+        assert_eq!(b.sync(folder.path()).await.applied, 1);
+        assert_eq!(b.task(&shared.id).await.unwrap().title, "second edit");
+    }
+
+    #[tokio::test]
+    async fn an_unreadable_log_is_a_warning() {
+        let folder = TempDir::new().unwrap();
+        let (a, b) = (Device::new().await, Device::new().await);
+
+        let shared = task("shared");
+        a.db.create_task(shared.clone()).await.unwrap();
+        let device_a = a.sync(folder.path()).await.device_id;
+
+        // Cut the log mid-way through a multi-byte character
+        let path = log_path(folder.path(), &device_a);
+        let mut contents = tokio::fs::read(&path).await.unwrap();
+        contents.extend_from_slice(&[b'{', 0xE2, 0x82]);
+        tokio::fs::write(&path, contents).await.unwrap();
+
+        let report = b.sync(folder.path()).await;
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(b.task(&shared.id).await.is_some());
+    }
+}
@@ -0,0 +1,35 @@
+//! Builders shared by the unit tests.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{CreateTaskRequest, Importance, Priority, Task, TaskStatus};
+
+pub fn task(title: &str) -> Task {
+    Task::new(CreateTaskRequest {
+        title: title.to_string(),
+        description: None,
+        priority: Priority::Medium,
+        priority_pinned: false,
+        importance: Importance::Auto,
+        category: "work".to_string(),
+        project: None,
+        estimated_time: 30,
+        due_date: None,
+    })
+}
+
+/// A task created at `created_at` and, when given, completed at `completed_at`.
+pub fn task_at(
+    title: &str,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+) -> Task {
+    let mut task = task(title);
+    task.created_at = created_at;
+    task.updated_at = completed_at.unwrap_or(created_at);
+    if completed_at.is_some() {
+        task.status = TaskStatus::Completed;
+        task.completed_at = completed_at;
+    }
+    task
+}
@@ -0,0 +1,301 @@
+use crate::error::{AppError, Result};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::*;
+use crate::repository::{ImportBatch, Repositories};
+
+/// Bump when the bundle layout changes in a way older builds cannot read.
+pub const EXPORT_VERSION: u32 = 1;
+
+pub async fn export_bundle(repos: &Repositories, settings: &AppSettings) -> Result<ExportBundle> {
+    let mut settings = settings.clone();
+    // Paths are specific to this machine
+    settings.data_directory = None;
+    settings.sync.directory = None;
+
+    Ok(ExportBundle {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        tasks: repos.tasks.get_all_tasks().await?,
+        archived_tasks: repos.tasks.get_archived_tasks(i32::MAX, 0).await?,
+        insights: repos.insights.get_recent_insights(i32::MAX).await?,
+        notifications: repos.notifications.get_notifications(i32::MAX).await?,
+        settings: Some(settings),
+    })
+}
+
+pub fn bundle_to_json(bundle: &ExportBundle) -> Result<String> {
+    Ok(serde_json::to_string_pretty(bundle)?)
+}
+
+pub fn bundle_from_json(content: &str) -> Result<ExportBundle> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| {
+            AppError::validation("version", "Not a ChiCanDoIt export: missing version")
+        })?;
+
+    if version > EXPORT_VERSION as u64 {
+        return Err(AppError::Unsupported(format!(
+            "Export version {} is newer than this version of ChiCanDoIt supports ({})",
+            version, EXPORT_VERSION
+        )));
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Wraps tasks from a CSV file or another tool's export so they can go
+/// through `import_bundle`.
+pub fn tasks_bundle(tasks: Vec<Task>) -> ExportBundle {
+    ExportBundle {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        tasks,
+        archived_tasks: vec![],
+        insights: vec![],
+        notifications: vec![],
+        settings: None,
+    }
+}
+
+pub fn tasks_to_csv(tasks: &[Task]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for task in tasks {
+        writer
+            .serialize(task)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+pub fn tasks_from_csv(content: &str) -> Result<Vec<Task>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut tasks = Vec::new();
+    for (index, record) in reader.deserialize().enumerate() {
+        // Row 1 is the header
+        let task: Task = record
+            .map_err(|e| AppError::validation("content", format!("Row {}: {}", index + 2, e)))?;
+        tasks.push(task);
+    }
+
+    Ok(tasks)
+}
+
+/// Merges `bundle` into the repositories. A task is matched by id whether
+/// it is live or archived here, and an archived match is updated in the
+/// archive. Insights and notifications are never edited once written, so
+/// existing ones are skipped in every mode. Settings are only replaced when
+/// overwriting. Everything is written in one transaction; with `dry_run`
+/// nothing is written and the report describes what would have changed.
+pub async fn import_bundle(
+    repos: &Repositories,
+    bundle: ExportBundle,
+    mode: MergeMode,
+    dry_run: bool,
+) -> Result<(ImportReport, Option<AppSettings>)> {
+    let mut report = ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut batch = ImportBatch::default();
+
+    let archived: HashMap<String, Task> = repos
+        .tasks
+        .get_archived_tasks(i32::MAX, 0)
+        .await?
+        .into_iter()
+        .map(|a| (a.task.id.clone(), a.task))
+        .collect();
+    let incoming = bundle.tasks.into_iter().map(|task| (task, None)).chain(
+        bundle
+            .archived_tasks
+            .into_iter()
+            .map(|a| (a.task, Some(a.archived_at))),
+    );
+    let mut seen = HashSet::new();
+
+    for (task, archived_at) in incoming {
+        if !seen.insert(task.id.clone()) {
+            report.tasks.skipped += 1;
+            continue;
+        }
+
+        let (existing, in_archive) = match repos.tasks.get_task_by_id(&task.id).await? {
+            Some(existing) => (Some(existing), false),
+            None => (archived.get(&task.id).cloned(), true),
+        };
+        let replace = match (&existing, mode) {
+            (None, _) => {
+                report.tasks.created += 1;
+                match archived_at {
+                    Some(archived_at) => batch
+                        .created_archived_tasks
+                        .push(ArchivedTask { task, archived_at }),
+                    None => batch.created_tasks.push(task),
+                }
+                continue;
+            }
+            (Some(_), MergeMode::Skip) => false,
+            (Some(_), MergeMode::Overwrite) => true,
+            (Some(existing), MergeMode::KeepNewest) => task.updated_at > existing.updated_at,
+        };
+
+        if !replace {
+            report.tasks.skipped += 1;
+        } else if in_archive {
+            report.tasks.updated += 1;
+            batch.updated_archived_tasks.push(task);
+        } else {
+            report.tasks.updated += 1;
+            batch.updated_tasks.push(task);
+        }
+    }
+
+    let mut existing_insights: HashSet<String> = repos
+        .insights
+        .get_recent_insights(i32::MAX)
+        .await?
+        .into_iter()
+        .map(|i| i.id)
+        .collect();
+    for insight in bundle.insights {
+        if !existing_insights.insert(insight.id.clone()) {
+            report.insights.skipped += 1;
+            continue;
+        }
+
+        report.insights.created += 1;
+        batch.insights.push(insight);
+    }
+
+    let mut existing_notifications: HashSet<String> = repos
+        .notifications
+        .get_notifications(i32::MAX)
+        .await?
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    for notification in bundle.notifications {
+        if !existing_notifications.insert(notification.id.clone()) {
+            report.notifications.skipped += 1;
+            continue;
+        }
+
+        report.notifications.created += 1;
+        batch.notifications.push(notification);
+    }
+
+    if !dry_run {
+        repos.imports.apply_import(batch).await?;
+    }
+
+    let settings = match (bundle.settings, mode) {
+        (Some(settings), MergeMode::Overwrite) => {
+            report.settings_updated = true;
+            Some(settings)
+        }
+        _ => None,
+    };
+
+    Ok((report, settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::test_support::{task, task_at};
+    use chrono::TimeZone;
+
+    async fn sqlite_repos() -> Repositories {
+        Repositories::sqlite(&Database::in_memory().await.unwrap())
+    }
+
+    /// One live task and one archived task.
+    async fn seeded_repos() -> (Repositories, Task, Task) {
+        let repos = sqlite_repos().await;
+        let completed = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let done = task_at("done", completed, Some(completed));
+        let open = task("open");
+        repos.tasks.create_task(done.clone()).await.unwrap();
+        repos.tasks.create_task(open.clone()).await.unwrap();
+        repos
+            .tasks
+            .archive_tasks_older_than(Utc::now())
+            .await
+            .unwrap();
+        (repos, open, done)
+    }
+
+    #[tokio::test]
+    async fn export_round_trips_the_archive() {
+        let (repos, open, done) = seeded_repos().await;
+        let bundle = export_bundle(&repos, &AppSettings::default())
+            .await
+            .unwrap();
+        assert_eq!(bundle.tasks.len(), 1);
+        assert_eq!(bundle.archived_tasks.len(), 1);
+
+        let target = sqlite_repos().await;
+        let (report, _) = import_bundle(&target, bundle, MergeMode::Skip, false)
+            .await
+            .unwrap();
+        assert_eq!(report.tasks.created, 2);
+
+        let live = target.tasks.get_all_tasks().await.unwrap();
+        let archived = target.tasks.get_archived_tasks(10, 0).await.unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].id, open.id);
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].task.id, done.id);
+    }
+
+    #[tokio::test]
+    async fn import_updates_archived_tasks_in_place() {
+        let (repos, _, mut done) = seeded_repos().await;
+
+        // Exported before it was archived, then edited elsewhere
+        done.title = "done elsewhere".to_string();
+        let bundle = tasks_bundle(vec![done.clone()]);
+        let (report, _) = import_bundle(&repos, bundle, MergeMode::Overwrite, false)
+            .await
+            .unwrap();
+        assert_eq!(report.tasks.updated, 1);
+        assert_eq!(report.tasks.created, 0);
+
+        let live = repos.tasks.get_all_tasks().await.unwrap();
+        assert!(live.iter().all(|t| t.id != done.id));
+        let archived = repos.tasks.get_archived_tasks(10, 0).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].task.title, "done elsewhere");
+
+        // Unarchiving must still work, which it would not with a live copy
+        assert!(repos
+            .tasks
+            .unarchive_task(&done.id)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn import_writes_nothing_when_any_record_fails() {
+        let repos = sqlite_repos().await;
+        let first = task("first");
+        let batch = ImportBatch {
+            created_tasks: vec![first.clone(), first.clone()],
+            ..Default::default()
+        };
+
+        assert!(repos.imports.apply_import(batch).await.is_err());
+        assert!(repos.tasks.get_all_tasks().await.unwrap().is_empty());
+    }
+}
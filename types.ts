@@ -57,4 +57,21 @@ export interface ProductivityStats {
   score?: number;
   streak?: number;
   focusTime?: string;
-}
\ No newline at end of file
+}
+// Rejection value of every Tauri command
+export interface AppError {
+  code:
+    | 'not_found'
+    | 'validation'
+    | 'conflict'
+    | 'unsupported'
+    | 'locked'
+    | 'wrong_passphrase'
+    | 'busy'
+    | 'database'
+    | 'ai'
+    | 'io'
+    | 'internal';
+  message: string;
+  details: { entity?: string; id?: string; field?: string } | null;
+}